@@ -1,11 +1,20 @@
+use super::file::{
+    create_document_backup, get_sidecar_path, lock_sidecar, parse_sidecar, read_existing_sidecar,
+    save_sidecar, update_app_settings, write_atomic,
+};
+use crate::models::Sidecar;
+use base64::Engine;
 use keyring::Entry;
-use reqwest::Client;
+use reqwest::{Client, Url};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Mutex;
-use tauri::AppHandle;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_opener::OpenerExt;
 
 const SERVICE_NAME: &str = "writecraft";
@@ -15,6 +24,14 @@ const AUTH_ACCOUNT_NAME: &str = "supabase-auth";
 static AUTH_FALLBACK_STORAGE: std::sync::LazyLock<Mutex<HashMap<String, String>>> =
     std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
 
+// Last subscription info fetched, so model access can be checked and polling
+// answered without a round-trip
+static SUBSCRIPTION_CACHE: std::sync::LazyLock<Mutex<Option<CachedSubscription>>> =
+    std::sync::LazyLock::new(|| Mutex::new(None));
+
+// Set while a background subscription refresh is in flight
+static SUBSCRIPTION_REFRESHING: AtomicBool = AtomicBool::new(false);
+
 // ============================================
 // Error types
 // ============================================
@@ -25,6 +42,8 @@ pub enum AuthError {
     AuthFailed(String),
     #[error("Network error: {0}")]
     Network(String),
+    #[error("No internet connection")]
+    Offline,
     #[error("Storage error: {0}")]
     Storage(String),
     #[error("Not authenticated")]
@@ -37,14 +56,60 @@ pub enum AuthError {
     EmailNotConfirmed,
     #[error("User already exists")]
     UserAlreadyExists,
+    #[error("Invalid OAuth state")]
+    InvalidOAuthState,
+    #[error("Invalid or expired code")]
+    InvalidOtp,
+    /// Carries the seconds to wait, when Supabase says
+    #[error("{}", rate_limit_message(.0))]
+    RateLimited(Option<u64>),
+    #[error("No avatar")]
+    AvatarNotFound,
+    #[error("Sign-in cancelled")]
+    Cancelled,
+    #[error("The document has changed both here and in the cloud")]
+    CloudConflict,
+    #[error("Could not check the fizz:// link registration: {0}")]
+    DeepLinkCheck(String),
+}
+
+fn rate_limit_message(retry_after_secs: &Option<u64>) -> String {
+    match retry_after_secs {
+        Some(secs) => format!("Too many requests, please try again in {} seconds", secs),
+        None => "Too many requests, please wait before trying again".to_string(),
+    }
 }
 
+/// Most errors reach the frontend as their message. `RateLimited` becomes
+/// `{ kind: "rateLimited", message, retryAfter }` so the UI can count down.
 impl serde::Serialize for AuthError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        use serde::ser::SerializeStruct;
+
+        match self {
+            AuthError::RateLimited(retry_after) => {
+                let mut error = serializer.serialize_struct("AuthError", 3)?;
+                error.serialize_field("kind", "rateLimited")?;
+                error.serialize_field("message", &self.to_string())?;
+                error.serialize_field("retryAfter", retry_after)?;
+                error.end()
+            }
+            _ => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AuthError {
+    fn from(e: reqwest::Error) -> Self {
+        // Connect errors cover DNS failures, refused connections and no route
+        if e.is_connect() {
+            AuthError::Offline
+        } else {
+            AuthError::Network(e.to_string())
+        }
     }
 }
 
@@ -61,6 +126,22 @@ pub struct AuthSession {
     pub user: AuthUser,
 }
 
+/// What changed in an 'auth-state-changed' event
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthStatus {
+    SignedIn,
+    SignedOut,
+    Refreshed,
+}
+
+/// Payload of the 'auth-state-changed' event
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthStateChange {
+    pub status: AuthStatus,
+    pub user: Option<AuthUser>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthUser {
@@ -158,6 +239,71 @@ struct SupabaseError {
     error_description: Option<String>,
     message: Option<String>,
     msg: Option<String>,
+    error_code: Option<String>,
+}
+
+impl SupabaseError {
+    /// Parse an error response, tolerating bodies that aren't JSON
+    async fn from_response(response: reqwest::Response) -> Self {
+        response.json().await.unwrap_or(SupabaseError {
+            error: Some("Unknown error".to_string()),
+            error_description: None,
+            message: None,
+            msg: None,
+            error_code: None,
+        })
+    }
+
+    fn into_message(self) -> String {
+        self.message
+            .or(self.error_description)
+            .or(self.msg)
+            .or(self.error)
+            .unwrap_or_else(|| "Unknown error".to_string())
+    }
+
+    /// Supabase answers 429 with `over_email_send_rate_limit` when it won't
+    /// send another email yet. The wait comes from the Retry-After header or
+    /// a message like "you can only request this after 42 seconds".
+    fn email_rate_limit(
+        &self,
+        status: reqwest::StatusCode,
+        retry_after: Option<u64>,
+    ) -> Option<AuthError> {
+        let rate_limited = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || self.error_code.as_deref() == Some("over_email_send_rate_limit");
+        if !rate_limited {
+            return None;
+        }
+
+        let from_message = [&self.message, &self.msg, &self.error_description]
+            .into_iter()
+            .flatten()
+            .find_map(|m| parse_retry_seconds(m));
+        Some(AuthError::RateLimited(retry_after.or(from_message)))
+    }
+}
+
+/// The number just before "second(s)" in a message, if any
+fn parse_retry_seconds(message: &str) -> Option<u64> {
+    let (before, _) = message.split_once(" second")?;
+    before
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Seconds from a Retry-After header given as a delay
+fn retry_after_header(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
 }
 
 // ============================================
@@ -172,6 +318,11 @@ fn auth_fallback_key() -> String {
     format!("{}:{}", SERVICE_NAME, AUTH_ACCOUNT_NAME)
 }
 
+/// Forget the session held in memory; the keychain and session file are untouched
+pub(crate) fn clear_auth_fallback_storage() {
+    AUTH_FALLBACK_STORAGE.lock().unwrap().clear();
+}
+
 // ============================================
 // File-based persistent fallback
 // ============================================
@@ -211,6 +362,8 @@ fn clear_session_file() {
 
 fn save_session(session: &AuthSession) -> Result<(), AuthError> {
     let json = serde_json::to_string(session).map_err(|e| AuthError::Storage(e.to_string()))?;
+    // Signing in again unlocks an idle-locked session
+    set_session_locked(false);
 
     // Try keychain first
     if let Ok(entry) = get_auth_entry() {
@@ -238,6 +391,10 @@ fn save_session(session: &AuthSession) -> Result<(), AuthError> {
 }
 
 fn load_session() -> Option<AuthSession> {
+    if SESSION_LOCKED.load(Ordering::SeqCst) {
+        return None;
+    }
+
     // Try keychain first
     if let Ok(entry) = get_auth_entry() {
         match entry.get_password() {
@@ -288,6 +445,9 @@ fn clear_session() {
     let mut storage = AUTH_FALLBACK_STORAGE.lock().unwrap();
     storage.remove(&auth_fallback_key());
 
+    *SUBSCRIPTION_CACHE.lock().unwrap() = None;
+    set_session_locked(false);
+
     tracing::info!("Session cleared from all storage locations");
 }
 
@@ -296,14 +456,44 @@ fn clear_session() {
 // ============================================
 
 const SUPABASE_URL: &str = "https://ausxaxibmaztljouwhyx.supabase.co";
+// Modern publishable key format (replaces legacy JWT anon key)
 const SUPABASE_ANON_KEY: &str = "sb_publishable_cYznAZlD3GCHzRs4SWxtNw_L9pFRJal";
+/// Override the Supabase project, e.g. for self-hosting or staging
+const SUPABASE_URL_ENV: &str = "WRITECRAFT_SUPABASE_URL";
+const SUPABASE_ANON_KEY_ENV: &str = "WRITECRAFT_SUPABASE_ANON_KEY";
+
+/// A non-empty environment variable, trimmed
+fn env_override(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Supabase project URL, honoring `WRITECRAFT_SUPABASE_URL`
+pub(crate) fn get_supabase_url() -> Result<String, AuthError> {
+    let Some(url) = env_override(SUPABASE_URL_ENV) else {
+        return Ok(SUPABASE_URL.to_string());
+    };
 
-fn get_supabase_url() -> Result<String, AuthError> {
-    Ok(SUPABASE_URL.to_string())
+    let parsed = Url::parse(&url).map_err(|e| {
+        AuthError::AuthFailed(format!(
+            "{} is not a valid URL ({}): {}",
+            SUPABASE_URL_ENV, e, url
+        ))
+    })?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(AuthError::AuthFailed(format!(
+            "{} must be an http(s) URL: {}",
+            SUPABASE_URL_ENV, url
+        )));
+    }
+    Ok(url.trim_end_matches('/').to_string())
 }
 
-fn get_supabase_anon_key() -> Result<String, AuthError> {
-    Ok(SUPABASE_ANON_KEY.to_string())
+/// Supabase anon key, honoring `WRITECRAFT_SUPABASE_ANON_KEY`
+pub(crate) fn get_supabase_anon_key() -> Result<String, AuthError> {
+    Ok(env_override(SUPABASE_ANON_KEY_ENV).unwrap_or_else(|| SUPABASE_ANON_KEY.to_string()))
 }
 
 // ============================================
@@ -312,26 +502,39 @@ fn get_supabase_anon_key() -> Result<String, AuthError> {
 
 /// Sign up with email and password
 #[tauri::command]
-pub async fn sign_up(email: String, password: String) -> Result<AuthSession, AuthError> {
+pub async fn sign_up(
+    app: AppHandle,
+    client: State<'_, Client>,
+    oauth: State<'_, OAuthFlowState>,
+    email: String,
+    password: String,
+    full_name: Option<String>,
+) -> Result<AuthSession, AuthError> {
+    let generation = oauth.generation();
+    // Supabase stores `data` as the user's metadata
+    let mut body = serde_json::json!({
+        "email": email,
+        "password": password
+    });
+    if let Some(full_name) = full_name.filter(|n| !n.trim().is_empty()) {
+        body["data"] = serde_json::json!({ "full_name": full_name.trim() });
+    }
+
     let supabase_url = get_supabase_url()?;
     let anon_key = get_supabase_anon_key()?;
 
-    let client = Client::new();
     let response = client
         .post(format!("{}/auth/v1/signup", supabase_url))
         .header("apikey", &anon_key)
         .header("Content-Type", "application/json")
-        .json(&serde_json::json!({
-            "email": email,
-            "password": password
-        }))
+        .json(&body)
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
     let status = response.status();
 
     if !status.is_success() {
+        let retry_after = retry_after_header(&response);
         let error: SupabaseError = response
             .json()
             .await
@@ -340,8 +543,14 @@ pub async fn sign_up(email: String, password: String) -> Result<AuthSession, Aut
                 error_description: None,
                 message: None,
                 msg: None,
+                error_code: None,
             });
 
+        // Signing up sends a confirmation email
+        if let Some(rate_limited) = error.email_rate_limit(status, retry_after) {
+            return Err(rate_limited);
+        }
+
         let error_msg = error
             .message
             .or(error.error_description)
@@ -362,20 +571,28 @@ pub async fn sign_up(email: String, password: String) -> Result<AuthSession, Aut
         .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
 
     let session = convert_auth_response(auth_response)?;
+    oauth.check_not_cancelled(generation)?;
 
     // Save session to keychain
     save_session(&session)?;
+    on_signed_in(&app, &session);
 
     Ok(session)
 }
 
 /// Sign in with email and password
 #[tauri::command]
-pub async fn sign_in(email: String, password: String) -> Result<AuthSession, AuthError> {
+pub async fn sign_in(
+    app: AppHandle,
+    client: State<'_, Client>,
+    oauth: State<'_, OAuthFlowState>,
+    email: String,
+    password: String,
+) -> Result<AuthSession, AuthError> {
+    let generation = oauth.generation();
     let supabase_url = get_supabase_url()?;
     let anon_key = get_supabase_anon_key()?;
 
-    let client = Client::new();
     let response = client
         .post(format!(
             "{}/auth/v1/token?grant_type=password",
@@ -388,8 +605,7 @@ pub async fn sign_in(email: String, password: String) -> Result<AuthSession, Aut
             "password": password
         }))
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
     let status = response.status();
 
@@ -399,6 +615,7 @@ pub async fn sign_in(email: String, password: String) -> Result<AuthSession, Aut
             error_description: None,
             message: None,
             msg: None,
+            error_code: None,
         });
 
         let error_msg = error
@@ -424,26 +641,225 @@ pub async fn sign_in(email: String, password: String) -> Result<AuthSession, Aut
         .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
 
     let session = convert_auth_response(auth_response)?;
+    oauth.check_not_cancelled(generation)?;
+
+    // Save session to keychain
+    save_session(&session)?;
+    on_signed_in(&app, &session);
+
+    Ok(session)
+}
+
+/// Email a one-time sign-in code (magic link) to the user
+#[tauri::command]
+pub async fn sign_in_with_otp(client: State<'_, Client>, email: String) -> Result<(), AuthError> {
+    let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
+
+    let response = client
+        .post(format!("{}/auth/v1/otp", supabase_url))
+        .header("apikey", &anon_key)
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "email": email
+        }))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let error = SupabaseError::from_response(response).await;
+        return Err(AuthError::AuthFailed(error.into_message()));
+    }
+
+    Ok(())
+}
+
+/// Sign in with the one-time code sent by `sign_in_with_otp`
+#[tauri::command]
+pub async fn verify_otp(
+    app: AppHandle,
+    client: State<'_, Client>,
+    oauth: State<'_, OAuthFlowState>,
+    email: String,
+    token: String,
+) -> Result<AuthSession, AuthError> {
+    let generation = oauth.generation();
+    let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
+
+    let response = client
+        .post(format!("{}/auth/v1/verify", supabase_url))
+        .header("apikey", &anon_key)
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "type": "email",
+            "email": email,
+            "token": token
+        }))
+        .send()
+        .await?;
+
+    let status = response.status();
+
+    if !status.is_success() {
+        let error = SupabaseError::from_response(response).await;
+
+        // Supabase answers 403 for expired or wrong codes
+        let invalid_token = status.as_u16() == 403
+            || matches!(error.error_code.as_deref(), Some("otp_expired" | "otp_disabled"));
+        if invalid_token {
+            return Err(AuthError::InvalidOtp);
+        }
+
+        return Err(AuthError::AuthFailed(error.into_message()));
+    }
+
+    let auth_response: SupabaseAuthResponse = response
+        .json()
+        .await
+        .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
+
+    let session = convert_auth_response(auth_response)?;
+    oauth.check_not_cancelled(generation)?;
 
     // Save session to keychain
     save_session(&session)?;
+    on_signed_in(&app, &session);
 
     Ok(session)
 }
 
-/// Get OAuth URL for sign in with provider
+/// Send a new signup confirmation email
+#[tauri::command]
+pub async fn resend_confirmation(
+    client: State<'_, Client>,
+    email: String,
+) -> Result<(), AuthError> {
+    let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
+
+    let response = client
+        .post(format!("{}/auth/v1/resend", supabase_url))
+        .header("apikey", &anon_key)
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "type": "signup",
+            "email": email
+        }))
+        .send()
+        .await?;
+
+    let status = response.status();
+
+    if !status.is_success() {
+        let retry_after = retry_after_header(&response);
+        let error = SupabaseError::from_response(response).await;
+
+        if let Some(rate_limited) = error.email_rate_limit(status, retry_after) {
+            return Err(rate_limited);
+        }
+
+        return Err(AuthError::AuthFailed(error.into_message()));
+    }
+
+    Ok(())
+}
+
+// ============================================
+// OAuth (PKCE)
+// ============================================
+
+const OAUTH_REDIRECT_URL: &str = "fizz://auth/callback";
+/// Scheme of `OAUTH_REDIRECT_URL`, which the OS must route back to the app
+const DEEP_LINK_SCHEME: &str = "fizz";
+/// How long a started OAuth sign-in stays valid
+const OAUTH_FLOW_TTL: Duration = Duration::from_secs(10 * 60);
+
+struct PendingOAuth {
+    code_verifier: String,
+    started_at: Instant,
+}
+
+/// OAuth sign-ins in progress, keyed by their `state` parameter
+#[derive(Default)]
+pub struct OAuthFlowState {
+    pending: Mutex<HashMap<String, PendingOAuth>>,
+    /// States of flows dropped by `cancel_pending_auth`, so a late callback
+    /// reports the cancellation rather than an invalid state
+    cancelled: Mutex<HashSet<String>>,
+    /// Bumped on cancel; a sign-in that sees it change while its request is
+    /// in flight discards the result
+    generation: AtomicU64,
+}
+
+impl OAuthFlowState {
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Fail with `Cancelled` if `cancel_pending_auth` ran since `generation`
+    fn check_not_cancelled(&self, generation: u64) -> Result<(), AuthError> {
+        if self.generation() == generation {
+            Ok(())
+        } else {
+            Err(AuthError::Cancelled)
+        }
+    }
+}
+
+/// A random URL-safe string from two v4 UUIDs (244 random bits)
+fn random_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// S256 PKCE challenge for a code verifier
+fn pkce_challenge(code_verifier: &str) -> String {
+    let digest = Sha256::digest(code_verifier.as_bytes());
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest)
+}
+
+/// Get OAuth URL for sign in with provider.
+/// Starts a PKCE flow; the callback must come back with the same `state`.
 #[tauri::command]
-pub async fn sign_in_with_oauth(provider: String) -> Result<String, AuthError> {
+pub async fn sign_in_with_oauth(
+    oauth: State<'_, OAuthFlowState>,
+    provider: String,
+) -> Result<String, AuthError> {
     let supabase_url = get_supabase_url()?;
 
-    // Construct OAuth URL with redirect to custom URL scheme
-    let redirect_url = "fizz://auth/callback";
-    let oauth_url = format!(
-        "{}/auth/v1/authorize?provider={}&redirect_to={}",
-        supabase_url, provider, redirect_url
+    let state = random_token();
+    let code_verifier = random_token();
+    let code_challenge = pkce_challenge(&code_verifier);
+
+    // Supabase keeps the redirect's query string, so the state rides along there
+    let redirect_url = format!("{}?state={}", OAUTH_REDIRECT_URL, state);
+    let oauth_url = Url::parse_with_params(
+        &format!("{}/auth/v1/authorize", supabase_url),
+        &[
+            ("provider", provider.as_str()),
+            ("redirect_to", redirect_url.as_str()),
+            ("code_challenge", code_challenge.as_str()),
+            ("code_challenge_method", "s256"),
+        ],
+    )
+    .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
+
+    oauth.cancelled.lock().unwrap().clear();
+    let mut pending = oauth.pending.lock().unwrap();
+    pending.retain(|_, flow| flow.started_at.elapsed() < OAUTH_FLOW_TTL);
+    pending.insert(
+        state,
+        PendingOAuth {
+            code_verifier,
+            started_at: Instant::now(),
+        },
     );
 
-    Ok(oauth_url)
+    Ok(oauth_url.to_string())
 }
 
 /// Open OAuth URL in default browser
@@ -455,97 +871,117 @@ pub async fn open_oauth_url(app: AppHandle, url: String) -> Result<(), AuthError
     Ok(())
 }
 
-/// Handle OAuth callback
+/// Handle OAuth callback.
+/// Validates the `state` against the pending sign-in and exchanges the
+/// authorization code using its PKCE verifier.
 #[tauri::command]
-pub async fn handle_oauth_callback(url: String) -> Result<AuthSession, AuthError> {
-    // Parse the callback URL to extract tokens
-    // URL format: fizz://auth/callback#access_token=...&refresh_token=...&expires_in=...
-
-    let fragment = url
-        .split('#')
-        .nth(1)
-        .ok_or_else(|| AuthError::AuthFailed("No fragment in callback URL".to_string()))?;
-
-    let params: HashMap<String, String> = fragment
-        .split('&')
-        .filter_map(|pair| {
-            let mut parts = pair.split('=');
-            Some((parts.next()?.to_string(), parts.next()?.to_string()))
-        })
-        .collect();
-
-    let access_token = params
-        .get("access_token")
-        .ok_or_else(|| AuthError::AuthFailed("No access token in callback".to_string()))?
-        .clone();
-
-    let refresh_token = params
-        .get("refresh_token")
-        .ok_or_else(|| AuthError::AuthFailed("No refresh token in callback".to_string()))?
-        .clone();
+pub async fn handle_oauth_callback(
+    app: AppHandle,
+    client: State<'_, Client>,
+    oauth: State<'_, OAuthFlowState>,
+    url: String,
+) -> Result<AuthSession, AuthError> {
+    // URL format: fizz://auth/callback?state=...&code=...
+    let callback_url =
+        Url::parse(&url).map_err(|_| AuthError::AuthFailed("Invalid callback URL".to_string()))?;
+    let params: HashMap<String, String> = callback_url.query_pairs().into_owned().collect();
+
+    // Each state is single use, whether or not the rest of the callback is valid
+    let state = params.get("state").ok_or(AuthError::InvalidOAuthState)?;
+    if oauth.cancelled.lock().unwrap().remove(state) {
+        return Err(AuthError::Cancelled);
+    }
+    let generation = oauth.generation();
+    let flow = oauth
+        .pending
+        .lock()
+        .unwrap()
+        .remove(state)
+        .filter(|flow| flow.started_at.elapsed() < OAUTH_FLOW_TTL)
+        .ok_or(AuthError::InvalidOAuthState)?;
+
+    if let Some(error) = params.get("error_description").or(params.get("error")) {
+        return Err(AuthError::AuthFailed(error.clone()));
+    }
 
-    let expires_in: i64 = params
-        .get("expires_in")
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(3600);
+    let code = params
+        .get("code")
+        .ok_or_else(|| AuthError::AuthFailed("No authorization code in callback".to_string()))?;
 
-    // Get user info using the access token
     let supabase_url = get_supabase_url()?;
     let anon_key = get_supabase_anon_key()?;
 
-    let client = Client::new();
     let response = client
-        .get(format!("{}/auth/v1/user", supabase_url))
+        .post(format!("{}/auth/v1/token?grant_type=pkce", supabase_url))
         .header("apikey", &anon_key)
-        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "auth_code": code,
+            "code_verifier": flow.code_verifier
+        }))
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
     if !response.status().is_success() {
-        return Err(AuthError::AuthFailed("Failed to get user info".to_string()));
+        return Err(AuthError::AuthFailed(
+            "Failed to exchange authorization code".to_string(),
+        ));
     }
 
-    let user: SupabaseUser = response
+    let auth_response: SupabaseAuthResponse = response
         .json()
         .await
         .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
 
-    let now = chrono::Utc::now().timestamp();
-    let expires_at = now + expires_in;
-
-    let session = AuthSession {
-        access_token,
-        refresh_token,
-        expires_at,
-        user: AuthUser {
-            id: user.id,
-            email: user.email.unwrap_or_default(),
-            full_name: user
-                .user_metadata
-                .as_ref()
-                .and_then(|m| m.full_name.clone().or_else(|| m.name.clone())),
-            avatar_url: user.user_metadata.as_ref().and_then(|m| m.avatar_url.clone()),
-            email_confirmed: user.email_confirmed_at.is_some(),
-        },
-    };
+    let session = convert_auth_response(auth_response)?;
+    oauth.check_not_cancelled(generation)?;
 
     // Save session to keychain
     save_session(&session)?;
+    on_signed_in(&app, &session);
 
     Ok(session)
 }
 
+/// Abandon any sign-in in progress, e.g. after the user closed the browser.
+/// Pending OAuth state and PKCE verifiers are dropped; a sign-in request
+/// still in flight, or a callback that arrives later, returns `Cancelled`
+/// instead of signing in.
+#[tauri::command]
+pub fn cancel_pending_auth(oauth: State<'_, OAuthFlowState>) {
+    oauth.generation.fetch_add(1, Ordering::SeqCst);
+    let states: Vec<String> = oauth
+        .pending
+        .lock()
+        .unwrap()
+        .drain()
+        .map(|(state, _)| state)
+        .collect();
+    if !states.is_empty() {
+        tracing::info!(flows = states.len(), "Cancelled pending sign-in");
+    }
+    oauth.cancelled.lock().unwrap().extend(states);
+}
+
+// ============================================
+// Session commands
+// ============================================
+
 /// Sign out and clear session
 #[tauri::command]
-pub async fn sign_out() -> Result<(), AuthError> {
+pub async fn sign_out(
+    app: AppHandle,
+    client: State<'_, Client>,
+    refresher: State<'_, SessionRefresher>,
+) -> Result<(), AuthError> {
+    refresher.stop();
+
     // Clear local session
     clear_session();
 
     // Try to sign out on server (optional, don't fail if it doesn't work)
     if let Some(session) = load_session() {
         if let (Ok(supabase_url), Ok(anon_key)) = (get_supabase_url(), get_supabase_anon_key()) {
-            let client = Client::new();
             let _ = client
                 .post(format!("{}/auth/v1/logout", supabase_url))
                 .header("apikey", &anon_key)
@@ -555,12 +991,54 @@ pub async fn sign_out() -> Result<(), AuthError> {
         }
     }
 
+    emit_auth_state(&app, AuthStatus::SignedOut, None);
+
+    Ok(())
+}
+
+/// Permanently delete the signed-in user's account, then clear the local
+/// session and stored API key. Emits 'account-deleted' on success.
+#[tauri::command]
+pub async fn delete_account(
+    app: AppHandle,
+    client: State<'_, Client>,
+    refresher: State<'_, SessionRefresher>,
+) -> Result<(), AuthError> {
+    load_session().ok_or(AuthError::NotAuthenticated)?;
+    let access_token = get_access_token(&client).await?;
+    let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
+
+    // Users can't delete themselves with the anon key, so an edge function
+    // does it with the service role
+    let response = client
+        .post(format!("{}/functions/v1/delete-account", supabase_url))
+        .header("apikey", &anon_key)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({}))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(AuthError::AuthFailed("Failed to delete account".to_string()));
+    }
+
+    refresher.stop();
+    clear_session();
+    // The account is gone either way, so a key that can't be deleted is only
+    // logged and the UI still signs out
+    super::keychain::delete_all_api_keys();
+
+    let _ = app.emit("account-deleted", ());
+    emit_auth_state(&app, AuthStatus::SignedOut, None);
+
     Ok(())
 }
 
 /// Get current session if valid
 #[tauri::command]
-pub async fn get_session() -> Result<Option<AuthSession>, AuthError> {
+pub async fn get_session(client: State<'_, Client>) -> Result<Option<AuthSession>, AuthError> {
     let session = match load_session() {
         Some(s) => s,
         None => return Ok(None),
@@ -570,12 +1048,10 @@ pub async fn get_session() -> Result<Option<AuthSession>, AuthError> {
     let now = chrono::Utc::now().timestamp();
     if session.expires_at <= now {
         // Try to refresh
-        match refresh_session_internal(&session.refresh_token).await {
+        match refresh_session_internal(&client, &session.refresh_token).await {
             Ok(new_session) => Ok(Some(new_session)),
-            Err(_) => {
-                clear_session();
-                Ok(None)
-            }
+            Err(AuthError::SessionExpired) => Ok(None),
+            Err(e) => Err(e),
         }
     } else {
         Ok(Some(session))
@@ -584,16 +1060,53 @@ pub async fn get_session() -> Result<Option<AuthSession>, AuthError> {
 
 /// Refresh the current session
 #[tauri::command]
-pub async fn refresh_session() -> Result<AuthSession, AuthError> {
+pub async fn refresh_session(
+    app: AppHandle,
+    client: State<'_, Client>,
+) -> Result<AuthSession, AuthError> {
     let session = load_session().ok_or(AuthError::NotAuthenticated)?;
-    refresh_session_internal(&session.refresh_token).await
+    match refresh_session_internal(&client, &session.refresh_token).await {
+        Ok(new_session) => {
+            emit_auth_state(&app, AuthStatus::Refreshed, Some(new_session.user.clone()));
+            Ok(new_session)
+        }
+        Err(AuthError::SessionExpired) => {
+            emit_auth_state(&app, AuthStatus::SignedOut, None);
+            Err(AuthError::SessionExpired)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Refresh the session and store the result: the new session on success,
+/// or nothing once the refresh token is rejected
+async fn refresh_session_internal(
+    client: &Client,
+    refresh_token: &str,
+) -> Result<AuthSession, AuthError> {
+    match request_session_refresh(client, refresh_token).await {
+        Ok(session) => {
+            save_session(&session)?;
+            Ok(session)
+        }
+        Err(AuthError::SessionExpired) => {
+            clear_session();
+            Err(AuthError::SessionExpired)
+        }
+        Err(e) => Err(e),
+    }
 }
 
-async fn refresh_session_internal(refresh_token: &str) -> Result<AuthSession, AuthError> {
+/// Exchange a refresh token for a new session without touching storage.
+/// Only a 4xx answer means the token was rejected (`SessionExpired`); a
+/// server error is reported as a network error, so the session survives it.
+async fn request_session_refresh(
+    client: &Client,
+    refresh_token: &str,
+) -> Result<AuthSession, AuthError> {
     let supabase_url = get_supabase_url()?;
     let anon_key = get_supabase_anon_key()?;
 
-    let client = Client::new();
     let response = client
         .post(format!(
             "{}/auth/v1/token?grant_type=refresh_token",
@@ -605,78 +1118,354 @@ async fn refresh_session_internal(refresh_token: &str) -> Result<AuthSession, Au
             "refresh_token": refresh_token
         }))
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
-    if !response.status().is_success() {
-        clear_session();
+    let status = response.status();
+    if status.is_client_error() {
         return Err(AuthError::SessionExpired);
     }
+    if !status.is_success() {
+        return Err(AuthError::Network(format!(
+            "Session refresh failed ({})",
+            status
+        )));
+    }
 
     let auth_response: SupabaseAuthResponse = response
         .json()
         .await
         .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
 
-    let session = convert_auth_response(auth_response)?;
+    convert_auth_response(auth_response)
+}
 
-    // Save new session to keychain
-    save_session(&session)?;
+// ============================================
+// Background session refresh
+// ============================================
 
-    Ok(session)
+/// How often the background task checks the stored session
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(120);
+/// Refresh sessions this close to expiry
+const SESSION_REFRESH_WINDOW_SECS: i64 = 5 * 60;
+
+/// Handle to the background task that keeps the session fresh
+#[derive(Default)]
+pub struct SessionRefresher {
+    stop: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
 }
 
-/// Send password reset email
+impl SessionRefresher {
+    /// Start the refresh task, replacing any task already running
+    pub fn start(&self, app: &AppHandle) {
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel();
+        if let Some(previous) = self.stop.lock().unwrap().replace(stop_tx) {
+            let _ = previous.send(());
+        }
+        tauri::async_runtime::spawn(session_refresh_loop(app.clone(), stop_rx));
+    }
+
+    /// Stop the refresh task, if one is running
+    pub fn stop(&self) {
+        if let Some(stop_tx) = self.stop.lock().unwrap().take() {
+            let _ = stop_tx.send(());
+        }
+    }
+}
+
+/// Periodically refresh the stored session before it expires.
+/// Emits 'session-refreshed' with the new session, or 'session-expired'
+/// when the refresh token is rejected.
+async fn session_refresh_loop(app: AppHandle, mut stop: tokio::sync::oneshot::Receiver<()>) {
+    loop {
+        // Wakes early (with Ok) when stopped
+        if tokio::time::timeout(SESSION_CHECK_INTERVAL, &mut stop)
+            .await
+            .is_ok()
+        {
+            tracing::debug!("Session refresh task stopped");
+            return;
+        }
+
+        let Some(session) = load_session() else {
+            continue;
+        };
+        let now = chrono::Utc::now().timestamp();
+        if session.expires_at - now > SESSION_REFRESH_WINDOW_SECS {
+            continue;
+        }
+
+        let client = app.state::<Client>();
+        let result = request_session_refresh(&client, &session.refresh_token).await;
+
+        // Stopped while the refresh was in flight (signed out, or replaced by
+        // a new sign-in's task): whatever is stored now isn't ours to touch
+        if !matches!(
+            stop.try_recv(),
+            Err(tokio::sync::oneshot::error::TryRecvError::Empty)
+        ) {
+            tracing::debug!("Session refresh task stopped during a refresh");
+            return;
+        }
+        // The stored session changed some other way, e.g. a foreground refresh
+        if load_session().is_none_or(|s| s.refresh_token != session.refresh_token) {
+            continue;
+        }
+
+        match result {
+            Ok(new_session) => {
+                if let Err(e) = save_session(&new_session) {
+                    tracing::warn!(error = %e, "Could not store refreshed session");
+                    continue;
+                }
+                tracing::debug!("Session refreshed in background");
+                emit_auth_state(&app, AuthStatus::Refreshed, Some(new_session.user.clone()));
+                let _ = app.emit("session-refreshed", new_session);
+            }
+            // The refresh token was rejected
+            Err(e @ AuthError::SessionExpired) => {
+                tracing::info!(error = %e, "Session expired during background refresh");
+                clear_session();
+                let _ = app.emit("session-expired", ());
+                emit_auth_state(&app, AuthStatus::SignedOut, None);
+                return;
+            }
+            // Offline or a server error: the session is still valid, try again later
+            Err(e) => {
+                tracing::warn!(error = %e, "Background session refresh failed");
+            }
+        }
+    }
+}
+
+/// Minimum password length accepted by Supabase
+const MIN_PASSWORD_LENGTH: usize = 6;
+
+/// Change the signed-in user's password. The current session stays valid.
 #[tauri::command]
-pub async fn reset_password(email: String) -> Result<(), AuthError> {
+pub async fn update_password(
+    client: State<'_, Client>,
+    new_password: String,
+) -> Result<(), AuthError> {
+    if new_password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AuthError::AuthFailed(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    let access_token = get_access_token(&client).await?;
     let supabase_url = get_supabase_url()?;
     let anon_key = get_supabase_anon_key()?;
 
-    let client = Client::new();
     let response = client
-        .post(format!("{}/auth/v1/recover", supabase_url))
+        .put(format!("{}/auth/v1/user", supabase_url))
         .header("apikey", &anon_key)
+        .header("Authorization", format!("Bearer {}", access_token))
         .header("Content-Type", "application/json")
         .json(&serde_json::json!({
-            "email": email
+            "password": new_password
         }))
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
     if !response.status().is_success() {
-        let error: SupabaseError = response.json().await.unwrap_or(SupabaseError {
-            error: Some("Unknown error".to_string()),
-            error_description: None,
-            message: None,
-            msg: None,
-        });
+        // Includes weak-password rejections, which explain what's missing
+        let error = SupabaseError::from_response(response).await;
+        return Err(AuthError::AuthFailed(error.into_message()));
+    }
 
-        let error_msg = error
-            .message
-            .or(error.error_description)
-            .or(error.msg)
-            .or(error.error)
-            .unwrap_or_else(|| "Unknown error".to_string());
+    Ok(())
+}
 
-        return Err(AuthError::AuthFailed(error_msg));
+/// Send password reset email
+#[tauri::command]
+pub async fn reset_password(client: State<'_, Client>, email: String) -> Result<(), AuthError> {
+    let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
+
+    let response = client
+        .post(format!("{}/auth/v1/recover", supabase_url))
+        .header("apikey", &anon_key)
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "email": email
+        }))
+        .send()
+        .await?;
+
+    let status = response.status();
+
+    if !status.is_success() {
+        let retry_after = retry_after_header(&response);
+        let error = SupabaseError::from_response(response).await;
+
+        if let Some(rate_limited) = error.email_rate_limit(status, retry_after) {
+            return Err(rate_limited);
+        }
+
+        return Err(AuthError::AuthFailed(error.into_message()));
+    }
+
+    Ok(())
+}
+
+// ============================================
+// Idle lock
+// ============================================
+
+/// How often the idle timer checks for inactivity
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// Set while the session is locked for inactivity. The session file and
+// keychain entry stay in place, but `load_session` reports no session until
+// the user signs in again. Mirrored by a marker file so a relaunch stays
+// locked.
+static SESSION_LOCKED: std::sync::LazyLock<AtomicBool> = std::sync::LazyLock::new(|| {
+    AtomicBool::new(session_lock_file_path().is_some_and(|path| path.exists()))
+});
+
+fn session_lock_file_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("com.writecraft.app").join("session.locked"))
+}
+
+fn set_session_locked(locked: bool) {
+    if SESSION_LOCKED.swap(locked, Ordering::SeqCst) == locked {
+        return;
+    }
+
+    let Some(path) = session_lock_file_path() else {
+        return;
+    };
+    let result = if locked {
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&path, b""))
+    } else {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    };
+    if let Err(e) = result {
+        tracing::warn!(path = %path.display(), "Failed to update session lock marker: {}", e);
+    }
+}
+
+/// Tracks frontend activity for locking the session on shared machines
+pub struct IdleLock {
+    last_activity: Mutex<Instant>,
+    /// Disabled when `None`
+    timeout: Mutex<Option<Duration>>,
+}
+
+impl Default for IdleLock {
+    fn default() -> Self {
+        IdleLock {
+            last_activity: Mutex::new(Instant::now()),
+            timeout: Mutex::new(None),
+        }
+    }
+}
+
+impl IdleLock {
+    /// Start the idle timer. Runs for the life of the app.
+    pub fn start(&self, app: &AppHandle) {
+        tauri::async_runtime::spawn(idle_lock_loop(app.clone()));
     }
 
+    fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// The idle timeout in minutes, `None` when the lock is disabled
+    pub(crate) fn timeout_minutes(&self) -> Option<u32> {
+        self.timeout
+            .lock()
+            .unwrap()
+            .map(|timeout| (timeout.as_secs() / 60) as u32)
+    }
+
+    pub(crate) fn set_timeout_minutes(&self, minutes: Option<u32>) {
+        let timeout = minutes
+            .filter(|m| *m > 0)
+            .map(|m| Duration::from_secs(u64::from(m) * 60));
+        *self.timeout.lock().unwrap() = timeout;
+        self.touch();
+    }
+
+    fn is_idle(&self) -> bool {
+        self.timeout
+            .lock()
+            .unwrap()
+            .is_some_and(|timeout| self.last_activity.lock().unwrap().elapsed() >= timeout)
+    }
+}
+
+async fn idle_lock_loop(app: AppHandle) {
+    loop {
+        tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+
+        if SESSION_LOCKED.load(Ordering::SeqCst) || !app.state::<IdleLock>().is_idle() {
+            continue;
+        }
+        let Some(session) = load_session() else {
+            continue;
+        };
+
+        lock_session();
+        app.state::<SessionRefresher>().stop();
+        tracing::info!("Session locked after inactivity");
+        let _ = app.emit("session-locked", session.user);
+    }
+}
+
+/// Forget the in-memory session and treat the user as signed out until they
+/// sign in again
+fn lock_session() {
+    set_session_locked(true);
+    clear_auth_fallback_storage();
+    *SUBSCRIPTION_CACHE.lock().unwrap() = None;
+}
+
+/// Record user activity in the frontend, postponing the idle lock
+#[tauri::command]
+pub fn report_activity(idle: State<'_, IdleLock>) -> Result<(), AuthError> {
+    idle.touch();
     Ok(())
 }
 
+/// Lock the session after `minutes` without activity. `None` or 0 disables
+/// the lock. Locking emits 'session-locked' with the user, and
+/// `get_session` returns `None` until they sign in again. The timeout is
+/// saved with the app settings.
+#[tauri::command]
+pub async fn set_idle_timeout(
+    idle: State<'_, IdleLock>,
+    minutes: Option<u32>,
+) -> Result<(), AuthError> {
+    idle.set_timeout_minutes(minutes);
+    update_app_settings(|settings| settings.idle_timeout_minutes = idle.timeout_minutes())
+        .await
+        .map_err(|e| AuthError::Storage(e.to_string()))
+}
+
+/// The idle timeout in minutes, `None` when the lock is disabled
+#[tauri::command]
+pub fn get_idle_timeout(idle: State<'_, IdleLock>) -> Option<u32> {
+    idle.timeout_minutes()
+}
+
 // ============================================
 // Profile commands
 // ============================================
 
 /// Get user profile
 #[tauri::command]
-pub async fn get_profile() -> Result<Profile, AuthError> {
+pub async fn get_profile(client: State<'_, Client>) -> Result<Profile, AuthError> {
     let session = load_session().ok_or(AuthError::NotAuthenticated)?;
     let supabase_url = get_supabase_url()?;
     let anon_key = get_supabase_anon_key()?;
 
-    let client = Client::new();
     let response = client
         .get(format!(
             "{}/rest/v1/profiles?id=eq.{}&select=*",
@@ -685,8 +1474,7 @@ pub async fn get_profile() -> Result<Profile, AuthError> {
         .header("apikey", &anon_key)
         .header("Authorization", format!("Bearer {}", session.access_token))
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
     if !response.status().is_success() {
         return Err(AuthError::AuthFailed("Failed to get profile".to_string()));
@@ -705,12 +1493,14 @@ pub async fn get_profile() -> Result<Profile, AuthError> {
 
 /// Update user profile
 #[tauri::command]
-pub async fn update_profile(updates: ProfileUpdate) -> Result<Profile, AuthError> {
+pub async fn update_profile(
+    client: State<'_, Client>,
+    updates: ProfileUpdate,
+) -> Result<Profile, AuthError> {
     let session = load_session().ok_or(AuthError::NotAuthenticated)?;
     let supabase_url = get_supabase_url()?;
     let anon_key = get_supabase_anon_key()?;
 
-    let client = Client::new();
     let response = client
         .patch(format!(
             "{}/rest/v1/profiles?id=eq.{}",
@@ -722,8 +1512,7 @@ pub async fn update_profile(updates: ProfileUpdate) -> Result<Profile, AuthError
         .header("Prefer", "return=representation")
         .json(&updates)
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
     if !response.status().is_success() {
         return Err(AuthError::AuthFailed("Failed to update profile".to_string()));
@@ -741,22 +1530,497 @@ pub async fn update_profile(updates: ProfileUpdate) -> Result<Profile, AuthError
 }
 
 // ============================================
-// Subscription commands
+// Avatar cache
 // ============================================
 
-/// Get subscription and usage info
+fn get_avatar_cache_dir() -> Option<PathBuf> {
+    // ~/Library/Caches/com.writecraft.app/avatars
+    dirs::cache_dir().map(|p| p.join("com.writecraft.app").join("avatars"))
+}
+
+/// Image type of a cached avatar, from the extension it was saved with
+fn avatar_mime_type(path: &std::path::Path) -> Option<&'static str> {
+    match path.extension()?.to_str()? {
+        "jpg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        "png" => Some("image/png"),
+        _ => None,
+    }
+}
+
+/// Cached avatars belonging to a user, whatever URL they came from. Leftover
+/// temp files from an interrupted write are skipped.
+fn cached_avatars(dir: &std::path::Path, user_id: &str) -> Vec<PathBuf> {
+    let prefix = format!("{}-", user_id);
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.starts_with(&prefix))
+                        && avatar_mime_type(path).is_some()
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A `data:` URL for an avatar, which the webview can show without access
+/// to the cache directory
+fn avatar_data_url(path: &std::path::Path, bytes: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        avatar_mime_type(path).unwrap_or("image/png"),
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+async fn read_cached_avatar(path: &std::path::Path) -> Result<String, AuthError> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| AuthError::Storage(e.to_string()))?;
+    Ok(avatar_data_url(path, &bytes))
+}
+
+/// The signed-in user's avatar as a `data:` URL, downloading it the first
+/// time and again only when the avatar URL changes. If the download fails,
+/// an avatar cached from an earlier URL is returned instead.
+/// Returns `AvatarNotFound` when the user has no avatar.
+#[tauri::command]
+pub async fn get_cached_avatar(client: State<'_, Client>) -> Result<String, AuthError> {
+    let session = load_session().ok_or(AuthError::NotAuthenticated)?;
+    let url = session
+        .user
+        .avatar_url
+        .filter(|url| !url.trim().is_empty())
+        .ok_or(AuthError::AvatarNotFound)?;
+    let dir = get_avatar_cache_dir()
+        .ok_or_else(|| AuthError::Storage("No cache directory".to_string()))?;
+
+    // The file name carries a hash of the URL, so a new URL misses the cache
+    let digest = Sha256::digest(url.as_bytes());
+    let url_hash: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    let stem = format!("{}-{}", session.user.id, url_hash);
+    let existing = cached_avatars(&dir, &session.user.id);
+    if let Some(path) = existing
+        .iter()
+        .find(|p| p.file_stem().and_then(|s| s.to_str()) == Some(stem.as_str()))
+    {
+        return read_cached_avatar(path).await;
+    }
+
+    let download = async {
+        let response = client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(AuthError::Network(format!(
+                "Avatar download failed ({})",
+                response.status()
+            )));
+        }
+        let extension = match response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+        {
+            Some(t) if t.starts_with("image/jpeg") => "jpg",
+            Some(t) if t.starts_with("image/gif") => "gif",
+            Some(t) if t.starts_with("image/webp") => "webp",
+            Some(t) if t.starts_with("image/svg") => "svg",
+            _ => "png",
+        };
+        let bytes = response.bytes().await?;
+        Ok::<_, AuthError>((bytes, extension))
+    };
+
+    let (bytes, extension) = match download.await {
+        Ok(downloaded) => downloaded,
+        Err(e) => {
+            tracing::debug!(error = %e, "Avatar download failed");
+            // Offline: an older avatar beats none
+            return match existing.first() {
+                Some(path) => read_cached_avatar(path).await,
+                None => Err(e),
+            };
+        }
+    };
+
+    fs::create_dir_all(&dir).map_err(|e| AuthError::Storage(e.to_string()))?;
+    let path = dir.join(format!("{}.{}", stem, extension));
+    write_atomic(&path, &bytes)
+        .await
+        .map_err(|e| AuthError::Storage(e.to_string()))?;
+    for old in existing {
+        let _ = fs::remove_file(old);
+    }
+
+    Ok(avatar_data_url(&path, &bytes))
+}
+
+// ============================================
+// Cloud documents
+// ============================================
+
+/// A document stored in the `documents` table. Listing leaves out the body
+/// and sidecar so it stays cheap.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct CloudDoc {
+    pub id: String,
+    pub title: String,
+    /// When the pushed copy was last modified on disk
+    pub modified_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudDocBody {
+    body: String,
+    sidecar: serde_json::Value,
+    updated_at: String,
+}
+
+/// The cloud copy this machine last pulled or pushed: the row's
+/// `updated_at`, which pushes are conditional on, and a hash of the body,
+/// to tell whether the local file has been edited since
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CloudSyncBase {
+    updated_at: String,
+    body_hash: String,
+}
+
+/// Serializes read-modify-write cycles on the sync record
+static CLOUD_SYNC_LOCK: std::sync::LazyLock<tokio::sync::Mutex<()>> =
+    std::sync::LazyLock::new(|| tokio::sync::Mutex::new(()));
+
+fn storage_error(e: impl std::fmt::Display) -> AuthError {
+    AuthError::Storage(e.to_string())
+}
+
+/// Modification time of a local file
+fn local_modified_at(path: &std::path::Path) -> Result<chrono::DateTime<chrono::Utc>, AuthError> {
+    let modified = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(storage_error)?;
+    Ok(chrono::DateTime::<chrono::Utc>::from(modified))
+}
+
+fn body_hash(body: &str) -> String {
+    Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Sync bases by document id. Kept per machine rather than in the sidecar,
+/// since the sidecar itself is synced.
+fn cloud_sync_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("com.writecraft.app").join("cloud_sync.json"))
+}
+
+async fn read_cloud_sync() -> Result<HashMap<String, CloudSyncBase>, AuthError> {
+    let Some(path) = cloud_sync_path() else {
+        return Ok(HashMap::new());
+    };
+    match tokio::fs::read(&path).await {
+        Ok(content) => serde_json::from_slice(&content).map_err(storage_error),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(storage_error(e)),
+    }
+}
+
+async fn cloud_sync_base(document_id: &str) -> Result<Option<CloudSyncBase>, AuthError> {
+    Ok(read_cloud_sync().await?.remove(document_id))
+}
+
+async fn record_cloud_sync(document_id: &str, base: CloudSyncBase) -> Result<(), AuthError> {
+    let path = cloud_sync_path().ok_or_else(|| storage_error("No data directory"))?;
+    let _guard = CLOUD_SYNC_LOCK.lock().await;
+    let mut bases = read_cloud_sync().await?;
+    bases.insert(document_id.to_string(), base);
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(storage_error)?;
+    }
+    let json = serde_json::to_vec_pretty(&bases).map_err(storage_error)?;
+    write_atomic(&path, &json).await.map_err(storage_error)
+}
+
+/// List the signed-in user's cloud documents, most recently modified first
 #[tauri::command]
-pub async fn get_subscription_info() -> Result<SubscriptionInfo, AuthError> {
+pub async fn list_cloud_documents(client: State<'_, Client>) -> Result<Vec<CloudDoc>, AuthError> {
     let session = load_session().ok_or(AuthError::NotAuthenticated)?;
     let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
 
-    let client = Client::new();
     let response = client
-        .get(format!("{}/functions/v1/get-subscription", supabase_url))
+        .get(format!(
+            "{}/rest/v1/documents?user_id=eq.{}&select=id,title,modified_at,updated_at&order=modified_at.desc",
+            supabase_url, session.user.id
+        ))
+        .header("apikey", &anon_key)
+        .header("Authorization", format!("Bearer {}", session.access_token))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(AuthError::AuthFailed(
+            "Failed to list cloud documents".to_string(),
+        ));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| AuthError::AuthFailed(e.to_string()))
+}
+
+/// Download a cloud document to `local_path`, replacing the markdown file
+/// and its sidecar. A local file that was edited since it was last synced
+/// fails with `CloudConflict` unless `force` is set; whatever gets replaced
+/// is backed up first.
+#[tauri::command]
+pub async fn pull_cloud_document(
+    client: State<'_, Client>,
+    id: String,
+    local_path: String,
+    force: Option<bool>,
+) -> Result<(), AuthError> {
+    // The id goes into a PostgREST filter, so only accept a real document id
+    let id = uuid::Uuid::parse_str(&id)
+        .map_err(|_| AuthError::AuthFailed(format!("Invalid cloud document id: {}", id)))?;
+    let session = load_session().ok_or(AuthError::NotAuthenticated)?;
+    let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
+    let md_path = PathBuf::from(&local_path);
+    let sidecar_path = get_sidecar_path(&local_path).map_err(storage_error)?;
+
+    let response = client
+        .get(format!("{}/rest/v1/documents", supabase_url))
+        .query(&[
+            ("id", format!("eq.{}", id)),
+            ("select", "body,sidecar,updated_at".to_string()),
+        ])
+        .header("apikey", &anon_key)
+        .header("Authorization", format!("Bearer {}", session.access_token))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(AuthError::AuthFailed(
+            "Failed to get cloud document".to_string(),
+        ));
+    }
+
+    let docs: Vec<CloudDocBody> = response
+        .json()
+        .await
+        .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
+    let doc = docs
+        .into_iter()
+        .next()
+        .ok_or_else(|| AuthError::AuthFailed("Cloud document not found".to_string()))?;
+
+    // Run the sidecar through the usual migration before it touches disk
+    let sidecar_bytes = serde_json::to_vec(&doc.sidecar).map_err(storage_error)?;
+    let (sidecar, _) = parse_sidecar(&sidecar_bytes).map_err(storage_error)?;
+
+    if md_path.exists() {
+        let local_body = tokio::fs::read_to_string(&md_path)
+            .await
+            .map_err(storage_error)?;
+        if local_body != doc.body {
+            let unchanged_since_sync = cloud_sync_base(&id)
+                .await?
+                .is_some_and(|base| base.body_hash == body_hash(&local_body));
+            if !unchanged_since_sync && !force.unwrap_or(false) {
+                return Err(AuthError::CloudConflict);
+            }
+        }
+        create_document_backup(local_path.clone(), None)
+            .await
+            .map_err(storage_error)?;
+    }
+
+    write_atomic(&md_path, doc.body.as_bytes())
+        .await
+        .map_err(storage_error)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    save_sidecar(&sidecar_path, &sidecar)
+        .await
+        .map_err(storage_error)?;
+    record_cloud_sync(
+        &id.to_string(),
+        CloudSyncBase {
+            updated_at: doc.updated_at,
+            body_hash: body_hash(&doc.body),
+        },
+    )
+    .await?;
+
+    tracing::info!(id = %id, "Pulled cloud document");
+    Ok(())
+}
+
+/// Upload the document at `local_path` and its sidecar, keyed by the
+/// sidecar's document id. The upload only applies if the cloud copy is still
+/// the one this machine last synced; otherwise it fails with `CloudConflict`,
+/// unless `force` is set.
+#[tauri::command]
+pub async fn push_cloud_document(
+    client: State<'_, Client>,
+    local_path: String,
+    force: Option<bool>,
+) -> Result<CloudDoc, AuthError> {
+    let session = load_session().ok_or(AuthError::NotAuthenticated)?;
+    let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
+
+    let md_path = PathBuf::from(&local_path);
+    let body = tokio::fs::read_to_string(&md_path)
+        .await
+        .map_err(storage_error)?;
+    let modified_at = local_modified_at(&md_path)?;
+
+    // A document without a sidecar gets one now so its id stays stable
+    // across pushes
+    let sidecar_path = get_sidecar_path(&local_path).map_err(storage_error)?;
+    let guard = lock_sidecar(&sidecar_path).await;
+    let sidecar = match read_existing_sidecar(&sidecar_path)
+        .await
+        .map_err(storage_error)?
+    {
+        Some(sidecar) => sidecar,
+        None => {
+            let sidecar = Sidecar::new();
+            save_sidecar(&sidecar_path, &sidecar)
+                .await
+                .map_err(storage_error)?;
+            sidecar
+        }
+    };
+    drop(guard);
+
+    let title = md_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let row = serde_json::json!({
+        "id": &sidecar.document_id,
+        "user_id": &session.user.id,
+        "title": title,
+        "body": &body,
+        "sidecar": &sidecar,
+        "modified_at": modified_at.to_rfc3339(),
+    });
+
+    let base = cloud_sync_base(&sidecar.document_id).await?;
+    let request = match (force.unwrap_or(false), base) {
+        (true, _) => client
+            .post(format!("{}/rest/v1/documents", supabase_url))
+            .header(
+                "Prefer",
+                "resolution=merge-duplicates,return=representation",
+            ),
+        // Only matches while the row is still at the synced version
+        (false, Some(base)) => client
+            .patch(format!("{}/rest/v1/documents", supabase_url))
+            .query(&[
+                ("id", format!("eq.{}", sidecar.document_id)),
+                ("updated_at", format!("eq.{}", base.updated_at)),
+            ])
+            .header("Prefer", "return=representation"),
+        // Never synced from here: inserting fails if the id is already taken
+        (false, None) => client
+            .post(format!("{}/rest/v1/documents", supabase_url))
+            .header("Prefer", "return=representation"),
+    };
+
+    let response = request
+        .header("apikey", &anon_key)
         .header("Authorization", format!("Bearer {}", session.access_token))
+        .header("Content-Type", "application/json")
+        .json(&row)
         .send()
+        .await?;
+
+    if response.status() == reqwest::StatusCode::CONFLICT {
+        return Err(AuthError::CloudConflict);
+    }
+    if !response.status().is_success() {
+        return Err(AuthError::AuthFailed(
+            "Failed to push cloud document".to_string(),
+        ));
+    }
+
+    let docs: Vec<CloudDoc> = response
+        .json()
         .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
+    // An update that matched no row means someone else pushed in between
+    let doc = docs.into_iter().next().ok_or(AuthError::CloudConflict)?;
+
+    record_cloud_sync(
+        &sidecar.document_id,
+        CloudSyncBase {
+            updated_at: doc.updated_at.clone(),
+            body_hash: body_hash(&body),
+        },
+    )
+    .await?;
+
+    tracing::info!(id = %sidecar.document_id, "Pushed cloud document");
+    Ok(doc)
+}
+
+// ============================================
+// Subscription commands
+// ============================================
+
+/// How long a fetched subscription is served before being refreshed
+const SUBSCRIPTION_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Oldest a cached subscription may be and still be served at all, e.g. when
+/// background refreshes keep failing
+const SUBSCRIPTION_CACHE_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+struct CachedSubscription {
+    /// User the info belongs to, so a stale entry never leaks across accounts
+    user_id: String,
+    info: SubscriptionInfo,
+    fetched_at: Instant,
+}
+
+/// The cached subscription for `user_id` and its age, unless it belongs to
+/// another user or is too old to serve
+fn cached_subscription(user_id: &str) -> Option<(SubscriptionInfo, Duration)> {
+    SUBSCRIPTION_CACHE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|cached| cached.user_id == user_id)
+        .map(|cached| (cached.info.clone(), cached.fetched_at.elapsed()))
+        .filter(|(_, age)| *age < SUBSCRIPTION_CACHE_MAX_AGE)
+}
+
+async fn fetch_subscription_info(
+    client: &Client,
+    session: &AuthSession,
+) -> Result<SubscriptionInfo, AuthError> {
+    let supabase_url = get_supabase_url()?;
+
+    let response = client
+        .get(format!("{}/functions/v1/get-subscription", supabase_url))
+        .header("Authorization", format!("Bearer {}", session.access_token))
+        .send()
+        .await?;
 
     if !response.status().is_success() {
         return Err(AuthError::AuthFailed(
@@ -769,16 +2033,70 @@ pub async fn get_subscription_info() -> Result<SubscriptionInfo, AuthError> {
         .await
         .map_err(|e| AuthError::AuthFailed(e.to_string()))?;
 
+    // Signed out (or switched accounts) while the request was in flight
+    if load_session().is_some_and(|current| current.user.id == session.user.id) {
+        *SUBSCRIPTION_CACHE.lock().unwrap() = Some(CachedSubscription {
+            user_id: session.user.id.clone(),
+            info: info.clone(),
+            fetched_at: Instant::now(),
+        });
+    }
+
     Ok(info)
 }
 
+/// Get subscription and usage info. A cached result is returned for up to a
+/// minute; after that the stale value is returned while a background refresh
+/// runs, emitting 'subscription-updated' when it lands. Pass `force` to skip
+/// the cache, e.g. right after an upgrade.
+#[tauri::command]
+pub async fn get_subscription_info(
+    app: AppHandle,
+    client: State<'_, Client>,
+    force: Option<bool>,
+) -> Result<SubscriptionInfo, AuthError> {
+    let session = load_session().ok_or(AuthError::NotAuthenticated)?;
+
+    if !force.unwrap_or(false) {
+        if let Some((info, age)) = cached_subscription(&session.user.id) {
+            if age >= SUBSCRIPTION_CACHE_TTL
+                && !SUBSCRIPTION_REFRESHING.swap(true, Ordering::SeqCst)
+            {
+                tauri::async_runtime::spawn(async move {
+                    let client = app.state::<Client>();
+                    match fetch_subscription_info(&client, &session).await {
+                        Ok(info) => {
+                            let _ = app.emit("subscription-updated", info);
+                        }
+                        Err(e) => tracing::debug!("Background subscription refresh failed: {}", e),
+                    }
+                    SUBSCRIPTION_REFRESHING.store(false, Ordering::SeqCst);
+                });
+            }
+            return Ok(info);
+        }
+    }
+
+    fetch_subscription_info(&client, &session).await
+}
+
+/// Models the signed-in user's plan allows, from the last
+/// `get_subscription_info` call. `None` until it has been fetched for the
+/// current user, or once that fetch is too old to trust.
+pub(crate) fn cached_allowed_models() -> Option<Vec<String>> {
+    let session = load_session()?;
+    cached_subscription(&session.user.id).map(|(info, _)| info.allowed_models)
+}
+
 /// Get Stripe checkout URL for upgrading
 #[tauri::command]
-pub async fn get_checkout_url(price_id: String) -> Result<String, AuthError> {
+pub async fn get_checkout_url(
+    client: State<'_, Client>,
+    price_id: String,
+) -> Result<String, AuthError> {
     let session = load_session().ok_or(AuthError::NotAuthenticated)?;
     let supabase_url = get_supabase_url()?;
 
-    let client = Client::new();
     let response = client
         .post(format!("{}/functions/v1/create-checkout", supabase_url))
         .header("Authorization", format!("Bearer {}", session.access_token))
@@ -787,8 +2105,7 @@ pub async fn get_checkout_url(price_id: String) -> Result<String, AuthError> {
             "priceId": price_id
         }))
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
     if !response.status().is_success() {
         // Try to get detailed error from response
@@ -832,11 +2149,10 @@ pub async fn get_checkout_url(price_id: String) -> Result<String, AuthError> {
 
 /// Get Stripe billing portal URL
 #[tauri::command]
-pub async fn get_billing_portal_url() -> Result<String, AuthError> {
+pub async fn get_billing_portal_url(client: State<'_, Client>) -> Result<String, AuthError> {
     let session = load_session().ok_or(AuthError::NotAuthenticated)?;
     let supabase_url = get_supabase_url()?;
 
-    let client = Client::new();
     let response = client
         .post(format!(
             "{}/functions/v1/create-portal-session",
@@ -846,8 +2162,7 @@ pub async fn get_billing_portal_url() -> Result<String, AuthError> {
         .header("Content-Type", "application/json")
         .json(&serde_json::json!({}))
         .send()
-        .await
-        .map_err(|e| AuthError::Network(e.to_string()))?;
+        .await?;
 
     if !response.status().is_success() {
         return Err(AuthError::AuthFailed(
@@ -872,6 +2187,16 @@ pub async fn get_billing_portal_url() -> Result<String, AuthError> {
 // Helper functions
 // ============================================
 
+fn emit_auth_state(app: &AppHandle, status: AuthStatus, user: Option<AuthUser>) {
+    let _ = app.emit("auth-state-changed", AuthStateChange { status, user });
+}
+
+/// Common follow-up once a new session has been saved
+fn on_signed_in(app: &AppHandle, session: &AuthSession) {
+    app.state::<SessionRefresher>().start(app);
+    emit_auth_state(app, AuthStatus::SignedIn, Some(session.user.clone()));
+}
+
 fn convert_auth_response(response: SupabaseAuthResponse) -> Result<AuthSession, AuthError> {
     let now = chrono::Utc::now().timestamp();
     let expires_at = response
@@ -902,25 +2227,67 @@ fn convert_auth_response(response: SupabaseAuthResponse) -> Result<AuthSession,
 
 /// Get the current access token (for internal use by Claude proxy)
 /// Automatically refreshes expired sessions
-pub async fn get_access_token() -> Result<String, AuthError> {
+pub async fn get_access_token(client: &Client) -> Result<String, AuthError> {
     let session = load_session().ok_or(AuthError::NotAuthenticated)?;
 
     // Check if session is expired
     let now = chrono::Utc::now().timestamp();
     if session.expires_at <= now {
         // Try to refresh the session
-        match refresh_session_internal(&session.refresh_token).await {
-            Ok(new_session) => Ok(new_session.access_token),
-            Err(_) => {
-                clear_session();
-                Err(AuthError::SessionExpired)
-            }
-        }
+        refresh_session_internal(client, &session.refresh_token)
+            .await
+            .map(|new_session| new_session.access_token)
     } else {
         Ok(session.access_token)
     }
 }
 
+/// Id of the signed-in user, for log context. Never refreshes the session.
+pub(crate) fn current_user_id() -> Option<String> {
+    load_session().map(|session| session.user.id)
+}
+
+/// Whether the OS sends `fizz://` links to this app. When it doesn't, OAuth
+/// sign-in opens the browser but never comes back, so the UI can warn and
+/// offer to paste the callback URL instead.
+#[tauri::command]
+pub fn check_deep_link_registration(app: AppHandle) -> Result<bool, AuthError> {
+    #[cfg(target_os = "macos")]
+    {
+        let handler = macos_default_url_handler(DEEP_LINK_SCHEME);
+        Ok(handler
+            .is_some_and(|bundle_id| bundle_id.eq_ignore_ascii_case(&app.config().identifier)))
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        use tauri_plugin_deep_link::DeepLinkExt;
+
+        app.deep_link()
+            .is_registered(DEEP_LINK_SCHEME)
+            .map_err(|e| AuthError::DeepLinkCheck(e.to_string()))
+    }
+}
+
+/// Bundle id of Launch Services' default handler for a URL scheme
+#[cfg(target_os = "macos")]
+fn macos_default_url_handler(scheme: &str) -> Option<String> {
+    use core_foundation::base::TCFType;
+    use core_foundation::string::{CFString, CFStringRef};
+
+    #[link(name = "CoreServices", kind = "framework")]
+    extern "C" {
+        fn LSCopyDefaultHandlerForURLScheme(scheme: CFStringRef) -> CFStringRef;
+    }
+
+    let scheme = CFString::new(scheme);
+    let handler = unsafe { LSCopyDefaultHandlerForURLScheme(scheme.as_concrete_TypeRef()) };
+    if handler.is_null() {
+        return None;
+    }
+    // Copy functions follow the Create Rule, so the string is ours to release
+    Some(unsafe { CFString::wrap_under_create_rule(handler) }.to_string())
+}
+
 /// Debug command to check auth state
 #[tauri::command]
 pub fn debug_auth_state() -> Result<String, String> {
@@ -942,3 +2309,173 @@ pub fn debug_auth_state() -> Result<String, String> {
         None => Ok("No session found".to_string())
     }
 }
+
+/// How the stored session looks to this machine and to Supabase
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    /// Unexpired locally and accepted by the server
+    Valid,
+    /// Past its local expiry time
+    LocallyExpired,
+    /// Unexpired locally, but the server refused it (e.g. revoked)
+    ServerRejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionValidity {
+    pub state: SessionState,
+    /// Seconds until the local expiry; negative once expired
+    pub expires_in: i64,
+    pub server_accepted: bool,
+    pub server_status: u16,
+}
+
+/// Debug command that checks the stored access token against Supabase's
+/// `/auth/v1/user`. Unlike `get_session`, it never refreshes or clears the
+/// session.
+#[tauri::command]
+pub async fn validate_session_remote(
+    client: State<'_, Client>,
+) -> Result<SessionValidity, AuthError> {
+    let session = load_session().ok_or(AuthError::NotAuthenticated)?;
+    let supabase_url = get_supabase_url()?;
+    let anon_key = get_supabase_anon_key()?;
+
+    let response = client
+        .get(format!("{}/auth/v1/user", supabase_url))
+        .header("apikey", &anon_key)
+        .header("Authorization", format!("Bearer {}", session.access_token))
+        .send()
+        .await?;
+
+    let status = response.status();
+    let server_accepted = status.is_success();
+    if !server_accepted && !matches!(status.as_u16(), 401 | 403) {
+        let error = SupabaseError::from_response(response).await;
+        return Err(AuthError::AuthFailed(error.into_message()));
+    }
+
+    let expires_in = session.expires_at - chrono::Utc::now().timestamp();
+    let state = if expires_in <= 0 {
+        SessionState::LocallyExpired
+    } else if !server_accepted {
+        SessionState::ServerRejected
+    } else {
+        SessionState::Valid
+    };
+
+    Ok(SessionValidity {
+        state,
+        expires_in,
+        server_accepted,
+        server_status: status.as_u16(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn email_rate_limit_reads_wait_from_body() {
+        let body = r#"{
+            "code": 429,
+            "error_code": "over_email_send_rate_limit",
+            "msg": "For security purposes, you can only request this after 42 seconds."
+        }"#;
+        let error: SupabaseError = serde_json::from_str(body).unwrap();
+        assert!(matches!(
+            error.email_rate_limit(StatusCode::TOO_MANY_REQUESTS, None),
+            Some(AuthError::RateLimited(Some(42)))
+        ));
+    }
+
+    #[test]
+    fn email_rate_limit_prefers_retry_after_header() {
+        let body = r#"{
+            "code": 429,
+            "error_code": "over_email_send_rate_limit",
+            "msg": "For security purposes, you can only request this after 42 seconds."
+        }"#;
+        let error: SupabaseError = serde_json::from_str(body).unwrap();
+        assert!(matches!(
+            error.email_rate_limit(StatusCode::TOO_MANY_REQUESTS, Some(60)),
+            Some(AuthError::RateLimited(Some(60)))
+        ));
+    }
+
+    #[test]
+    fn email_rate_limit_without_wait() {
+        let body = r#"{
+            "code": 429,
+            "error_code": "over_email_send_rate_limit",
+            "msg": "email rate limit exceeded"
+        }"#;
+        let error: SupabaseError = serde_json::from_str(body).unwrap();
+        assert!(matches!(
+            error.email_rate_limit(StatusCode::TOO_MANY_REQUESTS, None),
+            Some(AuthError::RateLimited(None))
+        ));
+    }
+
+    #[test]
+    fn rate_limited_serializes_with_its_retry_window() {
+        let json = serde_json::to_value(AuthError::RateLimited(Some(42))).unwrap();
+        assert_eq!(json["kind"], "rateLimited");
+        assert_eq!(json["retryAfter"], 42);
+        assert!(json["message"].as_str().unwrap().contains("42 seconds"));
+
+        let json = serde_json::to_value(AuthError::NotAuthenticated).unwrap();
+        assert_eq!(json, "Not authenticated");
+    }
+
+    #[test]
+    fn other_errors_are_not_rate_limits() {
+        let body = r#"{"code": 400, "error_code": "validation_failed", "msg": "Invalid email"}"#;
+        let error: SupabaseError = serde_json::from_str(body).unwrap();
+        assert!(error
+            .email_rate_limit(StatusCode::BAD_REQUEST, None)
+            .is_none());
+    }
+
+    #[test]
+    fn parse_retry_seconds_finds_number_before_seconds() {
+        assert_eq!(parse_retry_seconds("request this after 1 second."), Some(1));
+        assert_eq!(
+            parse_retry_seconds("request this after 42 seconds."),
+            Some(42)
+        );
+        assert_eq!(parse_retry_seconds("email rate limit exceeded"), None);
+        assert_eq!(parse_retry_seconds("wait a few seconds"), None);
+    }
+
+    #[test]
+    fn cached_subscription_is_scoped_to_its_user_and_expires() {
+        let info = SubscriptionInfo {
+            subscription: None,
+            usage: None,
+            allowed_models: vec!["model-a".to_string()],
+        };
+        *SUBSCRIPTION_CACHE.lock().unwrap() = Some(CachedSubscription {
+            user_id: "user-a".to_string(),
+            info: info.clone(),
+            fetched_at: Instant::now(),
+        });
+        assert!(cached_subscription("user-a").is_some());
+        assert!(cached_subscription("user-b").is_none());
+
+        let Some(fetched_at) = Instant::now().checked_sub(SUBSCRIPTION_CACHE_MAX_AGE) else {
+            return;
+        };
+        *SUBSCRIPTION_CACHE.lock().unwrap() = Some(CachedSubscription {
+            user_id: "user-a".to_string(),
+            info,
+            fetched_at,
+        });
+        assert!(cached_subscription("user-a").is_none());
+        *SUBSCRIPTION_CACHE.lock().unwrap() = None;
+    }
+}
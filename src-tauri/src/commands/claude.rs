@@ -1,10 +1,36 @@
 use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter};
-
-const CLAUDE_API_URL: &str = "https://api.anthropic.com/v1/messages";
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const DEFAULT_CLAUDE_BASE_URL: &str = "https://api.anthropic.com";
+/// Overrides the Claude API base URL, e.g. to go through a proxy or a local stub
+const CLAUDE_BASE_URL_ENV: &str = "WRITECRAFT_CLAUDE_BASE_URL";
+const DEFAULT_ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Overrides the `anthropic-version` header sent with every Claude request
+const ANTHROPIC_VERSION_ENV: &str = "WRITECRAFT_ANTHROPIC_VERSION";
+const PROMPT_CACHING_BETA: &str = "prompt-caching-2024-07-31";
 const DEFAULT_MODEL: &str = "claude-haiku-4-5-20251001";
+pub(crate) const DEFAULT_MAX_TOKENS: u32 = 4096;
+/// Smallest extended thinking budget the API accepts
+const MIN_THINKING_BUDGET_TOKENS: u32 = 1024;
+
+/// Retries for rate-limited (429) requests before giving up
+pub(crate) const DEFAULT_MAX_RETRIES: u32 = 3;
+const BASE_RETRY_DELAY_MS: u64 = 1000;
+const MAX_RETRY_DELAY_MS: u64 = 30_000;
+
+/// How long a response stream may go without sending anything, keepalive
+/// pings included, before it is treated as stalled
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Claude requests allowed in flight at once before new ones wait
+const DEFAULT_CONCURRENT_REQUESTS: usize = 2;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ClaudeError {
@@ -16,6 +42,27 @@ pub enum ClaudeError {
     NoApiKey,
     #[error("Rate limited: {0}")]
     RateLimited(String),
+    #[error("Usage limit reached: {0}")]
+    UsageExceeded(String),
+    #[error("Request timed out, try again")]
+    Timeout,
+    #[error("No internet connection")]
+    Offline,
+    #[error("{0} is not included in your plan. Upgrade to use this model.")]
+    ModelNotAllowed(String),
+}
+
+impl From<reqwest::Error> for ClaudeError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            ClaudeError::Timeout
+        } else if e.is_connect() {
+            // DNS failures, refused connections and no route all land here
+            ClaudeError::Offline
+        } else {
+            ClaudeError::Network(e.to_string())
+        }
+    }
 }
 
 impl serde::Serialize for ClaudeError {
@@ -27,6 +74,277 @@ impl serde::Serialize for ClaudeError {
     }
 }
 
+/// Claude API base URL, honoring the `WRITECRAFT_CLAUDE_BASE_URL` override
+fn claude_base_url() -> String {
+    let base = std::env::var(CLAUDE_BASE_URL_ENV)
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| DEFAULT_CLAUDE_BASE_URL.to_string());
+    base.trim_end_matches('/').to_string()
+}
+
+/// API version to request, honoring the `WRITECRAFT_ANTHROPIC_VERSION` override
+pub(crate) fn anthropic_version() -> String {
+    std::env::var(ANTHROPIC_VERSION_ENV)
+        .ok()
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+        .unwrap_or_else(|| DEFAULT_ANTHROPIC_VERSION.to_string())
+}
+
+/// Value for the `anthropic-beta` header: prompt caching when `cache` is set,
+/// plus the caller's beta flags, without duplicates. `None` if there are none.
+fn anthropic_beta(cache: bool, beta_features: Option<&[String]>) -> Option<String> {
+    let mut flags: Vec<&str> = Vec::new();
+    if cache {
+        flags.push(PROMPT_CACHING_BETA);
+    }
+    for flag in beta_features.unwrap_or_default() {
+        let flag = flag.trim();
+        if !flag.is_empty() && !flags.contains(&flag) {
+            flags.push(flag);
+        }
+    }
+    (!flags.is_empty()).then(|| flags.join(","))
+}
+
+pub(crate) fn claude_messages_url() -> String {
+    format!("{}/v1/messages", claude_base_url())
+}
+
+pub(crate) fn claude_models_url() -> String {
+    format!("{}/v1/models", claude_base_url())
+}
+
+// ============================================
+// Generation cancellation
+// ============================================
+
+/// Lets the frontend stop in-flight generations. Each request registers a
+/// token under its channel id, with requests that have no channel sharing the
+/// default one, so stopping one conversation leaves the others streaming.
+#[derive(Default)]
+pub struct GenerationState {
+    tokens: Mutex<HashMap<String, Vec<Arc<CancelToken>>>>,
+}
+
+/// Cancellation flag for a single request
+#[derive(Default)]
+pub(crate) struct CancelToken {
+    cancelled: AtomicBool,
+    notify: tokio::sync::Notify,
+}
+
+impl CancelToken {
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Resolves once the token is cancelled
+    pub(crate) async fn cancelled(&self) {
+        // Registered before the check so a cancel in between still wakes us
+        let notified = self.notify.notified();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+}
+
+impl GenerationState {
+    /// Register a new generation on `channel_id`. The returned guard derefs
+    /// to its cancel token and unregisters it when the request finishes,
+    /// however it exits.
+    pub(crate) fn begin(&self, channel_id: Option<&str>) -> GenerationGuard<'_> {
+        let key = channel_id.unwrap_or_default().to_string();
+        let token = Arc::new(CancelToken::default());
+        self.tokens
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .push(token.clone());
+        GenerationGuard {
+            state: self,
+            key,
+            token,
+        }
+    }
+
+    /// Cancel every generation registered on `channel_id`
+    fn cancel(&self, channel_id: Option<&str>) {
+        let key = channel_id.unwrap_or_default();
+        if let Some(tokens) = self.tokens.lock().unwrap().get(key) {
+            tracing::info!(channel_id = key, "Cancelling in-flight generation");
+            for token in tokens {
+                token.cancel();
+            }
+        }
+    }
+}
+
+pub(crate) struct GenerationGuard<'a> {
+    state: &'a GenerationState,
+    key: String,
+    token: Arc<CancelToken>,
+}
+
+impl std::ops::Deref for GenerationGuard<'_> {
+    type Target = CancelToken;
+
+    fn deref(&self) -> &CancelToken {
+        &self.token
+    }
+}
+
+impl Drop for GenerationGuard<'_> {
+    fn drop(&mut self) {
+        let mut tokens = self.state.tokens.lock().unwrap();
+        if let Some(channel) = tokens.get_mut(&self.key) {
+            channel.retain(|token| !Arc::ptr_eq(token, &self.token));
+            if channel.is_empty() {
+                tokens.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Stop the in-flight generations on `channel_id`, or those started without
+/// a channel. Each streaming command returns whatever text it has received
+/// so far. Does nothing if none is running there.
+#[tauri::command]
+pub fn cancel_generation(generation: State<'_, GenerationState>, channel_id: Option<String>) {
+    generation.cancel(channel_id.as_deref());
+}
+
+// ============================================
+// Request queue
+// ============================================
+
+/// Emitted when a request has to wait for another to finish
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedEvent {
+    pub max_concurrent: usize,
+}
+
+/// Caps concurrent Claude requests so bursts (like quick tool-use
+/// continuations) wait in-process instead of tripping per-minute limits
+pub struct RequestQueue {
+    permits: Arc<Semaphore>,
+    limits: Arc<Mutex<QueueLimits>>,
+}
+
+struct QueueLimits {
+    /// The limit last asked for
+    target: usize,
+    /// Permits in circulation, free or held. Above `target` after a decrease
+    /// until enough held slots are released.
+    issued: usize,
+}
+
+/// A request's place in the queue, freed when dropped. If the limit was
+/// lowered while it was held, the slot is retired instead of freed.
+pub(crate) struct RequestSlot {
+    permit: Option<OwnedSemaphorePermit>,
+    limits: Arc<Mutex<QueueLimits>>,
+}
+
+impl Drop for RequestSlot {
+    fn drop(&mut self) {
+        let mut limits = self.limits.lock().unwrap();
+        if let Some(permit) = self.permit.take() {
+            if limits.issued > limits.target {
+                permit.forget();
+                limits.issued -= 1;
+            }
+        }
+    }
+}
+
+impl Default for RequestQueue {
+    fn default() -> Self {
+        RequestQueue {
+            permits: Arc::new(Semaphore::new(DEFAULT_CONCURRENT_REQUESTS)),
+            limits: Arc::new(Mutex::new(QueueLimits {
+                target: DEFAULT_CONCURRENT_REQUESTS,
+                issued: DEFAULT_CONCURRENT_REQUESTS,
+            })),
+        }
+    }
+}
+
+impl RequestQueue {
+    /// Wait for a free request slot, emitting 'claude-queued' if there isn't
+    /// one yet. Returns `None` if the generation is cancelled while waiting.
+    pub(crate) async fn acquire(
+        &self,
+        app: &AppHandle,
+        channel_id: Option<&str>,
+        cancel: &CancelToken,
+    ) -> Option<RequestSlot> {
+        let permit = match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let max_concurrent = self.limit();
+                tracing::debug!(max_concurrent, "Claude request queued");
+                let _ = app.emit(
+                    &stream_event("claude-queued", channel_id),
+                    QueuedEvent { max_concurrent },
+                );
+                tokio::select! {
+                    biased;
+                    _ = cancel.cancelled() => return None,
+                    permit = self.permits.clone().acquire_owned() => {
+                        permit.expect("request queue semaphore is never closed")
+                    }
+                }
+            }
+        };
+        Some(RequestSlot {
+            permit: Some(permit),
+            limits: self.limits.clone(),
+        })
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limits.lock().unwrap().target
+    }
+
+    pub(crate) fn set_limit(&self, limit: usize) {
+        let mut limits = self.limits.lock().unwrap();
+        limits.target = limit;
+        if limit > limits.issued {
+            self.permits.add_permits(limit - limits.issued);
+            limits.issued = limit;
+        } else {
+            // Free permits go now; slots in use are retired as they're
+            // released (see `RequestSlot`)
+            limits.issued -= self.permits.forget_permits(limits.issued - limit);
+        }
+    }
+}
+
+/// Set how many Claude requests may run at once (at least 1). Requests past
+/// the limit wait their turn instead of failing with a 429. The limit is
+/// saved with the app settings.
+#[tauri::command]
+pub async fn set_max_concurrent_requests(
+    queue: State<'_, RequestQueue>,
+    max_concurrent: usize,
+) -> Result<(), ClaudeError> {
+    let limit = max_concurrent.max(1);
+    queue.set_limit(limit);
+    super::file::update_app_settings(|settings| settings.max_concurrent_requests = Some(limit))
+        .await
+        .map_err(|e| ClaudeError::Api(format!("Failed to save request limit: {}", e)))
+}
+
 // ============================================
 // Tool calling types
 // ============================================
@@ -39,6 +357,14 @@ pub struct Tool {
     pub input_schema: serde_json::Value,
 }
 
+/// Image source for vision input, either inline base64 or a URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
+}
+
 /// Content block types for messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -46,6 +372,9 @@ pub enum ContentBlock {
     Text {
         text: String,
     },
+    Image {
+        source: ImageSource,
+    },
     ToolUse {
         id: String,
         name: String,
@@ -57,6 +386,15 @@ pub enum ContentBlock {
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
     },
+    /// Extended thinking, sent back unchanged (signature included) ahead of
+    /// the tool uses when a tool-use turn continues
+    Thinking {
+        thinking: String,
+        signature: String,
+    },
+    RedactedThinking {
+        data: String,
+    },
 }
 
 /// Message content can be either a simple string or an array of content blocks
@@ -74,6 +412,35 @@ pub struct Message {
     pub content: MessageContent,
 }
 
+impl Message {
+    /// Ids of the tool calls this message makes
+    fn tool_use_ids(&self) -> Vec<&str> {
+        self.blocks()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Ids of the tool calls this message answers
+    fn tool_result_ids(&self) -> Vec<&str> {
+        self.blocks()
+            .filter_map(|block| match block {
+                ContentBlock::ToolResult { tool_use_id, .. } => Some(tool_use_id.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = &ContentBlock> {
+        match &self.content {
+            MessageContent::Blocks(blocks) => blocks.iter(),
+            MessageContent::Text(_) => Default::default(),
+        }
+    }
+}
+
 /// Simplified chat message for backward compatibility
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -117,6 +484,33 @@ pub struct ToolUseEvent {
     pub input: serde_json::Value,
 }
 
+/// Emitted while waiting to retry a rate-limited request
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryEvent {
+    pub attempt: u32,
+    pub max_retries: u32,
+    pub delay_ms: u64,
+}
+
+/// Emitted when a request moves on to the next model in its fallback chain
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelFallbackEvent {
+    pub from_model: String,
+    pub to_model: String,
+    pub reason: String,
+}
+
+/// Emitted periodically while a response streams in, for a live rate readout
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamProgressEvent {
+    /// Characters of text (and thinking) received so far
+    pub characters: usize,
+    pub elapsed_ms: u64,
+}
+
 /// Message stop event with stop reason
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -130,10 +524,15 @@ pub struct MessageStopEvent {
 
 #[derive(Debug, Deserialize)]
 struct ContentBlockDelta {
-    #[serde(rename = "type")]
+    // message_delta events carry a delta without a type
+    #[serde(rename = "type", default)]
     delta_type: String,
     text: Option<String>,
     partial_json: Option<String>,
+    thinking: Option<String>,
+    signature: Option<String>,
+    stop_reason: Option<String>,
+    stop_sequence: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -142,6 +541,8 @@ struct ContentBlockStart {
     block_type: String,
     id: Option<String>,
     name: Option<String>,
+    /// Set on redacted_thinking blocks
+    data: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -154,12 +555,29 @@ struct StreamEvent {
     content_block: Option<ContentBlockStart>,
     delta: Option<ContentBlockDelta>,
     message: Option<MessageInfo>,
+    usage: Option<Usage>,
     error: Option<ApiError>,
 }
 
 #[derive(Debug, Deserialize)]
 struct MessageInfo {
     stop_reason: Option<String>,
+    usage: Option<Usage>,
+}
+
+/// Token usage reported by the API. Input and cache counts arrive with
+/// message_start, the final output count with message_delta.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u32,
+    #[serde(default)]
+    pub output_tokens: u32,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u32,
+    #[serde(default)]
+    pub cache_read_input_tokens: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -175,14 +593,14 @@ struct ApiError {
 
 /// Cache control for prompt caching
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct CacheControl {
+pub struct CacheControl {
     #[serde(rename = "type")]
     cache_type: String,
 }
 
 /// System prompt block with optional cache control
-#[derive(Debug, Clone, Serialize)]
-struct SystemBlock {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemBlock {
     #[serde(rename = "type")]
     block_type: String,
     text: String,
@@ -190,6 +608,15 @@ struct SystemBlock {
     cache_control: Option<CacheControl>,
 }
 
+/// A system prompt from the frontend: plain text, or blocks that each carry
+/// their own cache control so only part of the prompt is cached
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SystemPrompt {
+    Text(String),
+    Blocks(Vec<SystemBlock>),
+}
+
 #[derive(Debug, Serialize)]
 struct ClaudeRequest {
     model: String,
@@ -200,6 +627,108 @@ struct ClaudeRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<ThinkingConfig>,
+}
+
+/// Model backend a chat request is sent to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Provider {
+    #[default]
+    #[serde(rename = "anthropic")]
+    Anthropic,
+    #[serde(rename = "openai")]
+    OpenAi,
+}
+
+/// Extended thinking configuration
+#[derive(Debug, Serialize)]
+struct ThinkingConfig {
+    #[serde(rename = "type")]
+    thinking_type: String,
+    budget_tokens: u32,
+}
+
+/// Combine the caller's system prompt with the document's stored template,
+/// when the request is for a document that has one
+pub(crate) async fn document_system_prompt(
+    md_path: Option<&str>,
+    system_prompt: Option<String>,
+) -> Option<String> {
+    let sidecar = match md_path {
+        Some(path) => super::file::load_existing_sidecar(std::path::Path::new(path)).await,
+        None => None,
+    };
+    match sidecar {
+        Some(sidecar) => sidecar.system_prompt(system_prompt),
+        None => system_prompt,
+    }
+}
+
+/// Check that every tool result answers a tool use from the assistant turn
+/// right before it, and every tool use is answered in the turn after it.
+/// The API rejects a conversation that breaks either rule with a bare 400,
+/// so name the offending `tool_use_id` instead.
+fn validate_tool_pairing(messages: &[Message]) -> Result<(), ClaudeError> {
+    for (index, message) in messages.iter().enumerate() {
+        let preceding_uses = match index.checked_sub(1).map(|i| &messages[i]) {
+            Some(previous) if previous.role == "assistant" => previous.tool_use_ids(),
+            _ => Vec::new(),
+        };
+        if let Some(orphan) = message
+            .tool_result_ids()
+            .into_iter()
+            .find(|id| !preceding_uses.contains(id))
+        {
+            return Err(ClaudeError::Api(format!(
+                "Tool result for {} has no matching tool use in the preceding assistant message",
+                orphan
+            )));
+        }
+
+        // A trailing tool use is still waiting for its result
+        if let Some(next) = messages.get(index + 1) {
+            let answered = next.tool_result_ids();
+            if let Some(orphan) = message
+                .tool_use_ids()
+                .into_iter()
+                .find(|id| !answered.contains(id))
+            {
+                return Err(ClaudeError::Api(format!(
+                    "Tool use {} has no matching tool result in the following message",
+                    orphan
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reject sampling parameters outside the range the API accepts
+fn validate_sampling(temperature: Option<f32>, top_p: Option<f32>) -> Result<(), ClaudeError> {
+    if let Some(t) = temperature {
+        if !(0.0..=1.0).contains(&t) {
+            return Err(ClaudeError::Api(format!(
+                "Temperature must be between 0.0 and 1.0 (got {})",
+                t
+            )));
+        }
+    }
+    if let Some(p) = top_p {
+        if !(0.0..=1.0).contains(&p) {
+            return Err(ClaudeError::Api(format!(
+                "top_p must be between 0.0 and 1.0 (got {})",
+                p
+            )));
+        }
+    }
+    Ok(())
 }
 
 /// Response from send_message_with_tools
@@ -209,29 +738,358 @@ pub struct AssistantResponse {
     pub text_content: String,
     pub tool_uses: Vec<ToolUseEvent>,
     pub stop_reason: String,
+    /// The custom stop sequence that ended generation, if any
+    pub stop_sequence: Option<String>,
+    pub usage: Option<Usage>,
+    /// Extended thinking text, when thinking was enabled
+    pub thinking: Option<String>,
+    /// The thinking blocks as received, for the assistant turn sent back with
+    /// tool results
+    pub thinking_blocks: Vec<ContentBlock>,
+}
+
+/// Name of a stream event, namespaced as `<event>:<channel_id>` when the
+/// caller streams on its own channel so concurrent chats stay separate
+pub(crate) fn stream_event(event: &str, channel_id: Option<&str>) -> String {
+    match channel_id {
+        Some(id) => format!("{}:{}", event, id),
+        None => event.to_string(),
+    }
+}
+
+/// Report a stream that closed before its end-of-message event, and send the
+/// done chunk so the frontend stops waiting for more
+pub(crate) fn stream_ended_early(app: &AppHandle, channel_id: Option<&str>) -> ClaudeError {
+    let error = "The connection closed before the response finished".to_string();
+    let _ = app.emit(
+        &stream_event("claude-stream-error", channel_id),
+        StreamError {
+            error: error.clone(),
+        },
+    );
+    let _ = app.emit(
+        &stream_event("claude-stream-chunk", channel_id),
+        StreamChunk {
+            chunk: String::new(),
+            done: true,
+        },
+    );
+    ClaudeError::Network(error)
+}
+
+/// Minimum gap between 'claude-stream-progress' events
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Counts streamed characters and emits throttled 'claude-stream-progress'
+/// events until the message stops
+struct StreamProgress {
+    started: Instant,
+    last_emit: Option<Instant>,
+    characters: usize,
+    stopped: bool,
+}
+
+impl StreamProgress {
+    fn new() -> Self {
+        StreamProgress {
+            started: Instant::now(),
+            last_emit: None,
+            characters: 0,
+            stopped: false,
+        }
+    }
+
+    fn emit(&mut self, app: &AppHandle, channel_id: Option<&str>) {
+        self.last_emit = Some(Instant::now());
+        let _ = app.emit(
+            &stream_event("claude-stream-progress", channel_id),
+            StreamProgressEvent {
+                characters: self.characters,
+                elapsed_ms: self.started.elapsed().as_millis() as u64,
+            },
+        );
+    }
+
+    /// Count newly streamed text, emitting if the last event is old enough
+    fn record(&mut self, app: &AppHandle, channel_id: Option<&str>, text: &str) {
+        if self.stopped {
+            return;
+        }
+        self.characters += text.chars().count();
+        if self.last_emit.is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL) {
+            self.emit(app, channel_id);
+        }
+    }
+
+    /// Emit the final totals at message_stop; nothing is sent after this
+    fn stop(&mut self, app: &AppHandle, channel_id: Option<&str>) {
+        if !self.stopped {
+            self.emit(app, channel_id);
+            self.stopped = true;
+        }
+    }
+}
+
+/// Splits a Claude SSE stream into the payloads of its `data:` lines.
+/// Keepalive `ping` events, comments, blank lines and `[DONE]` are skipped.
+#[derive(Default)]
+struct SseParser {
+    /// Bytes after the last complete line, kept raw so a character split
+    /// across chunks decodes intact
+    buffer: Vec<u8>,
+    /// Name from the last `event:` line; applies until the blank line that
+    /// ends the event
+    event: Option<String>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Next complete data payload, or `None` until more bytes arrive
+    fn next_data(&mut self) -> Option<String> {
+        while let Some(newline_pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=newline_pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+
+            if line.is_empty() {
+                self.event = None;
+            } else if let Some(name) = line.strip_prefix("event:") {
+                self.event = Some(name.trim().to_string());
+            } else if let Some(data) = line.strip_prefix("data:") {
+                let data = data.trim_start();
+                if self.event.as_deref() != Some("ping") && data != "[DONE]" {
+                    return Some(data.to_string());
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Next item from a response body, or `None` once it ends or `cancel` fires.
+/// Fails with `Timeout` if nothing arrives for `STREAM_IDLE_TIMEOUT`; any
+/// bytes, pings included, restart the clock.
+pub(crate) async fn next_chunk<S>(
+    stream: &mut S,
+    cancel: &CancelToken,
+) -> Result<Option<S::Item>, ClaudeError>
+where
+    S: futures::Stream + Unpin,
+{
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Ok(None),
+        next = tokio::time::timeout(STREAM_IDLE_TIMEOUT, stream.next()) => next.map_err(|_| {
+            tracing::warn!("Response stream stalled");
+            ClaudeError::Timeout
+        }),
+    }
+}
+
+/// Channel ids become part of event names, which Tauri restricts
+pub(crate) fn validate_channel_id(channel_id: Option<&str>) -> Result<(), ClaudeError> {
+    match channel_id {
+        Some(id)
+            if id.is_empty()
+                || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+        {
+            Err(ClaudeError::Api(format!(
+                "Invalid channel id {:?}: use letters, digits, '-' or '_'",
+                id
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The model used when a request doesn't name one: the saved default, or
+/// the built-in one
+fn default_model() -> String {
+    super::file::load_app_settings()
+        .default_model
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string())
+}
+
+/// Tag the current request span with the signed-in user, if any
+fn record_user_id() {
+    if let Some(user_id) = super::auth::current_user_id() {
+        tracing::Span::current().record("user_id", user_id.as_str());
+    }
+}
+
+/// Send a request, retrying with backoff while Claude responds 429.
+/// Honors the `retry-after` header when present. Only the initial request
+/// is retried; once a response is returned the caller owns the stream.
+pub(crate) async fn send_with_retry(
+    app: &AppHandle,
+    request: reqwest::RequestBuilder,
+    max_retries: u32,
+    channel_id: Option<&str>,
+) -> Result<reqwest::Response, ClaudeError> {
+    let mut attempt = 0;
+
+    loop {
+        let this_request = request
+            .try_clone()
+            .ok_or_else(|| ClaudeError::Api("Request cannot be retried".to_string()))?;
+        let response = this_request
+            .send()
+            .await?;
+
+        if response.status().as_u16() != 429 || attempt >= max_retries {
+            return Ok(response);
+        }
+
+        attempt += 1;
+        let retry_after_ms = response
+            .headers()
+            .get("retry-after")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(|secs| secs * 1000);
+        let backoff_ms = BASE_RETRY_DELAY_MS * 2u64.pow(attempt - 1);
+        let delay_ms = retry_after_ms.unwrap_or(backoff_ms).min(MAX_RETRY_DELAY_MS);
+
+        tracing::warn!(attempt, delay_ms, "Claude rate limited, retrying");
+        let _ = app.emit(
+            &stream_event("claude-retry", channel_id),
+            RetryEvent {
+                attempt,
+                max_retries,
+                delay_ms,
+            },
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+    }
 }
 
 /// Send a message to Claude API with streaming response (backward compatible)
+/// `provider: "openai"` sends it to the OpenAI-compatible endpoint instead,
+/// through the same request queue, fallback chain and reply saving
 /// Emits 'claude-stream-chunk' events to frontend as chunks arrive
 /// Emits 'claude-stream-error' on error
-/// Returns the complete response when done
+/// Emits 'claude-retry' while waiting to retry after a 429
+/// If the model is overloaded (529) or not found before streaming starts, the
+/// next of `fallback_models` is tried, emitting 'claude-model-fallback'
+/// `beta_features` are added to the `anthropic-beta` header
+/// With `channel_id`, every event is emitted as `<event>:<channel_id>`
+/// Returns the complete response when done, or the partial response if
+/// cancelled via `cancel_generation`
+/// `document_id` only tags the request's log span
+/// With `persist_reply` and `md_path`, the reply is saved to the document's
+/// conversation as it streams, and 'claude-reply-persisted' is emitted with
+/// the saved message once it ends; the frontend then shouldn't append the
+/// reply itself
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    skip_all,
+    err(level = "warn"),
+    fields(
+        model = tracing::field::Empty,
+        message_count = messages.len(),
+        document_id = document_id.as_deref(),
+        user_id = tracing::field::Empty,
+    )
+)]
 pub async fn send_message(
     app: AppHandle,
+    client: State<'_, Client>,
+    generation: State<'_, GenerationState>,
     messages: Vec<ChatMessage>,
     system_prompt: Option<String>,
     model: Option<String>,
+    max_retries: Option<u32>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    md_path: Option<String>,
+    provider: Option<Provider>,
+    channel_id: Option<String>,
+    document_id: Option<String>,
+    fallback_models: Option<Vec<String>>,
+    beta_features: Option<Vec<String>>,
+    persist_reply: Option<bool>,
 ) -> Result<String, ClaudeError> {
+    record_user_id();
+    let channel_id = channel_id.as_deref();
+    validate_channel_id(channel_id)?;
+
     // Convert ChatMessage to Message
     let messages: Vec<Message> = messages.into_iter().map(|m| m.into()).collect();
 
+    let openai = provider.unwrap_or_default() == Provider::OpenAi;
+
     // Get API key from keychain
-    let api_key = super::keychain::get_api_key()
-        .map_err(|e| ClaudeError::Api(e.to_string()))?
-        .ok_or(ClaudeError::NoApiKey)?;
+    let api_key = if openai {
+        super::openai::openai_api_key()?
+    } else {
+        super::keychain::get_api_key(None)
+            .map_err(|e| ClaudeError::Api(e.to_string()))?
+            .ok_or(ClaudeError::NoApiKey)?
+    };
+
+    if openai {
+        super::openai::validate_openai_sampling(temperature, top_p)?;
+    } else {
+        validate_sampling(temperature, top_p)?;
+    }
+
+    let model = match model {
+        Some(model) => model,
+        None if openai => super::openai::default_openai_model(),
+        None => default_model(),
+    };
+    tracing::Span::current().record("model", model.as_str());
+    let system_prompt = document_system_prompt(md_path.as_deref(), system_prompt).await;
+    let cancel = generation.begin(channel_id);
+    let Some(_slot) = app
+        .state::<RequestQueue>()
+        .acquire(&app, channel_id, &cancel)
+        .await
+    else {
+        let _ = app.emit(
+            &stream_event("claude-stream-chunk", channel_id),
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+        return Ok(String::new());
+    };
+
+    // Save the reply into the document's conversation as it streams
+    let persist_path = md_path
+        .as_deref()
+        .filter(|_| persist_reply.unwrap_or(false));
+    let mut reply = match persist_path {
+        Some(md_path) => match super::file::StreamingReply::start(&app, md_path) {
+            Ok(reply) => Some(reply),
+            Err(e) => {
+                tracing::warn!("Not saving reply while streaming: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
 
-    let client = Client::new();
-    let model = model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    if openai {
+        let params = super::openai::ChatParams {
+            api_key,
+            model,
+            fallback_models: fallback_models.unwrap_or_default(),
+            messages,
+            system_prompt,
+            max_retries,
+            max_tokens,
+            temperature,
+            top_p,
+        };
+        return super::openai::stream_chat(&app, &client, &cancel, params, reply, channel_id).await;
+    }
 
     // Build system blocks with cache control for prompt caching
     let system_blocks = system_prompt.map(|text| {
@@ -244,41 +1102,84 @@ pub async fn send_message(
         }]
     });
 
-    let request_body = ClaudeRequest {
+    let mut request_body = ClaudeRequest {
         model,
-        max_tokens: 4096,
+        max_tokens: max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
         system: system_blocks,
         messages,
         stream: true,
         tools: None,
+        temperature,
+        top_p,
+        stop_sequences: None,
+        thinking: None,
     };
 
-    let response = client
-        .post(CLAUDE_API_URL)
-        .header("x-api-key", &api_key)
-        .header("anthropic-version", "2023-06-01")
-        .header("anthropic-beta", "prompt-caching-2024-07-31")
-        .header("content-type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| ClaudeError::Network(e.to_string()))?;
-
-    let status = response.status();
+    let beta = anthropic_beta(true, beta_features.as_deref());
+    let mut fallback_models = fallback_models.unwrap_or_default().into_iter();
+    let response = loop {
+        let mut request = client
+            .post(claude_messages_url())
+            .header("x-api-key", &api_key)
+            .header("anthropic-version", anthropic_version())
+            .header("content-type", "application/json")
+            .json(&request_body);
+        if let Some(beta) = &beta {
+            request = request.header("anthropic-beta", beta);
+        }
+        let response = send_with_retry(
+            &app,
+            request,
+            max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+            channel_id,
+        )
+        .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            break response;
+        }
 
-    // Handle error status codes
-    if !status.is_success() {
+        // Handle error status codes
         let error_body = response.text().await.unwrap_or_default();
 
-        // Try to parse as JSON to extract the error message
-        let error_msg = if let Ok(json) = serde_json::from_str::<serde_json::Value>(&error_body) {
-            json["error"]["message"]
-                .as_str()
-                .unwrap_or(&error_body)
-                .to_string()
-        } else {
-            error_body
-        };
+        // Try to parse as JSON to extract the error type and message
+        let (error_type, error_msg) =
+            match serde_json::from_str::<serde_json::Value>(&error_body) {
+                Ok(json) => (
+                    json["error"]["type"].as_str().unwrap_or_default().to_string(),
+                    json["error"]["message"]
+                        .as_str()
+                        .unwrap_or(&error_body)
+                        .to_string(),
+                ),
+                Err(_) => (String::new(), error_body),
+            };
+
+        // Nothing has streamed yet, so an overloaded or unknown model can
+        // still be swapped for the next one in the chain
+        let model_unavailable = status.as_u16() == 529
+            || error_type == "overloaded_error"
+            || (status.as_u16() == 404 && error_type == "not_found_error");
+        if model_unavailable {
+            if let Some(next_model) = fallback_models.next() {
+                tracing::warn!(
+                    from = %request_body.model,
+                    to = %next_model,
+                    status = %status,
+                    "Model unavailable, falling back"
+                );
+                let _ = app.emit(
+                    &stream_event("claude-model-fallback", channel_id),
+                    ModelFallbackEvent {
+                        from_model: std::mem::replace(&mut request_body.model, next_model.clone()),
+                        to_model: next_model,
+                        reason: error_msg,
+                    },
+                );
+                continue;
+            }
+        }
 
         return match status.as_u16() {
             401 => Err(ClaudeError::Api("Invalid API key".to_string())),
@@ -287,81 +1188,113 @@ pub async fn send_message(
             500..=599 => Err(ClaudeError::Api(format!("Server error: {}", error_msg))),
             _ => Err(ClaudeError::Api(format!("Error ({}): {}", status, error_msg))),
         };
-    }
+    };
 
     // Process SSE stream
     let mut stream = response.bytes_stream();
     let mut full_response = String::new();
-    let mut buffer = String::new();
-
-    while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result.map_err(|e| ClaudeError::Network(e.to_string()))?;
-
-        let chunk_str = String::from_utf8_lossy(&chunk);
-        buffer.push_str(&chunk_str);
-
-        // Process complete SSE events (lines starting with "data: ")
-        while let Some(newline_pos) = buffer.find('\n') {
-            let line = buffer[..newline_pos].trim().to_string();
-            buffer = buffer[newline_pos + 1..].to_string();
-
-            if line.starts_with("data: ") {
-                let data = &line[6..];
-
-                // Skip [DONE] marker
-                if data == "[DONE]" {
-                    continue;
-                }
-
-                // Parse the JSON event
-                if let Ok(event) = serde_json::from_str::<StreamEvent>(data) {
-                    match event.event_type.as_str() {
-                        "content_block_delta" => {
-                            if let Some(delta) = event.delta {
-                                if delta.delta_type == "text_delta" {
-                                    if let Some(text) = delta.text {
-                                        full_response.push_str(&text);
-
-                                        // Emit chunk to frontend
-                                        let _ = app.emit(
-                                            "claude-stream-chunk",
-                                            StreamChunk {
-                                                chunk: text,
-                                                done: false,
-                                            },
-                                        );
+    let mut sse = SseParser::default();
+    let mut usage: Option<Usage> = None;
+    let mut progress = StreamProgress::new();
+    let mut finished = false;
+
+    while let Some(chunk_result) = next_chunk(&mut stream, &cancel).await? {
+        let chunk = chunk_result?;
+
+        sse.push(&chunk);
+
+        // Process complete SSE events; pings and blank lines are skipped
+        while let Some(data) = sse.next_data() {
+            // Parse the JSON event
+            if let Ok(event) = serde_json::from_str::<StreamEvent>(&data) {
+                match event.event_type.as_str() {
+                    "message_start" => {
+                        usage = event.message.and_then(|m| m.usage);
+                    }
+                    "message_delta" => {
+                        if let (Some(total), Some(delta_usage)) = (usage.as_mut(), event.usage) {
+                            total.output_tokens = delta_usage.output_tokens;
+                        }
+                    }
+                    "content_block_delta" => {
+                        if let Some(delta) = event.delta {
+                            if delta.delta_type == "text_delta" {
+                                if let Some(text) = delta.text {
+                                    full_response.push_str(&text);
+                                    progress.record(&app, channel_id, &text);
+                                    if let Some(reply) = reply.as_mut() {
+                                        reply.push(&text).await;
                                     }
+
+                                    // Emit chunk to frontend
+                                    let _ = app.emit(
+                                        &stream_event("claude-stream-chunk", channel_id),
+                                        StreamChunk {
+                                            chunk: text,
+                                            done: false,
+                                        },
+                                    );
                                 }
                             }
                         }
-                        "message_stop" => {
-                            // Emit final done signal
+                    }
+                    "message_stop" => {
+                        finished = true;
+                        progress.stop(&app, channel_id);
+                        if let Some(reply) = reply.take() {
+                            reply.finish().await;
+                        }
+
+                        // Emit final done signal
+                        let _ = app.emit(
+                            &stream_event("claude-stream-chunk", channel_id),
+                            StreamChunk {
+                                chunk: String::new(),
+                                done: true,
+                            },
+                        );
+                    }
+                    "error" => {
+                        if let Some(err) = event.error {
+                            if let Some(reply) = reply.take() {
+                                reply.finish().await;
+                            }
+                            let error_msg = format!("{}: {}", err.error_type, err.message);
                             let _ = app.emit(
-                                "claude-stream-chunk",
-                                StreamChunk {
-                                    chunk: String::new(),
-                                    done: true,
+                                &stream_event("claude-stream-error", channel_id),
+                                StreamError {
+                                    error: error_msg.clone(),
                                 },
                             );
+                            return Err(ClaudeError::Api(error_msg));
                         }
-                        "error" => {
-                            if let Some(err) = event.error {
-                                let error_msg =
-                                    format!("{}: {}", err.error_type, err.message);
-                                let _ = app.emit(
-                                    "claude-stream-error",
-                                    StreamError { error: error_msg.clone() },
-                                );
-                                return Err(ClaudeError::Api(error_msg));
-                            }
-                        }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
         }
     }
 
+    if let Some(reply) = reply {
+        reply.finish().await;
+    }
+    if cancel.is_cancelled() {
+        let _ = app.emit(
+            &stream_event("claude-stream-chunk", channel_id),
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+        return Ok(full_response);
+    }
+    if !finished {
+        return Err(stream_ended_early(&app, channel_id));
+    }
+    if let Some(usage) = &usage {
+        super::file::record_usage(&request_body.model, usage).await;
+    }
+
     Ok(full_response)
 }
 
@@ -378,55 +1311,194 @@ struct ToolUseState {
 
 /// Send a message to Claude API with tools support
 /// Emits 'claude-stream-chunk' for text content
+/// Emits 'claude-thinking-chunk' for extended thinking text
 /// Emits 'claude-tool-use' when a tool call is complete
 /// Emits 'claude-message-stop' with stop reason
+/// Emits 'claude-retry' while waiting to retry after a 429
+/// With `channel_id`, every event is emitted as `<event>:<channel_id>`
+/// `system_prompt` is either a string or an array of system blocks, each with
+/// its own `cache_control`
+/// `beta_features` are added to the `anthropic-beta` header
 /// Returns AssistantResponse with text content, tool uses, and stop reason
+/// (stop reason is "cancelled" if stopped via `cancel_generation`)
+/// `document_id` only tags the request's log span
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    skip_all,
+    err(level = "warn"),
+    fields(
+        model = tracing::field::Empty,
+        message_count = messages.len(),
+        document_id = document_id.as_deref(),
+        user_id = tracing::field::Empty,
+    )
+)]
 pub async fn send_message_with_tools(
     app: AppHandle,
+    client: State<'_, Client>,
+    generation: State<'_, GenerationState>,
     messages: Vec<Message>,
-    system_prompt: Option<String>,
+    system_prompt: Option<SystemPrompt>,
     tools: Option<Vec<Tool>>,
     model: Option<String>,
+    max_retries: Option<u32>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    stop_sequences: Option<Vec<String>>,
+    cache_system: Option<bool>,
+    thinking_budget_tokens: Option<u32>,
+    md_path: Option<String>,
+    channel_id: Option<String>,
+    document_id: Option<String>,
+    beta_features: Option<Vec<String>>,
 ) -> Result<AssistantResponse, ClaudeError> {
+    record_user_id();
+    let channel_id = channel_id.as_deref();
+    validate_channel_id(channel_id)?;
+
     // Get API key from keychain
-    let api_key = super::keychain::get_api_key()
+    let api_key = super::keychain::get_api_key(None)
         .map_err(|e| ClaudeError::Api(e.to_string()))?
         .ok_or(ClaudeError::NoApiKey)?;
 
-    let client = Client::new();
-    let model = model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    validate_sampling(temperature, top_p)?;
+    validate_tool_pairing(&messages)?;
 
-    // Build system blocks with cache control for prompt caching
-    let system_blocks = system_prompt.map(|text| {
-        vec![SystemBlock {
+    if thinking_budget_tokens.is_some() && (temperature.is_some() || top_p.is_some()) {
+        return Err(ClaudeError::Api(
+            "Temperature and top_p can't be set when extended thinking is enabled".to_string(),
+        ));
+    }
+
+    if let Some(budget) = thinking_budget_tokens.filter(|b| *b < MIN_THINKING_BUDGET_TOKENS) {
+        return Err(ClaudeError::Api(format!(
+            "The thinking budget must be at least {} tokens (got {})",
+            MIN_THINKING_BUDGET_TOKENS, budget
+        )));
+    }
+
+    // The thinking budget counts against max_tokens, so leave room for the
+    // visible answer unless the caller picked a limit themselves
+    let max_tokens = match (thinking_budget_tokens, max_tokens) {
+        (Some(budget), Some(max)) if max <= budget => {
+            return Err(ClaudeError::Api(format!(
+                "max_tokens ({}) must be greater than the thinking budget ({})",
+                max, budget
+            )));
+        }
+        (Some(budget), None) => budget + DEFAULT_MAX_TOKENS,
+        (_, max) => max.unwrap_or(DEFAULT_MAX_TOKENS),
+    };
+
+    let model = model.unwrap_or_else(default_model);
+    tracing::Span::current().record("model", model.as_str());
+    let cache_system = cache_system.unwrap_or(true);
+    let (system_text, caller_blocks) = match system_prompt {
+        Some(SystemPrompt::Text(text)) => (Some(text), Vec::new()),
+        Some(SystemPrompt::Blocks(blocks)) => (None, blocks),
+        None => (None, Vec::new()),
+    };
+    let system_prompt = document_system_prompt(md_path.as_deref(), system_text).await;
+    let cancel = generation.begin(channel_id);
+
+    // Build system blocks, marking the text prompt (or the document's
+    // template ahead of caller blocks) cacheable when prompt caching is on
+    let system_blocks: Vec<SystemBlock> = system_prompt
+        .map(|text| SystemBlock {
             block_type: "text".to_string(),
             text,
-            cache_control: Some(CacheControl {
+            cache_control: cache_system.then(|| CacheControl {
                 cache_type: "ephemeral".to_string(),
             }),
-        }]
-    });
+        })
+        .into_iter()
+        .chain(caller_blocks)
+        .collect();
+    let uses_cache = system_blocks.iter().any(|b| b.cache_control.is_some());
+    let system_blocks = (!system_blocks.is_empty()).then_some(system_blocks);
 
     let request_body = ClaudeRequest {
         model,
-        max_tokens: 4096,
+        max_tokens,
         system: system_blocks,
         messages,
         stream: true,
         tools,
+        temperature,
+        top_p,
+        stop_sequences: stop_sequences.filter(|s| !s.is_empty()),
+        thinking: thinking_budget_tokens.map(|budget_tokens| ThinkingConfig {
+            thinking_type: "enabled".to_string(),
+            budget_tokens,
+        }),
     };
 
-    let response = client
-        .post(CLAUDE_API_URL)
-        .header("x-api-key", &api_key)
-        .header("anthropic-version", "2023-06-01")
-        .header("anthropic-beta", "prompt-caching-2024-07-31")
-        .header("content-type", "application/json")
-        .json(&request_body)
-        .send()
+    let response = stream_tool_turn(
+        &app,
+        &client,
+        &cancel,
+        &api_key,
+        &request_body,
+        anthropic_beta(uses_cache, beta_features.as_deref()).as_deref(),
+        max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+        channel_id,
+    )
+    .await?;
+
+    if let Some(usage) = &response.usage {
+        super::file::record_usage(&request_body.model, usage).await;
+    }
+
+    Ok(response)
+}
+
+/// Send one streaming request with tool support and collect the assistant turn.
+/// Emits the same events as `send_message_with_tools`.
+#[allow(clippy::too_many_arguments)]
+async fn stream_tool_turn(
+    app: &AppHandle,
+    client: &Client,
+    cancel: &CancelToken,
+    api_key: &str,
+    request_body: &ClaudeRequest,
+    beta: Option<&str>,
+    max_retries: u32,
+    channel_id: Option<&str>,
+) -> Result<AssistantResponse, ClaudeError> {
+    let Some(_slot) = app
+        .state::<RequestQueue>()
+        .acquire(app, channel_id, cancel)
         .await
-        .map_err(|e| ClaudeError::Network(e.to_string()))?;
+    else {
+        let _ = app.emit(
+            &stream_event("claude-stream-chunk", channel_id),
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+        return Ok(AssistantResponse {
+            text_content: String::new(),
+            tool_uses: Vec::new(),
+            stop_reason: String::from("cancelled"),
+            stop_sequence: None,
+            usage: None,
+            thinking: None,
+            thinking_blocks: Vec::new(),
+        });
+    };
+    let mut request = client
+        .post(claude_messages_url())
+        .header("x-api-key", api_key)
+        .header("anthropic-version", anthropic_version())
+        .header("content-type", "application/json")
+        .json(request_body);
+    if let Some(beta) = beta {
+        request = request.header("anthropic-beta", beta);
+    }
+    let response = send_with_retry(app, request, max_retries, channel_id).await?;
 
     let status = response.status();
 
@@ -456,35 +1528,44 @@ pub async fn send_message_with_tools(
     let mut stream = response.bytes_stream();
     let mut text_content = String::new();
     let mut tool_uses: Vec<ToolUseEvent> = Vec::new();
-    let mut buffer = String::new();
+    let mut sse = SseParser::default();
     let mut stop_reason = String::from("end_turn");
+    let mut stop_sequence: Option<String> = None;
+    let mut usage: Option<Usage> = None;
+    let mut thinking = String::new();
+    let mut thinking_blocks: Vec<ContentBlock> = Vec::new();
+    let mut progress = StreamProgress::new();
 
     // Track current content block being built
     let mut current_tool_use: Option<ToolUseState> = None;
-
-    while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result.map_err(|e| ClaudeError::Network(e.to_string()))?;
-
-        let chunk_str = String::from_utf8_lossy(&chunk);
-        buffer.push_str(&chunk_str);
-
-        // Process complete SSE events
-        while let Some(newline_pos) = buffer.find('\n') {
-            let line = buffer[..newline_pos].trim().to_string();
-            buffer = buffer[newline_pos + 1..].to_string();
-
-            if line.starts_with("data: ") {
-                let data = &line[6..];
-
-                if data == "[DONE]" {
-                    continue;
-                }
-
-                if let Ok(event) = serde_json::from_str::<StreamEvent>(data) {
-                    match event.event_type.as_str() {
-                        "content_block_start" => {
-                            if let Some(block) = event.content_block {
-                                if block.block_type == "tool_use" {
+    // Text and signature of the thinking block being built
+    let mut current_thinking: Option<(String, String)> = None;
+    // Set by message_stop; a stream that ends without it was cut off
+    let mut finished = false;
+
+    while let Some(chunk_result) = next_chunk(&mut stream, cancel).await? {
+        let chunk = chunk_result?;
+
+        sse.push(&chunk);
+
+        // Process complete SSE events; pings and blank lines are skipped
+        while let Some(data) = sse.next_data() {
+            if let Ok(event) = serde_json::from_str::<StreamEvent>(&data) {
+                match event.event_type.as_str() {
+                    "message_start" => {
+                        if let Some(start_usage) = event.message.and_then(|m| m.usage) {
+                            tracing::debug!(
+                                cache_creation = start_usage.cache_creation_input_tokens,
+                                cache_read = start_usage.cache_read_input_tokens,
+                                "Prompt cache usage"
+                            );
+                            usage = Some(start_usage);
+                        }
+                    }
+                    "content_block_start" => {
+                        if let Some(block) = event.content_block {
+                            match block.block_type.as_str() {
+                                "tool_use" => {
                                     // Start tracking a new tool use
                                     current_tool_use = Some(ToolUseState {
                                         id: block.id.unwrap_or_default(),
@@ -492,102 +1573,185 @@ pub async fn send_message_with_tools(
                                         input_json: String::new(),
                                     });
                                 }
+                                "thinking" => {
+                                    current_thinking = Some((String::new(), String::new()));
+                                }
+                                "redacted_thinking" => {
+                                    thinking_blocks.push(ContentBlock::RedactedThinking {
+                                        data: block.data.unwrap_or_default(),
+                                    });
+                                }
+                                _ => {}
                             }
                         }
-                        "content_block_delta" => {
-                            if let Some(delta) = event.delta {
-                                match delta.delta_type.as_str() {
-                                    "text_delta" => {
-                                        if let Some(text) = delta.text {
-                                            text_content.push_str(&text);
-
-                                            // Emit chunk to frontend
-                                            let _ = app.emit(
-                                                "claude-stream-chunk",
-                                                StreamChunk {
-                                                    chunk: text,
-                                                    done: false,
-                                                },
-                                            );
+                    }
+                    "content_block_delta" => {
+                        if let Some(delta) = event.delta {
+                            match delta.delta_type.as_str() {
+                                "text_delta" => {
+                                    if let Some(text) = delta.text {
+                                        text_content.push_str(&text);
+                                        progress.record(app, channel_id, &text);
+
+                                        // Emit chunk to frontend
+                                        let _ = app.emit(
+                                            &stream_event("claude-stream-chunk", channel_id),
+                                            StreamChunk {
+                                                chunk: text,
+                                                done: false,
+                                            },
+                                        );
+                                    }
+                                }
+                                "thinking_delta" => {
+                                    if let Some(text) = delta.thinking {
+                                        thinking.push_str(&text);
+                                        if let Some((block, _)) = current_thinking.as_mut() {
+                                            block.push_str(&text);
                                         }
+                                        progress.record(app, channel_id, &text);
+
+                                        // Kept off the text stream so it can
+                                        // be shown separately
+                                        let _ = app.emit(
+                                            &stream_event("claude-thinking-chunk", channel_id),
+                                            StreamChunk {
+                                                chunk: text,
+                                                done: false,
+                                            },
+                                        );
                                     }
-                                    "input_json_delta" => {
-                                        if let Some(partial) = delta.partial_json {
-                                            if let Some(ref mut tool) = current_tool_use {
-                                                tool.input_json.push_str(&partial);
-                                            }
+                                }
+                                "signature_delta" => {
+                                    if let (Some((_, signature)), Some(part)) =
+                                        (current_thinking.as_mut(), delta.signature)
+                                    {
+                                        signature.push_str(&part);
+                                    }
+                                }
+                                "input_json_delta" => {
+                                    if let Some(partial) = delta.partial_json {
+                                        if let Some(ref mut tool) = current_tool_use {
+                                            tool.input_json.push_str(&partial);
                                         }
                                     }
-                                    _ => {}
                                 }
+                                _ => {}
                             }
                         }
-                        "content_block_stop" => {
-                            // If we were building a tool use, finalize it
-                            if let Some(tool) = current_tool_use.take() {
-                                // Parse the accumulated JSON
-                                let input: serde_json::Value =
-                                    serde_json::from_str(&tool.input_json)
-                                        .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
-
-                                let tool_event = ToolUseEvent {
-                                    id: tool.id.clone(),
-                                    name: tool.name.clone(),
-                                    input: input.clone(),
-                                };
-
-                                // Emit tool use event to frontend
-                                let _ = app.emit("claude-tool-use", tool_event.clone());
-
-                                tool_uses.push(tool_event);
+                    }
+                    "content_block_stop" => {
+                        if let Some((thinking, signature)) = current_thinking.take() {
+                            thinking_blocks.push(ContentBlock::Thinking {
+                                thinking,
+                                signature,
+                            });
+                        }
+                        // If we were building a tool use, finalize it
+                        if let Some(tool) = current_tool_use.take() {
+                            // Parse the accumulated JSON
+                            let input: serde_json::Value = serde_json::from_str(&tool.input_json)
+                                .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
+
+                            let tool_event = ToolUseEvent {
+                                id: tool.id.clone(),
+                                name: tool.name.clone(),
+                                input: input.clone(),
+                            };
+
+                            // Emit tool use event to frontend
+                            let _ = app.emit(
+                                &stream_event("claude-tool-use", channel_id),
+                                tool_event.clone(),
+                            );
+
+                            tool_uses.push(tool_event);
+                        }
+                    }
+                    "message_delta" => {
+                        // The final stop reason (and matched stop sequence)
+                        // arrives here, just before message_stop
+                        if let Some(delta) = event.delta {
+                            if let Some(reason) = delta.stop_reason {
+                                stop_reason = reason;
+                            }
+                            if delta.stop_sequence.is_some() {
+                                stop_sequence = delta.stop_sequence;
                             }
                         }
-                        "message_delta" => {
-                            // Stop reason is handled separately in message_stop
-                            // message_delta may contain other info we don't need
+                        if let (Some(total), Some(delta_usage)) = (usage.as_mut(), event.usage) {
+                            total.output_tokens = delta_usage.output_tokens;
                         }
-                        "message_stop" => {
-                            // Get stop reason from the message info if available
-                            if let Some(msg) = event.message {
-                                if let Some(reason) = msg.stop_reason {
-                                    stop_reason = reason;
-                                }
+                    }
+                    "message_stop" => {
+                        finished = true;
+                        // Get stop reason from the message info if available
+                        if let Some(msg) = event.message {
+                            if let Some(reason) = msg.stop_reason {
+                                stop_reason = reason;
                             }
+                        }
 
-                            // Emit done signal
-                            let _ = app.emit(
-                                "claude-stream-chunk",
-                                StreamChunk {
-                                    chunk: String::new(),
-                                    done: true,
-                                },
-                            );
-
-                            // Emit message stop event
+                        progress.stop(app, channel_id);
+
+                        // Emit done signal
+                        let _ = app.emit(
+                            &stream_event("claude-stream-chunk", channel_id),
+                            StreamChunk {
+                                chunk: String::new(),
+                                done: true,
+                            },
+                        );
+
+                        // Emit message stop event
+                        let _ = app.emit(
+                            &stream_event("claude-message-stop", channel_id),
+                            MessageStopEvent {
+                                stop_reason: stop_reason.clone(),
+                            },
+                        );
+                    }
+                    "error" => {
+                        if let Some(err) = event.error {
+                            let error_msg = format!("{}: {}", err.error_type, err.message);
                             let _ = app.emit(
-                                "claude-message-stop",
-                                MessageStopEvent {
-                                    stop_reason: stop_reason.clone(),
+                                &stream_event("claude-stream-error", channel_id),
+                                StreamError {
+                                    error: error_msg.clone(),
                                 },
                             );
+                            return Err(ClaudeError::Api(error_msg));
                         }
-                        "error" => {
-                            if let Some(err) = event.error {
-                                let error_msg = format!("{}: {}", err.error_type, err.message);
-                                let _ = app.emit(
-                                    "claude-stream-error",
-                                    StreamError { error: error_msg.clone() },
-                                );
-                                return Err(ClaudeError::Api(error_msg));
-                            }
-                        }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
         }
     }
 
+    if cancel.is_cancelled() {
+        let _ = app.emit(
+            &stream_event("claude-stream-chunk", channel_id),
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+        return Ok(AssistantResponse {
+            text_content,
+            tool_uses,
+            stop_reason: String::from("cancelled"),
+            stop_sequence: None,
+            usage,
+            thinking: (!thinking.is_empty()).then_some(thinking),
+            thinking_blocks,
+        });
+    }
+
+    if !finished {
+        return Err(stream_ended_early(app, channel_id));
+    }
+
     // Handle the case where stop_reason comes from tool_use
     if !tool_uses.is_empty() && stop_reason == "end_turn" {
         stop_reason = String::from("tool_use");
@@ -597,25 +1761,311 @@ pub async fn send_message_with_tools(
         text_content,
         tool_uses,
         stop_reason,
+        stop_sequence,
+        usage,
+        thinking: (!thinking.is_empty()).then_some(thinking),
+        thinking_blocks,
     })
 }
 
 // ============================================
-// Authenticated Claude API (via Supabase proxy)
+// Backend tool-use loop
 // ============================================
 
-const SUPABASE_URL: &str = "https://ausxaxibmaztljouwhyx.supabase.co";
-// Modern publishable key format (replaces legacy JWT anon key)
-const SUPABASE_ANON_KEY: &str = "sb_publishable_cYznAZlD3GCHzRs4SWxtNw_L9pFRJal";
+const DEFAULT_MAX_TOOL_ITERATIONS: u32 = 10;
+/// How long to wait for the frontend to run a round of tools
+const TOOL_RESULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Tool results submitted by the frontend, keyed by channel id and
+/// tool_use_id. Results may arrive before the loop starts waiting for them,
+/// so they are buffered while any conversation on the channel is running.
+#[derive(Default)]
+pub struct ToolResultState {
+    pending: std::sync::Mutex<PendingToolResults>,
+    notify: tokio::sync::Notify,
+}
 
-fn get_supabase_url() -> Option<String> {
-    Some(SUPABASE_URL.to_string())
+#[derive(Default)]
+struct PendingToolResults {
+    /// Conversations running per channel
+    open: HashMap<String, usize>,
+    results: HashMap<(String, String), ContentBlock>,
 }
 
-fn get_supabase_anon_key() -> Option<String> {
-    Some(SUPABASE_ANON_KEY.to_string())
+impl ToolResultState {
+    /// Start buffering results for a conversation on `channel_id`. When the
+    /// last conversation on the channel ends, the guard drops whatever is
+    /// left of its results.
+    fn open(&self, channel_id: &str) -> ToolResultGuard<'_> {
+        *self
+            .pending
+            .lock()
+            .unwrap()
+            .open
+            .entry(channel_id.to_string())
+            .or_default() += 1;
+        ToolResultGuard {
+            state: self,
+            channel_id: channel_id.to_string(),
+        }
+    }
+
+    /// Buffer a result. Returns false if no conversation is running on
+    /// `channel_id`.
+    fn insert(&self, channel_id: &str, tool_use_id: String, result: ContentBlock) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if !pending.open.contains_key(channel_id) {
+            return false;
+        }
+        pending
+            .results
+            .insert((channel_id.to_string(), tool_use_id), result);
+        drop(pending);
+        self.notify.notify_waiters();
+        true
+    }
+
+    /// Take the results for all the given ids, or None if any is still missing
+    fn take_all(&self, channel_id: &str, ids: &[String]) -> Option<Vec<ContentBlock>> {
+        let mut pending = self.pending.lock().unwrap();
+        let keys: Vec<(String, String)> = ids
+            .iter()
+            .map(|id| (channel_id.to_string(), id.clone()))
+            .collect();
+        if !keys.iter().all(|key| pending.results.contains_key(key)) {
+            return None;
+        }
+        Some(
+            keys.iter()
+                .filter_map(|key| pending.results.remove(key))
+                .collect(),
+        )
+    }
+}
+
+struct ToolResultGuard<'a> {
+    state: &'a ToolResultState,
+    channel_id: String,
 }
 
+impl Drop for ToolResultGuard<'_> {
+    fn drop(&mut self) {
+        let mut pending = self.state.pending.lock().unwrap();
+        let Some(count) = pending.open.get_mut(&self.channel_id) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            pending.open.remove(&self.channel_id);
+            pending
+                .results
+                .retain(|(channel_id, _), _| *channel_id != self.channel_id);
+        }
+    }
+}
+
+/// Provide the result of a tool call requested during the
+/// `run_tool_conversation` running on `channel_id`
+#[tauri::command]
+pub fn submit_tool_result(
+    tool_results: State<'_, ToolResultState>,
+    channel_id: String,
+    tool_use_id: String,
+    content: String,
+    is_error: Option<bool>,
+) -> Result<(), ClaudeError> {
+    let result = ContentBlock::ToolResult {
+        tool_use_id: tool_use_id.clone(),
+        content,
+        is_error,
+    };
+    if !tool_results.insert(&channel_id, tool_use_id, result) {
+        return Err(ClaudeError::Api(format!(
+            "No tool conversation is running on channel {}",
+            channel_id
+        )));
+    }
+    Ok(())
+}
+
+/// Result of a backend tool-use conversation
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolConversation {
+    /// The full message history, including the new assistant and tool turns
+    pub messages: Vec<Message>,
+    /// The final assistant turn
+    pub response: AssistantResponse,
+    pub iterations: u32,
+}
+
+/// Wait until the frontend has submitted a result for every tool use
+async fn wait_for_tool_results(
+    tool_results: &ToolResultState,
+    cancel: &CancelToken,
+    channel_id: &str,
+    ids: &[String],
+) -> Result<Option<Vec<ContentBlock>>, ClaudeError> {
+    let deadline = tokio::time::Instant::now() + TOOL_RESULT_TIMEOUT;
+
+    loop {
+        // Registered before the check so a result submitted in between
+        // still wakes us
+        let notified = tool_results.notify.notified();
+        if let Some(results) = tool_results.take_all(channel_id, ids) {
+            return Ok(Some(results));
+        }
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(ClaudeError::Api(
+                "Timed out waiting for tool results".to_string(),
+            ));
+        }
+        tokio::select! {
+            _ = notified => {}
+            _ = cancel.cancelled() => {}
+            _ = tokio::time::sleep_until(deadline) => {}
+        }
+    }
+}
+
+/// Run a multi-turn tool conversation in the backend.
+/// Each turn streams like `send_message_with_tools`. When Claude stops to use
+/// tools, the loop waits for a result for every 'claude-tool-use' event via
+/// `submit_tool_result`, appends them, and continues until Claude finishes or
+/// `max_iterations` turns have run.
+/// `channel_id` identifies the conversation: events are emitted as
+/// `<event>:<channel_id>`, tool results are submitted for it, and
+/// `cancel_generation` for that channel stops only this conversation.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_tool_conversation(
+    app: AppHandle,
+    client: State<'_, Client>,
+    generation: State<'_, GenerationState>,
+    tool_results: State<'_, ToolResultState>,
+    messages: Vec<Message>,
+    tools: Vec<Tool>,
+    system_prompt: Option<String>,
+    model: Option<String>,
+    max_iterations: Option<u32>,
+    beta_features: Option<Vec<String>>,
+    channel_id: String,
+) -> Result<ToolConversation, ClaudeError> {
+    validate_channel_id(Some(&channel_id))?;
+
+    let api_key = super::keychain::get_api_key(None)
+        .map_err(|e| ClaudeError::Api(e.to_string()))?
+        .ok_or(ClaudeError::NoApiKey)?;
+
+    let model = model.unwrap_or_else(default_model);
+    let max_iterations = max_iterations.unwrap_or(DEFAULT_MAX_TOOL_ITERATIONS).max(1);
+    let cancel = generation.begin(Some(&channel_id));
+    let _tool_results = tool_results.open(&channel_id);
+
+    let system_blocks = system_prompt.map(|text| {
+        vec![SystemBlock {
+            block_type: "text".to_string(),
+            text,
+            cache_control: Some(CacheControl {
+                cache_type: "ephemeral".to_string(),
+            }),
+        }]
+    });
+
+    let beta = anthropic_beta(true, beta_features.as_deref());
+    let mut messages = messages;
+    let mut iterations = 0;
+
+    loop {
+        iterations += 1;
+
+        let request_body = ClaudeRequest {
+            model: model.clone(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            system: system_blocks.clone(),
+            messages: messages.clone(),
+            stream: true,
+            tools: Some(tools.clone()),
+            temperature: None,
+            top_p: None,
+            stop_sequences: None,
+            thinking: None,
+        };
+
+        let response = stream_tool_turn(
+            &app,
+            &client,
+            &cancel,
+            &api_key,
+            &request_body,
+            beta.as_deref(),
+            DEFAULT_MAX_RETRIES,
+            Some(&channel_id),
+        )
+        .await?;
+
+        // Record the assistant turn so the next request has the full context.
+        // With thinking enabled, the API wants the turn's thinking blocks back
+        // first, signatures intact.
+        let mut blocks = response.thinking_blocks.clone();
+        if !response.text_content.is_empty() {
+            blocks.push(ContentBlock::Text {
+                text: response.text_content.clone(),
+            });
+        }
+        for tool_use in &response.tool_uses {
+            blocks.push(ContentBlock::ToolUse {
+                id: tool_use.id.clone(),
+                name: tool_use.name.clone(),
+                input: tool_use.input.clone(),
+            });
+        }
+        if !blocks.is_empty() {
+            messages.push(Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(blocks),
+            });
+        }
+
+        if response.stop_reason != "tool_use" || iterations >= max_iterations {
+            if response.stop_reason == "tool_use" {
+                tracing::warn!(iterations, "Tool conversation hit the iteration cap");
+            }
+            return Ok(ToolConversation {
+                messages,
+                response,
+                iterations,
+            });
+        }
+
+        let ids: Vec<String> = response.tool_uses.iter().map(|t| t.id.clone()).collect();
+        let Some(results) =
+            wait_for_tool_results(&tool_results, &cancel, &channel_id, &ids).await?
+        else {
+            return Ok(ToolConversation {
+                messages,
+                response: AssistantResponse {
+                    stop_reason: String::from("cancelled"),
+                    ..response
+                },
+                iterations,
+            });
+        };
+
+        messages.push(Message {
+            role: "user".to_string(),
+            content: MessageContent::Blocks(results),
+        });
+    }
+}
+
+// ============================================
+// Authenticated Claude API (via Supabase proxy)
+// ============================================
+
 /// Request structure for the Supabase Claude proxy
 #[derive(Debug, Serialize)]
 struct ProxyClaudeRequest {
@@ -632,15 +2082,29 @@ struct ProxyClaudeRequest {
 /// Send a message through Supabase proxy with user authentication
 /// This enforces usage limits and plan restrictions
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn send_message_authenticated(
     app: AppHandle,
+    client: State<'_, Client>,
+    generation: State<'_, GenerationState>,
     messages: Vec<Message>,
     system_prompt: Option<String>,
     tools: Option<Vec<Tool>>,
     model: Option<String>,
+    md_path: Option<String>,
 ) -> Result<AssistantResponse, ClaudeError> {
+    let model = model.unwrap_or_else(default_model);
+
+    // Reject models outside the plan here rather than waiting for the proxy's
+    // 403. BYO-key requests go straight to Anthropic and aren't checked.
+    if let Some(allowed) = super::auth::cached_allowed_models() {
+        if !allowed.is_empty() && !allowed.contains(&model) {
+            return Err(ClaudeError::ModelNotAllowed(model));
+        }
+    }
+
     // Get access token from auth session (auto-refreshes if expired)
-    let access_token = match super::auth::get_access_token().await {
+    let access_token = match super::auth::get_access_token(&client).await {
         Ok(token) => {
             tracing::debug!(token_prefix = %&token[..20.min(token.len())], "Got access token");
             token
@@ -652,17 +2116,39 @@ pub async fn send_message_authenticated(
     };
 
     // Get Supabase URL and anon key
-    let supabase_url = get_supabase_url()
-        .ok_or_else(|| ClaudeError::Api("Supabase URL not configured".to_string()))?;
-    let anon_key = get_supabase_anon_key()
-        .ok_or_else(|| ClaudeError::Api("Supabase anon key not configured".to_string()))?;
-
-    let client = Client::new();
-    let model = model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    let supabase_url =
+        super::auth::get_supabase_url().map_err(|e| ClaudeError::Api(e.to_string()))?;
+    let anon_key =
+        super::auth::get_supabase_anon_key().map_err(|e| ClaudeError::Api(e.to_string()))?;
+
+    let cancel = generation.begin(None);
+    let Some(_slot) = app
+        .state::<RequestQueue>()
+        .acquire(&app, None, &cancel)
+        .await
+    else {
+        let _ = app.emit(
+            "claude-stream-chunk",
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+        return Ok(AssistantResponse {
+            text_content: String::new(),
+            tool_uses: Vec::new(),
+            stop_reason: String::from("cancelled"),
+            stop_sequence: None,
+            usage: None,
+            thinking: None,
+            thinking_blocks: Vec::new(),
+        });
+    };
 
+    let system_prompt = document_system_prompt(md_path.as_deref(), system_prompt).await;
     let request_body = ProxyClaudeRequest {
         model,
-        max_tokens: 4096,
+        max_tokens: DEFAULT_MAX_TOKENS,
         system: system_prompt,
         messages,
         stream: true,
@@ -679,8 +2165,7 @@ pub async fn send_message_authenticated(
         .header("Content-Type", "application/json")
         .json(&request_body)
         .send()
-        .await
-        .map_err(|e| ClaudeError::Network(e.to_string()))?;
+        .await?;
 
     let status = response.status();
 
@@ -688,20 +2173,29 @@ pub async fn send_message_authenticated(
     if !status.is_success() {
         let error_body = response.text().await.unwrap_or_default();
 
-        // Try to parse as JSON to extract the error message
-        let error_msg = if let Ok(json) = serde_json::from_str::<serde_json::Value>(&error_body) {
-            json["error"]["message"]
-                .as_str()
-                .unwrap_or(&error_body)
-                .to_string()
-        } else {
-            error_body
-        };
+        // Try to parse as JSON to extract the error type and message
+        let (error_type, error_msg) =
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&error_body) {
+                (
+                    json["error"]["type"].as_str().unwrap_or_default().to_string(),
+                    json["error"]["message"]
+                        .as_str()
+                        .unwrap_or(&error_body)
+                        .to_string(),
+                )
+            } else {
+                (String::new(), error_body)
+            };
 
         tracing::debug!(status = %status, error = %error_msg, "Supabase proxy error");
         return match status.as_u16() {
             401 => Err(ClaudeError::Api("Authentication required. Please sign in.".to_string())),
-            403 => Err(ClaudeError::Api(error_msg)),
+            // Plan limits: no active subscription, model not in plan, or out of credit
+            402 | 403 => Err(ClaudeError::UsageExceeded(error_msg)),
+            // The proxy reports the monthly message cap as a 429
+            429 if error_type == "usage_limit_exceeded" => {
+                Err(ClaudeError::UsageExceeded(error_msg))
+            }
             429 => Err(ClaudeError::RateLimited(error_msg)),
             400 => Err(ClaudeError::Api(error_msg)),
             500..=599 => Err(ClaudeError::Api(format!("Server error: {}", error_msg))),
@@ -713,125 +2207,140 @@ pub async fn send_message_authenticated(
     let mut stream = response.bytes_stream();
     let mut text_content = String::new();
     let mut tool_uses: Vec<ToolUseEvent> = Vec::new();
-    let mut buffer = String::new();
+    let mut sse = SseParser::default();
     let mut stop_reason = String::from("end_turn");
 
     // Track current content block being built
     let mut current_tool_use: Option<ToolUseState> = None;
-
-    while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result.map_err(|e| ClaudeError::Network(e.to_string()))?;
-
-        let chunk_str = String::from_utf8_lossy(&chunk);
-        buffer.push_str(&chunk_str);
-
-        // Process complete SSE events
-        while let Some(newline_pos) = buffer.find('\n') {
-            let line = buffer[..newline_pos].trim().to_string();
-            buffer = buffer[newline_pos + 1..].to_string();
-
-            if line.starts_with("data: ") {
-                let data = &line[6..];
-
-                if data == "[DONE]" {
-                    continue;
-                }
-
-                if let Ok(event) = serde_json::from_str::<StreamEvent>(data) {
-                    match event.event_type.as_str() {
-                        "content_block_start" => {
-                            if let Some(block) = event.content_block {
-                                if block.block_type == "tool_use" {
-                                    current_tool_use = Some(ToolUseState {
-                                        id: block.id.unwrap_or_default(),
-                                        name: block.name.unwrap_or_default(),
-                                        input_json: String::new(),
-                                    });
-                                }
+    // Set by message_stop; a stream that ends without it was cut off
+    let mut finished = false;
+
+    while let Some(chunk_result) = next_chunk(&mut stream, &cancel).await? {
+        let chunk = chunk_result?;
+
+        sse.push(&chunk);
+
+        // Process complete SSE events; pings and blank lines are skipped
+        while let Some(data) = sse.next_data() {
+            if let Ok(event) = serde_json::from_str::<StreamEvent>(&data) {
+                match event.event_type.as_str() {
+                    "content_block_start" => {
+                        if let Some(block) = event.content_block {
+                            if block.block_type == "tool_use" {
+                                current_tool_use = Some(ToolUseState {
+                                    id: block.id.unwrap_or_default(),
+                                    name: block.name.unwrap_or_default(),
+                                    input_json: String::new(),
+                                });
                             }
                         }
-                        "content_block_delta" => {
-                            if let Some(delta) = event.delta {
-                                match delta.delta_type.as_str() {
-                                    "text_delta" => {
-                                        if let Some(text) = delta.text {
-                                            text_content.push_str(&text);
-
-                                            let _ = app.emit(
-                                                "claude-stream-chunk",
-                                                StreamChunk {
-                                                    chunk: text,
-                                                    done: false,
-                                                },
-                                            );
-                                        }
+                    }
+                    "content_block_delta" => {
+                        if let Some(delta) = event.delta {
+                            match delta.delta_type.as_str() {
+                                "text_delta" => {
+                                    if let Some(text) = delta.text {
+                                        text_content.push_str(&text);
+
+                                        let _ = app.emit(
+                                            "claude-stream-chunk",
+                                            StreamChunk {
+                                                chunk: text,
+                                                done: false,
+                                            },
+                                        );
                                     }
-                                    "input_json_delta" => {
-                                        if let Some(partial) = delta.partial_json {
-                                            if let Some(ref mut tool) = current_tool_use {
-                                                tool.input_json.push_str(&partial);
-                                            }
+                                }
+                                "input_json_delta" => {
+                                    if let Some(partial) = delta.partial_json {
+                                        if let Some(ref mut tool) = current_tool_use {
+                                            tool.input_json.push_str(&partial);
                                         }
                                     }
-                                    _ => {}
                                 }
+                                _ => {}
                             }
                         }
-                        "content_block_stop" => {
-                            if let Some(tool) = current_tool_use.take() {
-                                let input: serde_json::Value =
-                                    serde_json::from_str(&tool.input_json)
-                                        .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
-
-                                let tool_event = ToolUseEvent {
-                                    id: tool.id.clone(),
-                                    name: tool.name.clone(),
-                                    input: input.clone(),
-                                };
-
-                                let _ = app.emit("claude-tool-use", tool_event.clone());
-                                tool_uses.push(tool_event);
-                            }
+                    }
+                    "content_block_stop" => {
+                        if let Some(tool) = current_tool_use.take() {
+                            let input: serde_json::Value = serde_json::from_str(&tool.input_json)
+                                .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
+
+                            let tool_event = ToolUseEvent {
+                                id: tool.id.clone(),
+                                name: tool.name.clone(),
+                                input: input.clone(),
+                            };
+
+                            let _ = app.emit("claude-tool-use", tool_event.clone());
+                            tool_uses.push(tool_event);
                         }
-                        "message_stop" => {
-                            if let Some(msg) = event.message {
-                                if let Some(reason) = msg.stop_reason {
-                                    stop_reason = reason;
-                                }
+                    }
+                    "message_stop" => {
+                        finished = true;
+                        if let Some(msg) = event.message {
+                            if let Some(reason) = msg.stop_reason {
+                                stop_reason = reason;
                             }
+                        }
 
+                        let _ = app.emit(
+                            "claude-stream-chunk",
+                            StreamChunk {
+                                chunk: String::new(),
+                                done: true,
+                            },
+                        );
+
+                        let _ = app.emit(
+                            "claude-message-stop",
+                            MessageStopEvent {
+                                stop_reason: stop_reason.clone(),
+                            },
+                        );
+                    }
+                    "error" => {
+                        if let Some(err) = event.error {
+                            let error_msg = format!("{}: {}", err.error_type, err.message);
                             let _ = app.emit(
-                                "claude-stream-chunk",
-                                StreamChunk {
-                                    chunk: String::new(),
-                                    done: true,
-                                },
-                            );
-
-                            let _ = app.emit(
-                                "claude-message-stop",
-                                MessageStopEvent {
-                                    stop_reason: stop_reason.clone(),
+                                "claude-stream-error",
+                                StreamError {
+                                    error: error_msg.clone(),
                                 },
                             );
+                            return Err(ClaudeError::Api(error_msg));
                         }
-                        "error" => {
-                            if let Some(err) = event.error {
-                                let error_msg = format!("{}: {}", err.error_type, err.message);
-                                let _ = app.emit(
-                                    "claude-stream-error",
-                                    StreamError { error: error_msg.clone() },
-                                );
-                                return Err(ClaudeError::Api(error_msg));
-                            }
-                        }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
         }
     }
 
+    if cancel.is_cancelled() {
+        let _ = app.emit(
+            "claude-stream-chunk",
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+        return Ok(AssistantResponse {
+            text_content,
+            tool_uses,
+            stop_reason: String::from("cancelled"),
+            stop_sequence: None,
+            usage: None,
+            thinking: None,
+            thinking_blocks: Vec::new(),
+        });
+    }
+
+    if !finished {
+        return Err(stream_ended_early(&app, None));
+    }
+
     if !tool_uses.is_empty() && stop_reason == "end_turn" {
         stop_reason = String::from("tool_use");
     }
@@ -840,5 +2349,255 @@ pub async fn send_message_authenticated(
         text_content,
         tool_uses,
         stop_reason,
+        stop_sequence: None,
+        usage: None,
+        thinking: None,
+        thinking_blocks: Vec::new(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_use(id: &str) -> ContentBlock {
+        ContentBlock::ToolUse {
+            id: id.to_string(),
+            name: "edit_document".to_string(),
+            input: serde_json::json!({}),
+        }
+    }
+
+    fn tool_result(id: &str) -> ContentBlock {
+        ContentBlock::ToolResult {
+            tool_use_id: id.to_string(),
+            content: "ok".to_string(),
+            is_error: None,
+        }
+    }
+
+    fn message(role: &str, blocks: Vec<ContentBlock>) -> Message {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Blocks(blocks),
+        }
+    }
+
+    fn text(role: &str, text: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Text(text.to_string()),
+        }
+    }
+
+    #[test]
+    fn tool_pairing_accepts_answered_tool_use() {
+        let messages = vec![
+            text("user", "Tighten the intro"),
+            message("assistant", vec![tool_use("toolu_1")]),
+            message("user", vec![tool_result("toolu_1")]),
+        ];
+        assert!(validate_tool_pairing(&messages).is_ok());
+    }
+
+    #[test]
+    fn tool_pairing_accepts_trailing_tool_use() {
+        let messages = vec![
+            text("user", "Tighten the intro"),
+            message("assistant", vec![tool_use("toolu_1")]),
+        ];
+        assert!(validate_tool_pairing(&messages).is_ok());
+    }
+
+    #[test]
+    fn tool_pairing_rejects_orphan_result() {
+        let messages = vec![
+            text("user", "Tighten the intro"),
+            text("assistant", "Sure"),
+            message("user", vec![tool_result("toolu_1")]),
+        ];
+        match validate_tool_pairing(&messages) {
+            Err(ClaudeError::Api(msg)) => assert!(msg.contains("toolu_1"), "{}", msg),
+            other => panic!("expected an orphan result error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tool_pairing_rejects_orphan_use() {
+        let messages = vec![
+            text("user", "Tighten the intro"),
+            message("assistant", vec![tool_use("toolu_1"), tool_use("toolu_2")]),
+            message("user", vec![tool_result("toolu_1")]),
+        ];
+        match validate_tool_pairing(&messages) {
+            Err(ClaudeError::Api(msg)) => assert!(msg.contains("toolu_2"), "{}", msg),
+            other => panic!("expected an orphan use error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tool_results_are_scoped_to_their_conversation() {
+        let tool_results = ToolResultState::default();
+        let first = tool_results.open("chat-1");
+        let _second = tool_results.open("chat-2");
+        let ids = vec!["toolu_1".to_string()];
+
+        assert!(tool_results.insert("chat-2", "toolu_1".into(), tool_result("toolu_1")));
+        assert!(tool_results.take_all("chat-1", &ids).is_none());
+        assert!(tool_results.take_all("chat-2", &ids).is_some());
+
+        // Leftovers go when the conversation ends, and late results are refused
+        assert!(tool_results.insert("chat-1", "toolu_2".into(), tool_result("toolu_2")));
+        drop(first);
+        assert!(tool_results.pending.lock().unwrap().results.is_empty());
+        assert!(!tool_results.insert("chat-1", "toolu_3".into(), tool_result("toolu_3")));
+    }
+
+    #[test]
+    fn conversations_sharing_a_channel_keep_each_others_results() {
+        let tool_results = ToolResultState::default();
+        let first = tool_results.open("chat-1");
+        assert!(tool_results.insert("chat-1", "toolu_1".into(), tool_result("toolu_1")));
+
+        // A second conversation on the channel neither clears the buffer nor
+        // loses it when the first one ends
+        let _second = tool_results.open("chat-1");
+        assert!(tool_results.insert("chat-1", "toolu_2".into(), tool_result("toolu_2")));
+        drop(first);
+        assert!(tool_results
+            .take_all("chat-1", &["toolu_1".to_string(), "toolu_2".to_string()])
+            .is_some());
+        assert!(tool_results.insert("chat-1", "toolu_3".into(), tool_result("toolu_3")));
+    }
+
+    #[test]
+    fn cancel_only_stops_its_own_channel() {
+        let generation = GenerationState::default();
+        let first = generation.begin(Some("chat-1"));
+        let second = generation.begin(Some("chat-2"));
+        let unnamed = generation.begin(None);
+
+        generation.cancel(Some("chat-1"));
+        assert!(first.is_cancelled());
+        assert!(!second.is_cancelled());
+        assert!(!unnamed.is_cancelled());
+
+        generation.cancel(None);
+        assert!(unnamed.is_cancelled());
+        assert!(!second.is_cancelled());
+    }
+
+    #[test]
+    fn finished_generation_unregisters_its_token() {
+        let generation = GenerationState::default();
+        let earlier = generation.begin(Some("chat-1"));
+        drop(generation.begin(Some("chat-1")));
+        assert_eq!(generation.tokens.lock().unwrap()["chat-1"].len(), 1);
+
+        // A new request on the channel starts uncancelled
+        generation.cancel(Some("chat-1"));
+        drop(earlier);
+        assert!(generation.tokens.lock().unwrap().is_empty());
+        assert!(!generation.begin(Some("chat-1")).is_cancelled());
+    }
+
+    fn hold_slot(queue: &RequestQueue) -> RequestSlot {
+        RequestSlot {
+            permit: Some(queue.permits.clone().try_acquire_owned().unwrap()),
+            limits: queue.limits.clone(),
+        }
+    }
+
+    #[test]
+    fn lowering_the_limit_retires_slots_as_they_are_released() {
+        let queue = RequestQueue::default();
+        queue.set_limit(2);
+        let slots = [hold_slot(&queue), hold_slot(&queue)];
+
+        queue.set_limit(1);
+        drop(slots);
+        assert_eq!(queue.permits.available_permits(), 1);
+    }
+
+    #[test]
+    fn raising_the_limit_after_lowering_it_is_not_undercut() {
+        let queue = RequestQueue::default();
+        queue.set_limit(2);
+        let slots = [hold_slot(&queue), hold_slot(&queue)];
+
+        queue.set_limit(1);
+        queue.set_limit(3);
+        drop(slots);
+        assert_eq!(queue.permits.available_permits(), 3);
+        assert_eq!(queue.limit(), 3);
+    }
+
+    #[tokio::test]
+    async fn next_chunk_returns_when_cancelled_mid_stall() {
+        let generation = GenerationState::default();
+        let cancel = generation.begin(None);
+        let mut stalled = futures::stream::pending::<Result<Vec<u8>, ClaudeError>>();
+
+        let (next, _) = tokio::join!(next_chunk(&mut stalled, &cancel), async {
+            tokio::task::yield_now().await;
+            generation.cancel(None);
+        });
+        assert!(matches!(next, Ok(None)));
+    }
+
+    const SSE_STREAM: &str = concat!(
+        "event: ping\n",
+        "data: {\"type\": \"ping\"}\n",
+        "\n",
+        "event: content_block_delta\n",
+        "data: {\"text\":\"Caf\u{e9} \u{65e5}\u{672c}\"}\n",
+        "\n",
+        "\n",
+        ": keepalive comment\n",
+        "event: content_block_delta\n",
+        "data: {\"text\":\"na\u{ef}ve \u{1f58b}\"}\n",
+        "\n",
+        "event: message_stop\n",
+        "data: [DONE]\n",
+        "\n",
+    );
+
+    fn parse_in_chunks(bytes: &[u8], boundaries: &[usize]) -> Vec<String> {
+        let mut parser = SseParser::default();
+        let mut payloads = Vec::new();
+        let mut start = 0;
+        for &end in boundaries.iter().chain(std::iter::once(&bytes.len())) {
+            parser.push(&bytes[start..end]);
+            while let Some(data) = parser.next_data() {
+                payloads.push(data);
+            }
+            start = end;
+        }
+        payloads
+    }
+
+    #[test]
+    fn sse_parser_reassembles_split_chunks() {
+        let bytes = SSE_STREAM.as_bytes();
+        let expected = vec![
+            "{\"text\":\"Caf\u{e9} \u{65e5}\u{672c}\"}".to_string(),
+            "{\"text\":\"na\u{ef}ve \u{1f58b}\"}".to_string(),
+        ];
+
+        // Cut in the middle of the two-byte "\u{e9}" and the four-byte emoji
+        let e_acute = SSE_STREAM.find('\u{e9}').unwrap();
+        let emoji = SSE_STREAM.find('\u{1f58b}').unwrap();
+        assert_eq!(parse_in_chunks(bytes, &[e_acute + 1, emoji + 2]), expected);
+
+        // Every chunk size, down to a byte at a time
+        for size in 1..=bytes.len() {
+            let boundaries: Vec<usize> = (size..bytes.len()).step_by(size).collect();
+            assert_eq!(
+                parse_in_chunks(bytes, &boundaries),
+                expected,
+                "chunk size {}",
+                size
+            );
+        }
+    }
+}
@@ -0,0 +1,1709 @@
+use super::file::{
+    collect_markdown_files, get_sidecar_path, load_existing_sidecar, save_sidecar, write_atomic,
+    FileError,
+};
+use crate::models::{DocumentStage, Sidecar};
+use docx_rs::{
+    AbstractNumbering, AlignmentType, Bold, BreakType, DocumentChild, Docx, HyperlinkData,
+    IndentLevel, InsertChild, Italic, Level, LevelJc, LevelOverride, LevelText, NumberFormat,
+    Numbering, NumberingId, Paragraph, ParagraphChild, Run, RunChild, RunFonts, SpecialIndentType,
+    Start, Style, StyleType, Table, TableCellContent, TableChild, TableRowChild,
+};
+use printpdf::{
+    BuiltinFont, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference,
+    PdfLayerReference, Point, Rgb,
+};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+// ============================================
+// Markdown block model shared by exporters
+// ============================================
+
+/// A run of text with inline styling
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+}
+
+/// Block-level elements the exporters know how to render
+#[derive(Debug, Clone)]
+pub(crate) enum Block {
+    Heading {
+        level: u8,
+        spans: Vec<Span>,
+    },
+    Paragraph {
+        spans: Vec<Span>,
+    },
+    /// `marker` is empty for continuation paragraphs inside the same item
+    ListItem {
+        marker: String,
+        depth: usize,
+        spans: Vec<Span>,
+    },
+    Quote {
+        spans: Vec<Span>,
+    },
+    Code {
+        text: String,
+    },
+    Rule,
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// Parse markdown into a flat list of blocks
+pub(crate) fn parse_blocks(markdown: &str) -> Vec<Block> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let parser = Parser::new_ext(markdown, options);
+
+    let mut blocks = Vec::new();
+    let mut spans: Vec<Span> = Vec::new();
+    let mut bold = 0usize;
+    let mut italic = 0usize;
+    let mut quote_depth = 0usize;
+    // Next number for ordered lists, None for bullet lists
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut item_marker: Option<String> = None;
+    let mut code_block: Option<String> = None;
+
+    // Turn the collected spans into a block based on where we are
+    fn flush(
+        blocks: &mut Vec<Block>,
+        spans: &mut Vec<Span>,
+        lists: &[Option<u64>],
+        item_marker: &mut Option<String>,
+        quote_depth: usize,
+    ) {
+        if spans.iter().all(|s| s.text.trim().is_empty()) {
+            spans.clear();
+            return;
+        }
+        let spans = std::mem::take(spans);
+        if !lists.is_empty() {
+            blocks.push(Block::ListItem {
+                marker: item_marker.take().unwrap_or_default(),
+                depth: lists.len() - 1,
+                spans,
+            });
+        } else if quote_depth > 0 {
+            blocks.push(Block::Quote { spans });
+        } else {
+            blocks.push(Block::Paragraph { spans });
+        }
+    }
+
+    for event in parser {
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Heading { .. } => flush(
+                    &mut blocks,
+                    &mut spans,
+                    &lists,
+                    &mut item_marker,
+                    quote_depth,
+                ),
+                Tag::BlockQuote(_) => quote_depth += 1,
+                Tag::CodeBlock(_) => code_block = Some(String::new()),
+                Tag::List(start) => {
+                    flush(
+                        &mut blocks,
+                        &mut spans,
+                        &lists,
+                        &mut item_marker,
+                        quote_depth,
+                    );
+                    lists.push(start);
+                }
+                Tag::Item => {
+                    let marker = match lists.last_mut() {
+                        Some(Some(n)) => {
+                            let marker = format!("{}.", n);
+                            *n += 1;
+                            marker
+                        }
+                        _ => "\u{2022}".to_string(),
+                    };
+                    item_marker = Some(marker);
+                }
+                Tag::Emphasis => italic += 1,
+                Tag::Strong => bold += 1,
+                _ => {}
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Heading(level) => {
+                    let spans = std::mem::take(&mut spans);
+                    if !spans.is_empty() {
+                        blocks.push(Block::Heading {
+                            level: heading_level(level),
+                            spans,
+                        });
+                    }
+                }
+                TagEnd::Paragraph | TagEnd::Item | TagEnd::TableHead | TagEnd::TableRow => flush(
+                    &mut blocks,
+                    &mut spans,
+                    &lists,
+                    &mut item_marker,
+                    quote_depth,
+                ),
+                TagEnd::BlockQuote(_) => {
+                    flush(
+                        &mut blocks,
+                        &mut spans,
+                        &lists,
+                        &mut item_marker,
+                        quote_depth,
+                    );
+                    quote_depth = quote_depth.saturating_sub(1);
+                }
+                TagEnd::CodeBlock => {
+                    if let Some(text) = code_block.take() {
+                        blocks.push(Block::Code {
+                            text: text.trim_end_matches('\n').to_string(),
+                        });
+                    }
+                }
+                TagEnd::List(_) => {
+                    lists.pop();
+                }
+                TagEnd::TableCell => spans.push(Span {
+                    text: "   ".to_string(),
+                    ..Default::default()
+                }),
+                TagEnd::Emphasis => italic = italic.saturating_sub(1),
+                TagEnd::Strong => bold = bold.saturating_sub(1),
+                _ => {}
+            },
+            Event::Text(text) => {
+                if let Some(code) = code_block.as_mut() {
+                    code.push_str(&text);
+                } else {
+                    spans.push(Span {
+                        text: text.to_string(),
+                        bold: bold > 0,
+                        italic: italic > 0,
+                        code: false,
+                    });
+                }
+            }
+            Event::Code(text) => spans.push(Span {
+                text: text.to_string(),
+                bold: bold > 0,
+                italic: italic > 0,
+                code: true,
+            }),
+            Event::SoftBreak | Event::HardBreak => spans.push(Span {
+                text: " ".to_string(),
+                ..Default::default()
+            }),
+            Event::Rule => {
+                flush(
+                    &mut blocks,
+                    &mut spans,
+                    &lists,
+                    &mut item_marker,
+                    quote_depth,
+                );
+                blocks.push(Block::Rule);
+            }
+            _ => {}
+        }
+    }
+
+    flush(
+        &mut blocks,
+        &mut spans,
+        &lists,
+        &mut item_marker,
+        quote_depth,
+    );
+    blocks
+}
+
+// ============================================
+// Export analysis
+// ============================================
+
+/// What an export will produce, without writing it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPreview {
+    pub page_count: usize,
+    pub word_count: usize,
+    /// Markdown elements the exporter drops or flattens, e.g. "table"
+    pub unsupported_elements: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// A document parsed for export, plus what won't survive the conversion
+struct ExportAnalysis {
+    blocks: Vec<Block>,
+    word_count: usize,
+    unsupported_elements: Vec<String>,
+}
+
+/// Parse a document for export and note the markdown the exporters can't render
+fn analyze_markdown(markdown: &str) -> ExportAnalysis {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS;
+
+    let mut unsupported_elements: Vec<String> = Vec::new();
+    for event in Parser::new_ext(markdown, options) {
+        let element = match event {
+            Event::Start(Tag::Table(_)) => "table",
+            Event::Start(Tag::Image { .. }) => "image",
+            Event::Start(Tag::Link { .. }) => "link",
+            Event::Start(Tag::Strikethrough) => "strikethrough",
+            Event::Start(Tag::FootnoteDefinition(_)) | Event::FootnoteReference(_) => "footnote",
+            Event::TaskListMarker(_) => "task list checkbox",
+            Event::Html(_) | Event::InlineHtml(_) => "HTML",
+            _ => continue,
+        };
+        if !unsupported_elements.iter().any(|e| e == element) {
+            unsupported_elements.push(element.to_string());
+        }
+    }
+
+    let blocks = parse_blocks(markdown);
+    let word_count = blocks
+        .iter()
+        .map(|block| match block {
+            Block::Heading { spans, .. }
+            | Block::Paragraph { spans }
+            | Block::ListItem { spans, .. }
+            | Block::Quote { spans } => spans
+                .iter()
+                .map(|s| s.text.split_whitespace().count())
+                .sum(),
+            Block::Code { text } => text.split_whitespace().count(),
+            Block::Rule => 0,
+        })
+        .sum();
+
+    ExportAnalysis {
+        blocks,
+        word_count,
+        unsupported_elements,
+    }
+}
+
+impl ExportAnalysis {
+    fn preview(&self, page_count: usize, warnings: Vec<String>) -> ExportPreview {
+        if !self.unsupported_elements.is_empty() {
+            tracing::debug!(
+                elements = ?self.unsupported_elements,
+                "Export drops unsupported markdown"
+            );
+        }
+        ExportPreview {
+            page_count,
+            word_count: self.word_count,
+            unsupported_elements: self.unsupported_elements.clone(),
+            warnings,
+        }
+    }
+}
+
+/// Title and core argument from the document's concept, if it has one
+async fn concept_title(md_path: &str) -> Option<(String, Option<String>)> {
+    let concept = load_existing_sidecar(&PathBuf::from(md_path))
+        .await
+        .and_then(|s| s.concept.current)?;
+
+    if concept.title.trim().is_empty() {
+        return None;
+    }
+    let subtitle = Some(concept.core_argument).filter(|a| !a.trim().is_empty());
+    Some((concept.title, subtitle))
+}
+
+/// Title for an exported document: the concept title if there is one,
+/// otherwise the file name.
+async fn document_title(md_path: &str) -> (String, Option<String>) {
+    if let Some(title) = concept_title(md_path).await {
+        return title;
+    }
+
+    let stem = PathBuf::from(md_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Untitled".to_string());
+    (stem, None)
+}
+
+// ============================================
+// PDF export
+// ============================================
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    #[default]
+    Letter,
+    A4,
+}
+
+impl PageSize {
+    /// Width and height in millimetres
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::Letter => (215.9, 279.4),
+            PageSize::A4 => (210.0, 297.0),
+        }
+    }
+}
+
+/// Page margins in millimetres
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfMargins {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Default for PdfMargins {
+    fn default() -> Self {
+        // One inch on every side
+        PdfMargins {
+            top: 25.4,
+            right: 25.4,
+            bottom: 25.4,
+            left: 25.4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfExportOptions {
+    pub page_size: PageSize,
+    pub margins: PdfMargins,
+    /// Add a title page built from the sidecar concept
+    pub include_title_page: bool,
+}
+
+const PT_TO_MM: f32 = 0.352_778;
+const BODY_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 9.5;
+const LINE_SPACING: f32 = 1.4;
+const LIST_INDENT: f32 = 6.0;
+const QUOTE_INDENT: f32 = 8.0;
+
+/// Helvetica advance widths (per 1000 units of font size).
+/// The builtin PDF fonts don't carry metrics, so wrapping needs these.
+fn helvetica_width(c: char) -> f32 {
+    let w = match c {
+        ' ' | '!' | ',' | '.' | '/' | ':' | ';' | '[' | '\\' | ']' | 'I' | 'f' | 't' => 278,
+        '"' => 355,
+        '#' | '$' | '0'..='9' | '?' | '_' | 'L' => 556,
+        '%' => 889,
+        '&' | 'A' | 'B' | 'E' | 'K' | 'P' | 'S' | 'V' | 'X' | 'Y' => 667,
+        '\'' => 191,
+        '(' | ')' | '-' | '`' | 'r' => 333,
+        '*' => 389,
+        '+' | '<' | '=' | '>' | '~' => 584,
+        '@' => 1015,
+        'C' | 'D' | 'H' | 'N' | 'R' | 'U' | 'w' => 722,
+        'F' | 'T' | 'Z' => 611,
+        'G' | 'O' | 'Q' => 778,
+        'J' | 'c' | 'k' | 's' | 'v' | 'x' | 'y' | 'z' => 500,
+        'M' | 'm' => 833,
+        'W' => 944,
+        '^' => 469,
+        'i' | 'j' | 'l' => 222,
+        '{' | '}' => 334,
+        '|' => 260,
+        '\u{2014}' | '\u{2026}' => 1000,
+        '\u{2018}' | '\u{2019}' => 222,
+        '\u{201C}' | '\u{201D}' => 333,
+        '\u{2022}' => 350,
+        _ => 556,
+    };
+    w as f32
+}
+
+/// Whether the builtin PDF fonts (WinAnsi encoding) can show a character
+fn pdf_font_supports(c: char) -> bool {
+    (c as u32) < 0x100
+        || matches!(
+            c,
+            '\u{2013}'
+                | '\u{2014}'
+                | '\u{2018}'
+                | '\u{2019}'
+                | '\u{201A}'
+                | '\u{201C}'
+                | '\u{201D}'
+                | '\u{201E}'
+                | '\u{2020}'
+                | '\u{2021}'
+                | '\u{2022}'
+                | '\u{2026}'
+                | '\u{2030}'
+                | '\u{20AC}'
+                | '\u{2122}'
+        )
+}
+
+/// Warn about characters the PDF fonts will drop, listing a few of them
+fn pdf_warnings(blocks: &[Block], title: &str, subtitle: Option<&str>) -> Vec<String> {
+    let mut missing: Vec<char> = Vec::new();
+    let mut note = |text: &str| {
+        for c in text
+            .chars()
+            .filter(|c| !c.is_control() && !pdf_font_supports(*c))
+        {
+            if !missing.contains(&c) {
+                missing.push(c);
+            }
+        }
+    };
+
+    note(title);
+    if let Some(subtitle) = subtitle {
+        note(subtitle);
+    }
+    for block in blocks {
+        match block {
+            Block::Heading { spans, .. }
+            | Block::Paragraph { spans }
+            | Block::ListItem { spans, .. }
+            | Block::Quote { spans } => spans.iter().for_each(|s| note(&s.text)),
+            Block::Code { text } => note(text),
+            Block::Rule => {}
+        }
+    }
+
+    if missing.is_empty() {
+        return Vec::new();
+    }
+    let sample: String = missing.iter().take(10).collect();
+    vec![format!(
+        "{} character(s) can't be shown in the PDF fonts and will be missing: {}",
+        missing.len(),
+        sample
+    )]
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+    Mono,
+}
+
+impl FontStyle {
+    fn for_span(span: &Span, force_italic: bool) -> Self {
+        let italic = span.italic || force_italic;
+        match (span.code, span.bold, italic) {
+            (true, _, _) => FontStyle::Mono,
+            (false, true, true) => FontStyle::BoldItalic,
+            (false, true, false) => FontStyle::Bold,
+            (false, false, true) => FontStyle::Italic,
+            (false, false, false) => FontStyle::Regular,
+        }
+    }
+}
+
+/// Measure text width in millimetres
+fn text_width(text: &str, style: FontStyle, size: f32) -> f32 {
+    let units: f32 = match style {
+        FontStyle::Mono => text.chars().count() as f32 * 600.0,
+        // Bold faces run slightly wider than the regular metrics
+        FontStyle::Bold | FontStyle::BoldItalic => {
+            text.chars().map(helvetica_width).sum::<f32>() * 1.06
+        }
+        _ => text.chars().map(helvetica_width).sum(),
+    };
+    units / 1000.0 * size * PT_TO_MM
+}
+
+/// A laid-out line: styled segments left to right
+type LaidOutLine = Vec<(String, FontStyle)>;
+
+/// Greedy word wrap of styled spans into lines that fit `max_width` mm
+fn wrap_spans(
+    spans: &[Span],
+    size: f32,
+    max_width: f32,
+    force_bold: bool,
+    force_italic: bool,
+) -> Vec<LaidOutLine> {
+    let mut lines: Vec<LaidOutLine> = Vec::new();
+    let mut line: LaidOutLine = Vec::new();
+    let mut line_width = 0.0;
+
+    for span in spans {
+        let mut style = FontStyle::for_span(span, force_italic);
+        if force_bold {
+            style = match style {
+                FontStyle::Regular => FontStyle::Bold,
+                FontStyle::Italic => FontStyle::BoldItalic,
+                other => other,
+            };
+        }
+
+        // Keep leading/trailing whitespace so adjacent spans stay separated
+        let mut word = String::new();
+        let mut pieces = Vec::new();
+        for c in span.text.chars() {
+            if c.is_whitespace() {
+                if !word.is_empty() {
+                    pieces.push(std::mem::take(&mut word));
+                }
+                pieces.push(" ".to_string());
+            } else {
+                word.push(c);
+            }
+        }
+        if !word.is_empty() {
+            pieces.push(word);
+        }
+
+        for piece in pieces {
+            let is_space = piece == " ";
+            if is_space && line.is_empty() {
+                continue;
+            }
+            let width = text_width(&piece, style, size);
+            if !is_space && line_width + width > max_width && !line.is_empty() {
+                // Drop trailing space before breaking
+                if let Some((last, _)) = line.last_mut() {
+                    let trimmed = last.trim_end().to_string();
+                    *last = trimmed;
+                }
+                lines.push(std::mem::take(&mut line));
+                line_width = 0.0;
+            }
+            if is_space && line.is_empty() {
+                continue;
+            }
+            match line.last_mut() {
+                Some((text, s)) if *s == style => text.push_str(&piece),
+                _ => line.push((piece, style)),
+            }
+            line_width += width;
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+struct PdfFonts {
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    italic: IndirectFontRef,
+    bold_italic: IndirectFontRef,
+    mono: IndirectFontRef,
+}
+
+impl PdfFonts {
+    fn load(doc: &PdfDocumentReference) -> Result<Self, FileError> {
+        let font = |f: BuiltinFont| {
+            doc.add_builtin_font(f)
+                .map_err(|e| FileError::Export(e.to_string()))
+        };
+        Ok(PdfFonts {
+            regular: font(BuiltinFont::Helvetica)?,
+            bold: font(BuiltinFont::HelveticaBold)?,
+            italic: font(BuiltinFont::HelveticaOblique)?,
+            bold_italic: font(BuiltinFont::HelveticaBoldOblique)?,
+            mono: font(BuiltinFont::Courier)?,
+        })
+    }
+
+    fn get(&self, style: FontStyle) -> &IndirectFontRef {
+        match style {
+            FontStyle::Regular => &self.regular,
+            FontStyle::Bold => &self.bold,
+            FontStyle::Italic => &self.italic,
+            FontStyle::BoldItalic => &self.bold_italic,
+            FontStyle::Mono => &self.mono,
+        }
+    }
+}
+
+/// Tracks the cursor and starts new pages as content flows down
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    fonts: PdfFonts,
+    width: f32,
+    height: f32,
+    margins: PdfMargins,
+    y: f32,
+    pages: usize,
+}
+
+impl PdfWriter {
+    fn content_width(&self) -> f32 {
+        self.width - self.margins.left - self.margins.right
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self
+            .doc
+            .add_page(Mm(self.width), Mm(self.height), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = self.height - self.margins.top;
+        self.pages += 1;
+    }
+
+    /// Move down by `height`, starting a new page if it won't fit
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < self.margins.bottom {
+            self.new_page();
+        }
+        self.y -= height;
+    }
+
+    fn draw_line(&self, line: &LaidOutLine, x: f32, size: f32) {
+        let mut x = x;
+        for (text, style) in line {
+            self.layer.use_text(
+                text.as_str(),
+                size,
+                Mm(x),
+                Mm(self.y),
+                self.fonts.get(*style),
+            );
+            x += text_width(text, *style, size);
+        }
+    }
+
+    fn draw_rule(&self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        self.layer
+            .set_outline_color(Color::Rgb(Rgb::new(0.6, 0.6, 0.6, None)));
+        self.layer.set_outline_thickness(0.75);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(x1), Mm(y1)), false),
+                (Point::new(Mm(x2), Mm(y2)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    fn write_spans(
+        &mut self,
+        spans: &[Span],
+        size: f32,
+        indent: f32,
+        force_bold: bool,
+        force_italic: bool,
+    ) -> usize {
+        let line_height = size * PT_TO_MM * LINE_SPACING;
+        let lines = wrap_spans(
+            spans,
+            size,
+            self.content_width() - indent,
+            force_bold,
+            force_italic,
+        );
+        for line in &lines {
+            self.reserve(line_height);
+            self.draw_line(line, self.margins.left + indent, size);
+        }
+        lines.len()
+    }
+
+    fn write_block(&mut self, block: &Block) {
+        let body_line = BODY_SIZE * PT_TO_MM * LINE_SPACING;
+
+        match block {
+            Block::Heading { level, spans } => {
+                let size = match level {
+                    1 => 22.0,
+                    2 => 18.0,
+                    3 => 15.0,
+                    _ => 13.0,
+                };
+                // Keep some space above headings, but not at the top of a page
+                if self.y < self.height - self.margins.top {
+                    self.reserve(body_line * 0.6);
+                }
+                self.write_spans(spans, size, 0.0, true, false);
+                self.y -= body_line * 0.3;
+            }
+            Block::Paragraph { spans } => {
+                self.write_spans(spans, BODY_SIZE, 0.0, false, false);
+                self.y -= body_line * 0.5;
+            }
+            Block::ListItem {
+                marker,
+                depth,
+                spans,
+            } => {
+                let indent = LIST_INDENT * (*depth as f32 + 1.0);
+                let line_height = body_line;
+                let lines = wrap_spans(
+                    spans,
+                    BODY_SIZE,
+                    self.content_width() - indent,
+                    false,
+                    false,
+                );
+                for (i, line) in lines.iter().enumerate() {
+                    self.reserve(line_height);
+                    if i == 0 && !marker.is_empty() {
+                        let marker_line = vec![(marker.clone(), FontStyle::Regular)];
+                        self.draw_line(
+                            &marker_line,
+                            self.margins.left + indent - LIST_INDENT + 1.5,
+                            BODY_SIZE,
+                        );
+                    }
+                    self.draw_line(line, self.margins.left + indent, BODY_SIZE);
+                }
+                self.y -= body_line * 0.15;
+            }
+            Block::Quote { spans } => {
+                let top = self.y;
+                let lines = self.write_spans(spans, BODY_SIZE, QUOTE_INDENT, false, true);
+                // Bar down the left side; skip it if the quote crossed a page
+                if lines > 0 && self.y < top {
+                    let x = self.margins.left + QUOTE_INDENT / 2.0 - 1.0;
+                    self.draw_rule(x, top, x, self.y - body_line * 0.25);
+                }
+                self.y -= body_line * 0.5;
+            }
+            Block::Code { text } => {
+                let line_height = CODE_SIZE * PT_TO_MM * LINE_SPACING;
+                let max_chars =
+                    ((self.content_width() - 4.0) / (0.6 * CODE_SIZE * PT_TO_MM)).max(1.0) as usize;
+                for source_line in text.lines() {
+                    let chars: Vec<char> = source_line.chars().collect();
+                    let chunks: Vec<String> = if chars.is_empty() {
+                        vec![String::new()]
+                    } else {
+                        chars
+                            .chunks(max_chars)
+                            .map(|c| c.iter().collect())
+                            .collect()
+                    };
+                    for chunk in chunks {
+                        self.reserve(line_height);
+                        let line = vec![(chunk, FontStyle::Mono)];
+                        self.draw_line(&line, self.margins.left + 4.0, CODE_SIZE);
+                    }
+                }
+                self.y -= body_line * 0.5;
+            }
+            Block::Rule => {
+                self.reserve(body_line);
+                let y = self.y + body_line / 2.0;
+                self.draw_rule(self.margins.left, y, self.width - self.margins.right, y);
+            }
+        }
+    }
+
+    fn write_title_page(&mut self, title: &str, subtitle: Option<&str>) {
+        let title_size = 26.0;
+        let content_width = self.content_width();
+        let title_spans = [Span {
+            text: title.to_string(),
+            bold: true,
+            ..Default::default()
+        }];
+
+        self.y = self.height * 0.62;
+        let title_line = title_size * PT_TO_MM * LINE_SPACING;
+        for line in wrap_spans(&title_spans, title_size, content_width, false, false) {
+            let width: f32 = line
+                .iter()
+                .map(|(t, s)| text_width(t, *s, title_size))
+                .sum();
+            self.y -= title_line;
+            self.draw_line(
+                &line,
+                self.margins.left + (content_width - width) / 2.0,
+                title_size,
+            );
+        }
+
+        if let Some(subtitle) = subtitle {
+            let sub_size = 13.0;
+            let sub_spans = [Span {
+                text: subtitle.to_string(),
+                italic: true,
+                ..Default::default()
+            }];
+            self.y -= title_line * 0.5;
+            for line in wrap_spans(&sub_spans, sub_size, content_width * 0.8, false, false) {
+                let width: f32 = line.iter().map(|(t, s)| text_width(t, *s, sub_size)).sum();
+                self.y -= sub_size * PT_TO_MM * LINE_SPACING;
+                self.draw_line(
+                    &line,
+                    self.margins.left + (content_width - width) / 2.0,
+                    sub_size,
+                );
+            }
+        }
+
+        self.new_page();
+    }
+}
+
+/// Lay blocks out onto PDF pages, ready to be saved
+fn layout_pdf(
+    blocks: &[Block],
+    title: &str,
+    subtitle: Option<&str>,
+    options: &PdfExportOptions,
+) -> Result<PdfWriter, FileError> {
+    let (width, height) = options.page_size.dimensions();
+    let (doc, page, layer) = PdfDocument::new(title, Mm(width), Mm(height), "Layer 1");
+    let layer = doc.get_page(page).get_layer(layer);
+    let fonts = PdfFonts::load(&doc)?;
+
+    let mut writer = PdfWriter {
+        doc,
+        layer,
+        fonts,
+        width,
+        height,
+        margins: options.margins,
+        y: height - options.margins.top,
+        pages: 1,
+    };
+
+    if options.include_title_page {
+        writer.write_title_page(title, subtitle);
+    }
+
+    for block in blocks {
+        writer.write_block(block);
+    }
+
+    Ok(writer)
+}
+
+/// Export a markdown document as a PDF.
+/// Text is set in the standard PDF fonts, which cover Latin-1 plus common
+/// typographic punctuation (em-dashes, smart quotes, ellipses).
+/// With `dry_run`, nothing is written and only the preview is returned.
+#[tauri::command]
+pub async fn export_pdf(
+    md_path: String,
+    output_path: String,
+    options: Option<PdfExportOptions>,
+    dry_run: Option<bool>,
+) -> Result<ExportPreview, FileError> {
+    let options = options.unwrap_or_default();
+    let markdown = tokio::fs::read_to_string(&md_path).await?;
+    let analysis = analyze_markdown(&markdown);
+    let (title, subtitle) = document_title(&md_path).await;
+
+    // The writer isn't Send, so it has to be gone before the write awaits
+    let (preview, bytes) = {
+        let writer = layout_pdf(&analysis.blocks, &title, subtitle.as_deref(), &options)?;
+        let preview = analysis.preview(
+            writer.pages,
+            pdf_warnings(&analysis.blocks, &title, subtitle.as_deref()),
+        );
+        if dry_run.unwrap_or(false) {
+            return Ok(preview);
+        }
+
+        let bytes = writer
+            .doc
+            .save_to_bytes()
+            .map_err(|e| FileError::Export(e.to_string()))?;
+        (preview, bytes)
+    };
+    write_atomic(Path::new(&output_path), &bytes).await?;
+
+    Ok(preview)
+}
+
+// ============================================
+// Word export
+// ============================================
+
+// docx-rs always writes its own numbering with id 1, so ours start at 2
+const BULLET_NUMBERING: usize = 2;
+const DECIMAL_NUMBERING: usize = 3;
+/// Word indents are in twentieths of a point
+const DOCX_INDENT_STEP: i32 = 720;
+
+fn docx_heading_style(level: u8) -> Style {
+    // Sizes are in half-points
+    let size = match level {
+        1 => 32,
+        2 => 26,
+        3 => 24,
+        _ => 22,
+    };
+    // Word and Google Docs both recognise the built-in "heading N" names
+    Style::new(format!("Heading{}", level), StyleType::Paragraph)
+        .name(format!("heading {}", level))
+        .based_on("Normal")
+        .next("Normal")
+        .size(size)
+        .bold()
+        .outline_lvl(level.saturating_sub(1) as usize)
+}
+
+fn docx_list_numbering(id: usize, bullet: bool) -> AbstractNumbering {
+    let mut numbering = AbstractNumbering::new(id);
+    for level in 0..9 {
+        let (format, text) = if bullet {
+            ("bullet", "\u{2022}".to_string())
+        } else {
+            ("decimal", format!("%{}.", level + 1))
+        };
+        numbering = numbering.add_level(
+            Level::new(
+                level,
+                Start::new(1),
+                NumberFormat::new(format),
+                LevelText::new(text),
+                LevelJc::new("left"),
+            )
+            .indent(
+                Some(DOCX_INDENT_STEP * (level as i32 + 1)),
+                Some(SpecialIndentType::Hanging(360)),
+                None,
+                None,
+            ),
+        );
+    }
+    numbering
+}
+
+fn docx_runs(mut paragraph: Paragraph, spans: &[Span]) -> Paragraph {
+    for span in spans {
+        let mut run = Run::new().add_text(span.text.as_str());
+        if span.bold {
+            run = run.bold();
+        }
+        if span.italic {
+            run = run.italic();
+        }
+        if span.code {
+            run = run.fonts(RunFonts::new().ascii("Courier New").hi_ansi("Courier New"));
+        }
+        paragraph = paragraph.add_run(run);
+    }
+    paragraph
+}
+
+/// Build a .docx from parsed blocks and return the packed bytes
+fn render_docx(blocks: &[Block], title: Option<&str>) -> Result<Vec<u8>, FileError> {
+    let mut docx = Docx::new()
+        .add_style(
+            Style::new("Title", StyleType::Paragraph)
+                .name("Title")
+                .based_on("Normal")
+                .next("Normal")
+                .size(52),
+        )
+        .add_style(
+            Style::new("Quote", StyleType::Paragraph)
+                .name("Quote")
+                .based_on("Normal")
+                .next("Normal")
+                .italic()
+                .indent(Some(DOCX_INDENT_STEP), None, None, None),
+        )
+        .add_abstract_numbering(docx_list_numbering(BULLET_NUMBERING, true))
+        .add_abstract_numbering(docx_list_numbering(DECIMAL_NUMBERING, false))
+        .add_numbering(Numbering::new(BULLET_NUMBERING, BULLET_NUMBERING));
+    for level in 1..=6 {
+        docx = docx.add_style(docx_heading_style(level));
+    }
+
+    let mut blocks = blocks;
+    if let Some(title) = title {
+        docx = docx.add_paragraph(
+            Paragraph::new()
+                .style("Title")
+                .add_run(Run::new().add_text(title)),
+        );
+        // Don't repeat the title if the document already opens with it
+        if let Some(Block::Heading { level: 1, spans }) = blocks.first() {
+            let heading: String = spans.iter().map(|s| s.text.as_str()).collect();
+            if heading.trim() == title.trim() {
+                blocks = &blocks[1..];
+            }
+        }
+    }
+
+    // Each ordered list gets its own numbering instance so it restarts
+    let mut next_numbering_id = DECIMAL_NUMBERING + 1;
+    let mut ordered_lists: Vec<Option<(usize, u64)>> = Vec::new();
+
+    for block in blocks {
+        let paragraph = match block {
+            Block::Heading { level, spans } => {
+                docx_runs(Paragraph::new().style(&format!("Heading{}", level)), spans)
+            }
+            Block::Paragraph { spans } => docx_runs(Paragraph::new(), spans),
+            Block::ListItem {
+                marker,
+                depth,
+                spans,
+            } => {
+                ordered_lists.resize(depth + 1, None);
+                let paragraph = if marker.is_empty() {
+                    Paragraph::new().indent(
+                        Some(DOCX_INDENT_STEP * (*depth as i32 + 1)),
+                        None,
+                        None,
+                        None,
+                    )
+                } else if let Some(number) =
+                    marker.strip_suffix('.').and_then(|n| n.parse::<u64>().ok())
+                {
+                    let id = match ordered_lists[*depth] {
+                        Some((id, last)) if last + 1 == number => id,
+                        _ => {
+                            let id = next_numbering_id;
+                            next_numbering_id += 1;
+                            docx = docx.add_numbering(
+                                Numbering::new(id, DECIMAL_NUMBERING).add_override(
+                                    LevelOverride::new(*depth).start(number as usize),
+                                ),
+                            );
+                            id
+                        }
+                    };
+                    ordered_lists[*depth] = Some((id, number));
+                    Paragraph::new().numbering(NumberingId::new(id), IndentLevel::new(*depth))
+                } else {
+                    Paragraph::new()
+                        .numbering(NumberingId::new(BULLET_NUMBERING), IndentLevel::new(*depth))
+                };
+                docx_runs(paragraph, spans)
+            }
+            Block::Quote { spans } => docx_runs(Paragraph::new().style("Quote"), spans),
+            Block::Code { text } => {
+                let mut run =
+                    Run::new().fonts(RunFonts::new().ascii("Courier New").hi_ansi("Courier New"));
+                for (i, line) in text.lines().enumerate() {
+                    if i > 0 {
+                        run = run.add_break(BreakType::TextWrapping);
+                    }
+                    run = run.add_text(line);
+                }
+                Paragraph::new().add_run(run)
+            }
+            Block::Rule => Paragraph::new()
+                .align(AlignmentType::Center)
+                .add_run(Run::new().add_text("*   *   *")),
+        };
+        docx = docx.add_paragraph(paragraph);
+    }
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    docx.build()
+        .pack(&mut buffer)
+        .map_err(|e| FileError::Export(e.to_string()))?;
+    Ok(buffer.into_inner())
+}
+
+/// Export a markdown document as a Word (.docx) file.
+/// The concept title, when set, becomes the document's Title paragraph.
+/// Word paginates on its own, so the page count is estimated from a default
+/// Letter-sized PDF layout. With `dry_run`, nothing is written.
+#[tauri::command]
+pub async fn export_word(
+    md_path: String,
+    output_path: String,
+    dry_run: Option<bool>,
+) -> Result<ExportPreview, FileError> {
+    let markdown = tokio::fs::read_to_string(&md_path).await?;
+    let analysis = analyze_markdown(&markdown);
+    let title = concept_title(&md_path).await.map(|(title, _)| title);
+
+    let mut blocks = analysis.blocks.clone();
+    if let Some(title) = &title {
+        blocks.insert(
+            0,
+            Block::Heading {
+                level: 1,
+                spans: vec![Span {
+                    text: title.clone(),
+                    ..Default::default()
+                }],
+            },
+        );
+    }
+    let pages = layout_pdf(&blocks, "", None, &PdfExportOptions::default())?.pages;
+    let preview = analysis.preview(pages, Vec::new());
+    if dry_run.unwrap_or(false) {
+        return Ok(preview);
+    }
+
+    let bytes = render_docx(&analysis.blocks, title.as_deref())?;
+    write_atomic(Path::new(&output_path), &bytes).await?;
+
+    Ok(preview)
+}
+
+// ============================================
+// Batch export
+// ============================================
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Pdf,
+    Word,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Word => "docx",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedFile {
+    pub md_path: String,
+    pub output_path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportFailure {
+    pub md_path: String,
+    pub error: String,
+}
+
+/// Outcome of `export_all`, one entry per document
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub exported: Vec<ExportedFile>,
+    pub failed: Vec<ExportFailure>,
+}
+
+/// Emitted by `export_all` as each document finishes, successfully or not
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgressEvent {
+    pub md_path: String,
+    pub completed: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
+
+/// Export every document in `dir` (not its subfolders) into `output_dir`,
+/// named after the document. A failed document is recorded in the report
+/// and the rest carry on. Emits 'export-progress' after each document.
+#[tauri::command]
+pub async fn export_all(
+    app: AppHandle,
+    dir: String,
+    output_dir: String,
+    format: ExportFormat,
+) -> Result<ExportReport, FileError> {
+    let dir_path = PathBuf::from(&dir);
+    if !dir_path.is_dir() {
+        return Err(FileError::InvalidPath("Not a directory".to_string()));
+    }
+
+    let mut files = collect_markdown_files(&dir_path, false).await?;
+    files.sort();
+    tokio::fs::create_dir_all(&output_dir).await?;
+
+    let total = files.len();
+    let mut report = ExportReport::default();
+    for (index, file) in files.into_iter().enumerate() {
+        let md_path = file.to_string_lossy().to_string();
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        let output_path = Path::new(&output_dir)
+            .join(format!("{}.{}", stem, format.extension()))
+            .to_string_lossy()
+            .to_string();
+
+        let result = match format {
+            ExportFormat::Pdf => export_pdf(md_path.clone(), output_path.clone(), None, None)
+                .await
+                .map(|_| ()),
+            ExportFormat::Word => export_word(md_path.clone(), output_path.clone(), None)
+                .await
+                .map(|_| ()),
+        };
+
+        let error = result.err().map(|e| e.to_string());
+        let _ = app.emit(
+            "export-progress",
+            ExportProgressEvent {
+                md_path: md_path.clone(),
+                completed: index + 1,
+                total,
+                error: error.clone(),
+            },
+        );
+        match error {
+            None => report.exported.push(ExportedFile {
+                md_path,
+                output_path,
+            }),
+            Some(error) => {
+                tracing::warn!(path = %md_path, error = %error, "Batch export failed for document");
+                report.failed.push(ExportFailure { md_path, error });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+// ============================================
+// HTML preview
+// ============================================
+
+/// GitHub-flavored extensions for the preview. Each is on unless the caller
+/// turns it off.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RenderOptions {
+    pub tables: bool,
+    pub strikethrough: bool,
+    pub task_lists: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            tables: true,
+            strikethrough: true,
+            task_lists: true,
+        }
+    }
+}
+
+/// Strip scripts, event handlers and unsafe URLs, keeping the markup
+/// pulldown-cmark produces for the enabled extensions
+fn sanitize_html(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("code", ["class"])
+        .add_tag_attributes("th", ["style"])
+        .add_tag_attributes("td", ["style"])
+        .filter_style_properties(["text-align"].into_iter().collect())
+        .clean(html)
+        .to_string()
+}
+
+/// Render markdown to sanitized HTML for the preview pane
+#[tauri::command]
+pub fn render_markdown(content: String, options: RenderOptions) -> Result<String, FileError> {
+    let mut flags = Options::empty();
+    if options.tables {
+        flags |= Options::ENABLE_TABLES;
+    }
+    if options.strikethrough {
+        flags |= Options::ENABLE_STRIKETHROUGH;
+    }
+    if options.task_lists {
+        flags |= Options::ENABLE_TASKLISTS;
+    }
+
+    let parser = Parser::new_ext(&content, flags);
+    let mut html = String::with_capacity(content.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, parser);
+
+    Ok(sanitize_html(&html))
+}
+
+// ============================================
+// Word import
+// ============================================
+
+/// Stands in for images and other elements markdown can't carry
+const IMPORT_PLACEHOLDER: &str = "[image]";
+
+/// A run of imported text. `text` is raw; escaping happens when rendering.
+#[derive(Debug, Clone, PartialEq)]
+struct ImportSpan {
+    text: String,
+    bold: bool,
+    italic: bool,
+    link: Option<String>,
+}
+
+/// Escape characters that would otherwise be read as markdown syntax
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn run_text(run: &Run) -> String {
+    let mut text = String::new();
+    for child in &run.children {
+        match child {
+            RunChild::Text(t) => text.push_str(&t.text),
+            RunChild::Tab(_)
+            | RunChild::PTab(_)
+            | RunChild::Break(_)
+            | RunChild::CarriageReturn(_) => text.push(' '),
+            RunChild::Drawing(_) | RunChild::Shape(_) => text.push_str(IMPORT_PLACEHOLDER),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn push_run(spans: &mut Vec<ImportSpan>, run: &Run, link: Option<&str>) {
+    let text = run_text(run);
+    if text.is_empty() {
+        return;
+    }
+    spans.push(ImportSpan {
+        text,
+        bold: run.run_property.bold.as_ref() == Some(&Bold::new()),
+        italic: run.run_property.italic.as_ref() == Some(&Italic::new()),
+        link: link.map(str::to_string),
+    });
+}
+
+/// Collect the spans of a paragraph, following hyperlinks and tracked insertions
+fn collect_import_spans(
+    docx: &Docx,
+    children: &[ParagraphChild],
+    link: Option<&str>,
+    spans: &mut Vec<ImportSpan>,
+) {
+    for child in children {
+        match child {
+            ParagraphChild::Run(run) => push_run(spans, run, link),
+            ParagraphChild::Insert(insert) => {
+                for child in &insert.children {
+                    if let InsertChild::Run(run) = child {
+                        push_run(spans, run, link);
+                    }
+                }
+            }
+            ParagraphChild::Hyperlink(hyperlink) => {
+                let url = match &hyperlink.link {
+                    HyperlinkData::External { rid, .. } => docx
+                        .document_rels
+                        .hyperlinks
+                        .iter()
+                        .find(|(id, _, _)| id == rid)
+                        .map(|(_, target, _)| target.as_str()),
+                    HyperlinkData::Anchor { .. } => None,
+                };
+                collect_import_spans(docx, &hyperlink.children, url.or(link), spans);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Render spans as inline markdown, merging neighbours with the same styling
+/// and keeping emphasis markers tight against the text they wrap.
+fn render_import_spans(spans: &[ImportSpan]) -> String {
+    let mut merged: Vec<ImportSpan> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last)
+                if last.bold == span.bold
+                    && last.italic == span.italic
+                    && last.link == span.link =>
+            {
+                last.text.push_str(&span.text)
+            }
+            _ => merged.push(span.clone()),
+        }
+    }
+
+    let mut out = String::new();
+    for span in &merged {
+        let marker = match (span.bold, span.italic) {
+            (true, true) => "***",
+            (true, false) => "**",
+            (false, true) => "*",
+            (false, false) => "",
+        };
+        let trimmed = span.text.trim();
+        if trimmed.is_empty() {
+            out.push_str(&span.text);
+            continue;
+        }
+        let leading = &span.text[..span.text.len() - span.text.trim_start().len()];
+        let trailing = &span.text[span.text.trim_end().len()..];
+
+        let mut inner = format!("{}{}{}", marker, escape_markdown(trimmed), marker);
+        if let Some(url) = &span.link {
+            inner = format!("[{}]({})", inner, url);
+        }
+        out.push_str(leading);
+        out.push_str(&inner);
+        out.push_str(trailing);
+    }
+    out.trim().to_string()
+}
+
+/// Heading level for a paragraph style, treating Title as a top-level heading
+fn import_heading_level(paragraph: &Paragraph) -> Option<usize> {
+    let style = paragraph.property.style.as_ref()?.val.to_lowercase();
+    if style == "title" {
+        return Some(1);
+    }
+    style
+        .strip_prefix("heading")
+        .and_then(|n| n.trim().parse::<usize>().ok())
+        .filter(|n| (1..=6).contains(n))
+}
+
+/// List depth and whether the list is bulleted, if the paragraph is a list item
+fn import_list_item(docx: &Docx, paragraph: &Paragraph) -> Option<(usize, bool)> {
+    let numbering = paragraph.property.numbering_property.as_ref()?;
+    // numId 0 means numbering was explicitly removed
+    let id = numbering
+        .id
+        .as_ref()
+        .map(|id| id.id)
+        .filter(|id| *id != 0)?;
+    let depth = numbering.level.as_ref().map(|l| l.val).unwrap_or(0);
+
+    let bullet = docx
+        .numberings
+        .numberings
+        .iter()
+        .find(|n| n.id == id)
+        .and_then(|n| {
+            docx.numberings
+                .abstract_nums
+                .iter()
+                .find(|a| a.id == n.abstract_num_id)
+        })
+        .and_then(|a| a.levels.iter().find(|l| l.level == depth))
+        .is_none_or(|l| l.format.val == "bullet");
+
+    Some((depth, bullet))
+}
+
+fn import_table(docx: &Docx, table: &Table) -> Option<String> {
+    let rows: Vec<Vec<String>> = table
+        .rows
+        .iter()
+        .map(|TableChild::TableRow(row)| {
+            row.cells
+                .iter()
+                .map(|TableRowChild::TableCell(cell)| {
+                    let mut spans = Vec::new();
+                    for content in &cell.children {
+                        if let TableCellContent::Paragraph(p) = content {
+                            if !spans.is_empty() {
+                                spans.push(ImportSpan {
+                                    text: " ".to_string(),
+                                    bold: false,
+                                    italic: false,
+                                    link: None,
+                                });
+                            }
+                            collect_import_spans(docx, &p.children, None, &mut spans);
+                        }
+                    }
+                    render_import_spans(&spans).replace('|', "\\|")
+                })
+                .collect()
+        })
+        .collect();
+
+    let columns = rows.iter().map(|r| r.len()).max().filter(|n| *n > 0)?;
+    let line = |cells: &[String]| {
+        let mut cells = cells.to_vec();
+        cells.resize(columns, String::new());
+        format!("| {} |", cells.join(" | "))
+    };
+
+    let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+    lines.extend(rows[1..].iter().map(|r| line(r)));
+    Some(lines.join("\n"))
+}
+
+/// Convert a parsed Word document to markdown, returning the first heading's
+/// text alongside it.
+fn docx_to_markdown(docx: &Docx) -> (String, Option<String>) {
+    let mut markdown = String::new();
+    let mut title = None;
+    // Bullet flag of the previous block when it was a list item
+    let mut previous_list: Option<bool> = None;
+
+    for child in &docx.document.children {
+        let (block, list) = match child {
+            DocumentChild::Paragraph(paragraph) => {
+                let mut spans = Vec::new();
+                collect_import_spans(docx, &paragraph.children, None, &mut spans);
+                let text = render_import_spans(&spans);
+                if text.is_empty() {
+                    continue;
+                }
+
+                if let Some(level) = import_heading_level(paragraph) {
+                    if title.is_none() {
+                        let plain: String = spans.iter().map(|s| s.text.as_str()).collect();
+                        title = Some(plain.trim().to_string());
+                    }
+                    (format!("{} {}", "#".repeat(level), text), None)
+                } else if let Some((depth, bullet)) = import_list_item(docx, paragraph) {
+                    let marker = if bullet { "-" } else { "1." };
+                    let list = (depth > 0).then_some(previous_list).flatten();
+                    (
+                        format!("{}{} {}", "    ".repeat(depth), marker, text),
+                        Some(list.unwrap_or(bullet)),
+                    )
+                } else {
+                    (text, None)
+                }
+            }
+            DocumentChild::Table(table) => match import_table(docx, table) {
+                Some(table) => (table, None),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        if !markdown.is_empty() {
+            // Keep items of the same list together; a new list type starts a new list
+            let same_list = list.is_some() && list == previous_list;
+            markdown.push_str(if same_list { "\n" } else { "\n\n" });
+        }
+        markdown.push_str(&block);
+        previous_list = list;
+    }
+
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+    (markdown, title)
+}
+
+/// Import a Word (.docx) file as a new markdown document with a fresh sidecar.
+/// Headings, bold, italic, links, lists and tables carry over; images become
+/// a placeholder. The first heading seeds the concept title.
+#[tauri::command]
+pub async fn import_docx(docx_path: String, output_md_path: String) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&output_md_path)?;
+    if Path::new(&output_md_path).exists() {
+        return Err(FileError::InvalidPath(
+            "A document already exists at that path".to_string(),
+        ));
+    }
+
+    let bytes = tokio::fs::read(&docx_path).await?;
+    let docx = docx_rs::read_docx(&bytes).map_err(|e| FileError::Import(e.to_string()))?;
+    let (markdown, title) = docx_to_markdown(&docx);
+
+    tokio::fs::write(&output_md_path, markdown).await?;
+
+    let mut sidecar = Sidecar::new();
+    if let Some(title) = title.filter(|t| !t.is_empty()) {
+        sidecar.seed_concept_title(&title);
+    }
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(sidecar)
+}
+
+// ============================================
+// Text import
+// ============================================
+
+/// Decode a text file: UTF-8 with any byte order mark dropped, falling back
+/// to Latin-1 for files that aren't valid UTF-8
+fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Turn plain text into markdown paragraphs. Blank lines separate
+/// paragraphs; single newlines are kept, or become hard breaks.
+fn text_to_markdown(text: &str, hard_breaks: bool) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let line_break = if hard_breaks { "\\\n" } else { "\n" };
+
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().chain(std::iter::once("")) {
+        if line.trim().is_empty() {
+            if !lines.is_empty() {
+                paragraphs.push(lines.join(line_break));
+                lines.clear();
+            }
+        } else {
+            lines.push(line.trim_end());
+        }
+    }
+
+    let mut markdown = paragraphs.join("\n\n");
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// Import a plain-text file as a new markdown draft with a fresh sidecar.
+/// Blank-line separated blocks become paragraphs; with `hard_breaks`, line
+/// breaks inside a block are kept as markdown hard breaks. The document is
+/// written as UTF-8 without a byte order mark.
+#[tauri::command]
+pub async fn import_text(
+    txt_path: String,
+    output_md_path: String,
+    hard_breaks: Option<bool>,
+) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&output_md_path)?;
+    if Path::new(&output_md_path).exists() {
+        return Err(FileError::InvalidPath(
+            "A document already exists at that path".to_string(),
+        ));
+    }
+
+    let bytes = tokio::fs::read(&txt_path).await?;
+    let markdown = text_to_markdown(&decode_text(&bytes), hard_breaks.unwrap_or(false));
+
+    tokio::fs::write(&output_md_path, markdown).await?;
+
+    let mut sidecar = Sidecar::new();
+    sidecar.stage = DocumentStage::Draft;
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(sidecar)
+}
@@ -1,6 +1,28 @@
-use crate::models::Sidecar;
-use std::path::PathBuf;
-use dirs::document_dir;
+use super::auth::IdleLock;
+use super::claude::{ImageSource, RequestQueue, Usage};
+use super::export::{parse_blocks, Block};
+use super::logging::{parse_log_level, LogState};
+use crate::models::{
+    backfill_sidecar, migrate_sidecar, ChatMessage, ConceptDiff, ContentBlock, DocumentStage,
+    EditHistoryEntry, MessageContent, OutlinePrompt, Sidecar,
+};
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::Disks;
+use dirs::{document_dir, home_dir};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, DebouncedEventKind, Debouncer};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_opener::OpenerExt;
 
 #[derive(Debug, thiserror::Error)]
 pub enum FileError {
@@ -10,6 +32,44 @@ pub enum FileError {
     Json(#[from] serde_json::Error),
     #[error("Invalid path: {0}")]
     InvalidPath(String),
+    #[error("Could not find your home directory")]
+    NoHomeDirectory,
+    #[error("Export failed: {0}")]
+    Export(String),
+    #[error("Import failed: {0}")]
+    Import(String),
+    #[error("Unsupported image type: {0} (use PNG, JPEG, GIF or WebP)")]
+    UnsupportedImage(String),
+    #[error("Unsupported sidecar version {0}; it may have been saved by a newer version of WriteCraft")]
+    UnsupportedSidecarVersion(String),
+    #[error("Could not move to trash: {0}")]
+    Trash(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Edit not found: {0}")]
+    EditNotFound(String),
+    #[error("Invalid outline: {0}")]
+    InvalidOutline(String),
+    #[error("Version {index} does not exist (there are {count} versions)")]
+    VersionOutOfRange { index: usize, count: usize },
+    #[error("Could not watch file: {0}")]
+    Watch(String),
+    #[error("Invalid search: {0}")]
+    InvalidQuery(String),
+    #[error("Invalid usage period: {0} (use \"day\" or \"month\")")]
+    InvalidUsagePeriod(String),
+    #[error("Could not show in file manager: {0}")]
+    Reveal(String),
+    #[error("Snapshot not found: {0}")]
+    SnapshotNotFound(String),
+    #[error("The document has no title to name it after")]
+    MissingTitle,
+    #[error("Invalid settings: {0}")]
+    InvalidSettings(String),
+    #[error("Invalid ignore pattern: {0}")]
+    InvalidIgnorePattern(String),
+    #[error("Not enough disk space to save {0}")]
+    InsufficientSpace(String),
 }
 
 impl serde::Serialize for FileError {
@@ -21,7 +81,7 @@ impl serde::Serialize for FileError {
     }
 }
 
-fn get_sidecar_path(md_path: &str) -> Result<PathBuf, FileError> {
+pub(crate) fn get_sidecar_path(md_path: &str) -> Result<PathBuf, FileError> {
     let path = PathBuf::from(md_path);
     
     // Ensure it's a .md file
@@ -36,68 +96,1063 @@ fn get_sidecar_path(md_path: &str) -> Result<PathBuf, FileError> {
     Ok(sidecar_path)
 }
 
+/// Gzip-compressed variant of a sidecar path (`.writing.json.gz`)
+fn compressed_sidecar_path(sidecar_path: &Path) -> PathBuf {
+    let mut name = sidecar_path.as_os_str().to_os_string();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// The sidecar variants present on disk, plaintext first
+fn existing_sidecar_files(sidecar_path: &Path) -> Vec<PathBuf> {
+    [sidecar_path.to_path_buf(), compressed_sidecar_path(sidecar_path)]
+        .into_iter()
+        .filter(|p| p.exists())
+        .collect()
+}
+
+fn sidecar_exists(sidecar_path: &Path) -> bool {
+    !existing_sidecar_files(sidecar_path).is_empty()
+}
+
+/// Read a sidecar's JSON. The compressed variant wins when both exist.
+async fn read_sidecar_file(sidecar_path: &Path) -> Result<Vec<u8>, FileError> {
+    let compressed_path = compressed_sidecar_path(sidecar_path);
+    if !compressed_path.exists() {
+        return Ok(tokio::fs::read(sidecar_path).await?);
+    }
+
+    let compressed = tokio::fs::read(&compressed_path).await?;
+    gunzip_sidecar(&compressed)
+}
+
+/// Decompress a gzipped sidecar. A damaged archive is reported like corrupt
+/// JSON, so it gets the same recovery as an unparseable sidecar.
+fn gunzip_sidecar(compressed: &[u8]) -> Result<Vec<u8>, FileError> {
+    let mut json = Vec::new();
+    GzDecoder::new(compressed)
+        .read_to_end(&mut json)
+        .map_err(|e| {
+            FileError::Json(serde::de::Error::custom(format!(
+                "compressed sidecar is damaged: {}",
+                e
+            )))
+        })?;
+    Ok(json)
+}
+
+/// Temp file for atomic writes: `<name>.tmp` in the target's own folder, so
+/// the final rename stays on one filesystem
+pub(crate) fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Write a file atomically: temp file, then rename over the target.
+/// The temp file is flushed to disk before the rename so a crash can't leave
+/// the target pointing at unwritten data; `recover_pending_writes` deals with
+/// temps orphaned by a crash before the rename.
+/// Some network mounts still refuse the rename as cross-device; then the temp
+/// file is copied over the target instead (see `move_file`).
+pub(crate) async fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), FileError> {
+    ensure_free_space(path, contents.len() as u64).await?;
+
+    let temp_path = temp_path_for(path);
+    let written = async {
+        tokio::fs::write(&temp_path, contents).await?;
+        tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(&temp_path)
+            .await?
+            .sync_all()
+            .await
+    }
+    .await;
+    if let Err(e) = written {
+        // Don't leave a partial temp file behind
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        if e.kind() == std::io::ErrorKind::StorageFull {
+            return Err(FileError::InsufficientSpace(path.display().to_string()));
+        }
+        return Err(e.into());
+    }
+    move_file(&temp_path, path).await
+}
+
+/// Writes at least this big check free space up front; smaller ones rely
+/// on the write itself reporting a full disk
+const LARGE_WRITE_BYTES: u64 = 1024 * 1024;
+
+/// Free bytes on the volume holding `path`, or `None` if it can't be told
+fn available_space(path: &Path) -> Option<u64> {
+    // The target may not exist yet; measure the nearest folder that does
+    let existing = path.ancestors().find(|p| p.exists())?.canonicalize().ok()?;
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|disk| existing.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+async fn ensure_free_space(path: &Path, needed_bytes: u64) -> Result<(), FileError> {
+    if needed_bytes < LARGE_WRITE_BYTES {
+        return Ok(());
+    }
+    if !check_free_space(path.to_string_lossy().to_string(), needed_bytes).await? {
+        return Err(FileError::InsufficientSpace(path.display().to_string()));
+    }
+    Ok(())
+}
+
+/// Whether the volume holding `path` has at least `needed_bytes` free.
+/// Reports `true` when free space can't be determined.
+#[tauri::command]
+pub async fn check_free_space(path: String, needed_bytes: u64) -> Result<bool, FileError> {
+    let path = PathBuf::from(path);
+    let available = tokio::task::spawn_blocking(move || available_space(&path))
+        .await
+        .map_err(|e| FileError::Io(std::io::Error::other(e)))?;
+    Ok(available.is_none_or(|available| available >= needed_bytes))
+}
+
 #[tauri::command]
-pub async fn read_document(path: String) -> Result<String, FileError> {
+pub async fn read_document(app: AppHandle, path: String) -> Result<String, FileError> {
     let content = tokio::fs::read_to_string(&path).await?;
+    record_recent_document(&app, &path);
     Ok(content)
 }
 
+/// Size of a document in bytes, for paging through it with
+/// `read_document_range`
 #[tauri::command]
-pub async fn write_document(path: String, content: String) -> Result<(), FileError> {
-    // Write to temp file first, then rename for atomic write
-    let temp_path = format!("{}.tmp", path);
-    tokio::fs::write(&temp_path, &content).await?;
-    tokio::fs::rename(&temp_path, &path).await?;
-    Ok(())
+pub async fn get_document_size(path: String) -> Result<u64, FileError> {
+    Ok(tokio::fs::metadata(&path).await?.len())
+}
+
+fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0b1100_0000 == 0b1000_0000
+}
+
+/// Read up to `length` bytes of a document starting at `start_byte`. Both
+/// ends are moved back to the start of the character they fall in, so
+/// consecutive ranges join up without splitting or repeating characters.
+/// Ranges past the end of the file are clamped.
+#[tauri::command]
+pub async fn read_document_range(
+    path: String,
+    start_byte: usize,
+    length: usize,
+) -> Result<String, FileError> {
+    tokio::task::spawn_blocking(move || {
+        use std::io::{Seek, SeekFrom};
+
+        let mut file = std::fs::File::open(&path)?;
+        let size = file.metadata()?.len() as usize;
+        let start = start_byte.min(size);
+        let end = start.saturating_add(length).min(size);
+
+        // A UTF-8 character is at most 4 bytes, so 3 bytes either side are
+        // enough to find the boundaries
+        let window_start = start.saturating_sub(3);
+        let window_end = end.saturating_add(1).min(size);
+        let mut window = vec![0u8; window_end - window_start];
+        file.seek(SeekFrom::Start(window_start as u64))?;
+        file.read_exact(&mut window)?;
+
+        let mut from = start - window_start;
+        while from > 0 && from < window.len() && is_utf8_continuation(window[from]) {
+            from -= 1;
+        }
+        let mut to = end - window_start;
+        while to > from && to < window.len() && is_utf8_continuation(window[to]) {
+            to -= 1;
+        }
+
+        String::from_utf8(window[from..to].to_vec())
+            .map_err(|e| FileError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+    })
+    .await
+    .map_err(|e| FileError::Io(std::io::Error::other(e)))?
+}
+
+/// A document split into its YAML frontmatter and markdown body
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentWithFrontmatter {
+    pub body: String,
+    /// `None` when the document has no frontmatter block
+    pub frontmatter: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Split a leading `---` YAML block (as written by Obsidian, Jekyll, etc.)
+/// from the body. Returns `None` when there is no block or it isn't a YAML
+/// mapping, so those files are treated as plain markdown.
+pub(crate) fn split_frontmatter(
+    content: &str,
+) -> Option<(serde_json::Map<String, serde_json::Value>, &str)> {
+    let rest = content.trim_start_matches('\u{feff}').strip_prefix("---")?;
+    let rest = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))?;
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let marker = line.trim_end_matches(['\r', '\n']);
+        if marker == "---" || marker == "..." {
+            let value: serde_json::Value = serde_yaml::from_str(&rest[..offset]).ok()?;
+            let frontmatter = match value {
+                serde_json::Value::Object(map) => map,
+                serde_json::Value::Null => serde_json::Map::new(),
+                _ => return None,
+            };
+            return Some((frontmatter, &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Read a document, separating any YAML frontmatter from the body
+#[tauri::command]
+pub async fn read_document_with_frontmatter(
+    path: String,
+) -> Result<DocumentWithFrontmatter, FileError> {
+    let content = tokio::fs::read_to_string(&path).await?;
+
+    Ok(match split_frontmatter(&content) {
+        Some((frontmatter, body)) => DocumentWithFrontmatter {
+            body: body.to_string(),
+            frontmatter: Some(frontmatter),
+        },
+        None => DocumentWithFrontmatter {
+            body: content,
+            frontmatter: None,
+        },
+    })
+}
+
+/// Modification time in milliseconds since the Unix epoch
+async fn modified_millis(path: &Path) -> Result<u64, FileError> {
+    let modified = tokio::fs::metadata(path).await?.modified()?;
+    Ok(modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0))
+}
+
+/// Get a document's modification time (ms since the Unix epoch), to pass
+/// back to `write_document` as `expected_mtime`.
+#[tauri::command]
+pub async fn get_document_mtime(path: String) -> Result<u64, FileError> {
+    modified_millis(Path::new(&path)).await
+}
+
+/// Write a document atomically and return its new modification time.
+/// When `expected_mtime` is given and the file on disk is newer, nothing is
+/// written and `FileError::Conflict` is returned.
+#[tauri::command]
+pub async fn write_document(
+    path: String,
+    content: String,
+    backup: Option<bool>,
+    expected_mtime: Option<u64>,
+) -> Result<u64, FileError> {
+    // Refuse to clobber changes made by another app
+    if let Some(expected) = expected_mtime {
+        if let Ok(on_disk) = modified_millis(Path::new(&path)).await {
+            if on_disk > expected {
+                return Err(FileError::Conflict(
+                    "The document was changed by another app since it was opened".to_string(),
+                ));
+            }
+        }
+    }
+
+    // Back up the current version before it gets overwritten
+    if backup.unwrap_or(false) && PathBuf::from(&path).exists() {
+        create_document_backup(path.clone(), None).await?;
+    }
+
+    write_atomic(Path::new(&path), content.as_bytes()).await?;
+    note_own_write(Path::new(&path));
+
+    modified_millis(Path::new(&path)).await
+}
+
+/// A document rewritten on disk by the backend, for the editor to take over
+/// instead of saving its stale copy back
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewrittenDocument {
+    pub content: String,
+    /// New modification time, to pass as the next `expected_mtime`
+    pub mtime: u64,
+    pub sidecar: Sidecar,
+}
+
+/// Emitted when a damaged sidecar had to be replaced on read
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarRecoveredEvent {
+    pub md_path: String,
+    /// Where the damaged sidecar was moved
+    pub corrupt_path: String,
+    /// Whether the data came from a leftover temp file rather than a fresh sidecar
+    pub from_temp_file: bool,
 }
 
+/// Read a document's sidecar, creating it if missing.
+/// A damaged sidecar never blocks opening the document: it is moved aside
+/// and replaced (see `recover_sidecar`), emitting 'sidecar-recovered'.
 #[tauri::command]
-pub async fn read_sidecar(md_path: String) -> Result<Sidecar, FileError> {
+pub async fn read_sidecar(app: AppHandle, md_path: String) -> Result<Sidecar, FileError> {
     let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
     
-    if !sidecar_path.exists() {
-        // Create new sidecar if it doesn't exist
-        let sidecar = Sidecar::new();
+    if !sidecar_exists(&sidecar_path) {
+        // Create new sidecar if it doesn't exist, taking the concept title
+        // from frontmatter when the document has one
+        let mut sidecar = Sidecar::new();
+        sidecar.settings.word_goal = load_app_settings().default_word_goal.filter(|g| *g > 0);
+        let content = tokio::fs::read_to_string(&md_path).await.unwrap_or_default();
+        let title = split_frontmatter(&content).and_then(|(frontmatter, _)| {
+            frontmatter
+                .get("title")
+                .and_then(|t| t.as_str())
+                .map(|t| t.trim().to_string())
+        });
+        if let Some(title) = title.filter(|t| !t.is_empty()) {
+            sidecar.seed_concept_title(&title);
+        }
         let json = serde_json::to_string_pretty(&sidecar)?;
         tokio::fs::write(&sidecar_path, json).await?;
+        note_own_write(&sidecar_path);
         return Ok(sidecar);
     }
     
-    let content = tokio::fs::read_to_string(&sidecar_path).await?;
-    let sidecar: Sidecar = serde_json::from_str(&content)?;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (sidecar, migrated) = match parse_sidecar(&content) {
+        Ok(parsed) => parsed,
+        Err(FileError::Json(e)) => {
+            tracing::warn!(path = %sidecar_path.display(), error = %e, "Sidecar is corrupted");
+            return recover_sidecar(&app, &md_path, &sidecar_path).await;
+        }
+        Err(e) => return Err(e),
+    };
+
+    // Persist the upgrade so it only runs once
+    if migrated {
+        save_sidecar(&sidecar_path, &sidecar).await?;
+    }
+
     Ok(sidecar)
 }
 
+/// Untangle a document's sidecar after files were moved or edited by hand.
+/// A sidecar named after the wrong file (`notes.md.writing.json`, or a case
+/// mismatch) is renamed to match the document, missing fields are filled
+/// with defaults, and a missing sidecar is created. Refreshes
+/// `meta.lastOpenedAt` and returns the repaired sidecar.
 #[tauri::command]
-pub async fn write_sidecar(md_path: String, sidecar: Sidecar) -> Result<(), FileError> {
+pub async fn repair_document_link(app: AppHandle, md_path: String) -> Result<Sidecar, FileError> {
     let sidecar_path = get_sidecar_path(&md_path)?;
-    let json = serde_json::to_string_pretty(&sidecar)?;
-    
-    // Atomic write: temp file then rename
-    let temp_path = format!("{}.tmp", sidecar_path.display());
-    tokio::fs::write(&temp_path, &json).await?;
-    tokio::fs::rename(&temp_path, &sidecar_path).await?;
-    
+    let _guard = lock_sidecar(&sidecar_path).await;
+    if !Path::new(&md_path).exists() {
+        return Err(FileError::InvalidPath("Document does not exist".to_string()));
+    }
+
+    if !sidecar_exists(&sidecar_path) {
+        if let Some(stray) = find_misnamed_sidecar(&md_path, &sidecar_path) {
+            let target = if stray.extension().and_then(|e| e.to_str()) == Some("gz") {
+                compressed_sidecar_path(&sidecar_path)
+            } else {
+                sidecar_path.clone()
+            };
+            tracing::info!(
+                from = %stray.display(),
+                to = %target.display(),
+                "Renaming mismatched sidecar"
+            );
+            move_file(&stray, &target).await?;
+        }
+    }
+
+    let mut sidecar = if sidecar_exists(&sidecar_path) {
+        let parsed = read_sidecar_file(&sidecar_path)
+            .await
+            .and_then(|content| repair_sidecar_json(&content));
+        match parsed {
+            Ok(sidecar) => sidecar,
+            Err(FileError::Json(e)) => {
+                tracing::warn!(path = %sidecar_path.display(), error = %e, "Sidecar is corrupted");
+                recover_sidecar(&app, &md_path, &sidecar_path).await?
+            }
+            Err(e) => return Err(e),
+        }
+    } else {
+        Sidecar::new()
+    };
+
+    if sidecar.document_id.trim().is_empty() {
+        sidecar.document_id = uuid::Uuid::new_v4().to_string();
+    }
+    sidecar.meta.last_opened_at = chrono::Utc::now().to_rfc3339();
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(sidecar)
+}
+
+/// Parse a sidecar like `parse_sidecar`, then fill in any missing fields
+fn repair_sidecar_json(content: &[u8]) -> Result<Sidecar, FileError> {
+    let mut value = sidecar_json_object(content)?;
+    migrate_sidecar(&mut value).map_err(FileError::UnsupportedSidecarVersion)?;
+    backfill_sidecar(&mut value);
+    Ok(serde_json::from_value(value)?)
+}
+
+/// A sidecar next to the document that belongs to it but has the wrong name:
+/// named after the full file name, or differing only in case
+fn find_misnamed_sidecar(md_path: &str, sidecar_path: &Path) -> Option<PathBuf> {
+    let md = Path::new(md_path);
+    let parent = md.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = md.file_name()?.to_string_lossy().to_string();
+    let expected = sidecar_path.file_name()?.to_string_lossy().to_lowercase();
+    let long_name = format!("{}.writing.json", file_name).to_lowercase();
+
+    std::fs::read_dir(parent)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .find(|path| {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let name = name.strip_suffix(".gz").unwrap_or(&name);
+            name == expected || name == long_name
+        })
+}
+
+/// Write a sidecar immediately, replacing any queued autosave for it.
+/// `compress` switches the sidecar to gzip (`.writing.json.gz`) or back to
+/// plain JSON; when omitted the current format is kept.
+#[tauri::command]
+pub async fn write_sidecar(
+    queue: State<'_, SidecarSaveQueue>,
+    md_path: String,
+    sidecar: Sidecar,
+    compress: Option<bool>,
+) -> Result<(), FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let mut written = queue.written.lock().await;
+    queue.pending.lock().unwrap().remove(&sidecar_path);
+
+    match compress {
+        Some(compress) => {
+            let _guard = lock_sidecar(&sidecar_path).await;
+            save_sidecar_as(&sidecar_path, &sidecar, compress).await?;
+            written.insert(sidecar_path, queue.next_seq());
+            Ok(())
+        }
+        None => write_queued_sidecar(&mut written, &sidecar_path, queue.next_seq(), &sidecar).await,
+    }
+}
+
+/// Move a damaged sidecar to `.writing.json.corrupt` and replace it with the
+/// leftover temp file from an interrupted write (plain or gzipped) if that
+/// parses, otherwise with a fresh sidecar.
+async fn recover_sidecar(
+    app: &AppHandle,
+    md_path: &str,
+    sidecar_path: &Path,
+) -> Result<Sidecar, FileError> {
+    let damaged_path = existing_sidecar_files(sidecar_path)
+        .pop()
+        .unwrap_or_else(|| sidecar_path.to_path_buf());
+    let corrupt_path = PathBuf::from(format!("{}.corrupt", damaged_path.display()));
+    tokio::fs::rename(&damaged_path, &corrupt_path).await?;
+
+    // Look at the temp file for the damaged format first
+    let mut temps = [
+        (temp_path_for(sidecar_path), false),
+        (temp_path_for(&compressed_sidecar_path(sidecar_path)), true),
+    ];
+    if damaged_path.extension().and_then(|e| e.to_str()) == Some("gz") {
+        temps.reverse();
+    }
+    let mut from_temp = None;
+    for (temp_path, compressed) in &temps {
+        from_temp = read_temp_sidecar(temp_path, *compressed).await;
+        if from_temp.is_some() {
+            break;
+        }
+    }
+    let from_temp_file = from_temp.is_some();
+    let sidecar = from_temp.unwrap_or_else(Sidecar::new);
+
+    save_sidecar(sidecar_path, &sidecar).await?;
+
+    tracing::info!(path = %sidecar_path.display(), from_temp_file, "Recovered sidecar");
+    let _ = app.emit(
+        "sidecar-recovered",
+        SidecarRecoveredEvent {
+            md_path: md_path.to_string(),
+            corrupt_path: corrupt_path.to_string_lossy().to_string(),
+            from_temp_file,
+        },
+    );
+
+    Ok(sidecar)
+}
+
+/// Parse raw sidecar JSON, rejecting anything that isn't an object as
+/// corrupt so it goes through the same recovery as unparseable JSON
+fn sidecar_json_object(content: &[u8]) -> Result<serde_json::Value, FileError> {
+    let value: serde_json::Value = serde_json::from_slice(content)?;
+    if !value.is_object() {
+        return Err(FileError::Json(serde::de::Error::custom(
+            "sidecar is not a JSON object",
+        )));
+    }
+    Ok(value)
+}
+
+/// Parse sidecar JSON, upgrading older schema versions first.
+/// Returns the sidecar and whether it was migrated.
+pub(crate) fn parse_sidecar(content: &[u8]) -> Result<(Sidecar, bool), FileError> {
+    let mut value = sidecar_json_object(content)?;
+    let migrated = migrate_sidecar(&mut value).map_err(FileError::UnsupportedSidecarVersion)?;
+    let sidecar: Sidecar = serde_json::from_value(value)?;
+    Ok((sidecar, migrated))
+}
+
+pub(crate) async fn save_sidecar(sidecar_path: &Path, sidecar: &Sidecar) -> Result<(), FileError> {
+    // Keep whichever format the sidecar is already stored in
+    let compress = compressed_sidecar_path(sidecar_path).exists();
+    save_sidecar_as(sidecar_path, sidecar, compress).await
+}
+
+/// Write a sidecar as plain or gzipped JSON, removing the other variant
+async fn save_sidecar_as(
+    sidecar_path: &Path,
+    sidecar: &Sidecar,
+    compress: bool,
+) -> Result<(), FileError> {
+    let json = serde_json::to_vec_pretty(sidecar)?;
+    let compressed_path = compressed_sidecar_path(sidecar_path);
+
+    let (target, stale) = if compress {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&json)?;
+        write_atomic(&compressed_path, &encoder.finish()?).await?;
+        (compressed_path, sidecar_path.to_path_buf())
+    } else {
+        write_atomic(sidecar_path, &json).await?;
+        (sidecar_path.to_path_buf(), compressed_path)
+    };
+    note_own_write(&target);
+
+    if stale.exists() {
+        tokio::fs::remove_file(&stale).await?;
+        note_own_write(&stale);
+    }
+
     Ok(())
 }
 
+/// Move a document to a new stage and record the transition in its sidecar.
+/// The sidecar is updated with a single atomic write.
+#[tauri::command]
+pub async fn transition_stage(
+    md_path: String,
+    new_stage: DocumentStage,
+) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+
+    let mut sidecar = if sidecar_exists(&sidecar_path) {
+        let content = read_sidecar_file(&sidecar_path).await?;
+        parse_sidecar(&content)?.0
+    } else {
+        Sidecar::new()
+    };
+
+    sidecar.transition_stage(new_stage);
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(sidecar)
+}
+
+/// Trim a document's conversation to its last `keep_last` messages, folding
+/// older ones into the conversation summary. Pass `summary` to supply one
+/// (e.g. generated by Claude) instead of the built-in condensed transcript.
+#[tauri::command]
+pub async fn prune_conversation(
+    md_path: String,
+    keep_last: usize,
+    summary: Option<String>,
+) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (mut sidecar, _) = parse_sidecar(&content)?;
+
+    if sidecar.conversation.prune(keep_last, summary) > 0 {
+        save_sidecar(&sidecar_path, &sidecar).await?;
+    }
+
+    Ok(sidecar)
+}
+
+/// Remove editing history older than `keep_days` days. Rejected edits are
+/// kept unless `include_rejected` is set, as are entries with unreadable
+/// timestamps. Returns the trimmed sidecar.
+#[tauri::command]
+pub async fn compact_editing_history(
+    md_path: String,
+    keep_days: u32,
+    include_rejected: Option<bool>,
+) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (mut sidecar, _) = parse_sidecar(&content)?;
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(i64::from(keep_days));
+    let removed = sidecar.compact_editing_history(cutoff, include_rejected.unwrap_or(false));
+    if removed > 0 {
+        tracing::info!(path = %sidecar_path.display(), removed, "Compacted editing history");
+        save_sidecar(&sidecar_path, &sidecar).await?;
+    }
+
+    Ok(sidecar)
+}
+
+/// Move the source document's conversation into the target's, interleaved by
+/// time. With `clear_source`, the source conversation is emptied once the
+/// target is saved. Returns the updated target sidecar.
+#[tauri::command]
+pub async fn merge_conversations(
+    source_md_path: String,
+    target_md_path: String,
+    clear_source: Option<bool>,
+) -> Result<Sidecar, FileError> {
+    let source_path = get_sidecar_path(&source_md_path)?;
+    let target_path = get_sidecar_path(&target_md_path)?;
+    if source_path == target_path {
+        return Err(FileError::InvalidPath("Can't merge a conversation into itself".to_string()));
+    }
+
+    // Lock in path order so two opposite merges can't deadlock
+    let (first, second) = if source_path < target_path {
+        (&source_path, &target_path)
+    } else {
+        (&target_path, &source_path)
+    };
+    let _first_guard = lock_sidecar(first).await;
+    let _second_guard = lock_sidecar(second).await;
+
+    let (mut source, _) = parse_sidecar(&read_sidecar_file(&source_path).await?)?;
+    let (mut target, _) = parse_sidecar(&read_sidecar_file(&target_path).await?)?;
+
+    let messages = source.conversation.messages.clone();
+    if target.conversation.merge(messages) == 0 {
+        return Ok(target);
+    }
+    save_sidecar(&target_path, &target).await?;
+
+    if clear_source.unwrap_or(false) {
+        source.conversation.messages.clear();
+        save_sidecar(&source_path, &source).await?;
+    }
+
+    Ok(target)
+}
+
+/// Append a message to a document's conversation. The read-modify-write runs
+/// under the sidecar's lock, so concurrent appends can't drop messages.
+#[tauri::command]
+pub async fn append_chat_message(md_path: String, message: ChatMessage) -> Result<(), FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+
+    let (mut sidecar, _) = parse_sidecar(&read_sidecar_file(&sidecar_path).await?)?;
+    sidecar.conversation.messages.push(message);
+    save_sidecar(&sidecar_path, &sidecar).await
+}
+
+/// Set a custom metadata field on a document, replacing any previous value
+#[tauri::command]
+pub async fn set_custom_field(
+    md_path: String,
+    key: String,
+    value: serde_json::Value,
+) -> Result<Sidecar, FileError> {
+    if key.trim().is_empty() {
+        return Err(FileError::InvalidPath(
+            "Custom field name is empty".to_string(),
+        ));
+    }
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+
+    let (mut sidecar, _) = parse_sidecar(&read_sidecar_file(&sidecar_path).await?)?;
+    sidecar.custom_fields.insert(key, value);
+    save_sidecar(&sidecar_path, &sidecar).await?;
+    Ok(sidecar)
+}
+
+/// Remove a custom metadata field. Removing a field that isn't set is a
+/// no-op.
+#[tauri::command]
+pub async fn remove_custom_field(md_path: String, key: String) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+
+    let (mut sidecar, _) = parse_sidecar(&read_sidecar_file(&sidecar_path).await?)?;
+    if sidecar.custom_fields.remove(&key).is_some() {
+        save_sidecar(&sidecar_path, &sidecar).await?;
+    }
+    Ok(sidecar)
+}
+
+/// Rearrange the outline to match `ordered_ids`, which must contain every
+/// section id exactly once. The previous order is kept in `outline.versions`.
+#[tauri::command]
+pub async fn reorder_outline(
+    md_path: String,
+    ordered_ids: Vec<String>,
+) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (mut sidecar, _) = parse_sidecar(&content)?;
+
+    sidecar
+        .outline
+        .reorder(&ordered_ids)
+        .map_err(FileError::InvalidOutline)?;
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(sidecar)
+}
+
+/// Edit a single outline section, matched by id. The previous outline is
+/// kept in `outline.versions`.
+#[tauri::command]
+pub async fn update_outline_prompt(
+    md_path: String,
+    prompt: OutlinePrompt,
+) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (mut sidecar, _) = parse_sidecar(&content)?;
+
+    sidecar
+        .outline
+        .update_prompt(prompt)
+        .map_err(FileError::InvalidOutline)?;
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(sidecar)
+}
+
+/// Build an outline from the document's headings: each heading becomes a
+/// section titled after it, described by the paragraph that follows, with
+/// the section's current word count as its estimate. Nested headings keep
+/// their depth below the shallowest heading level. With `apply`, the result
+/// replaces `outline.current`.
+#[tauri::command]
+pub async fn extract_outline_from_document(
+    md_path: String,
+    apply: Option<bool>,
+) -> Result<Vec<OutlinePrompt>, FileError> {
+    let content = tokio::fs::read_to_string(&md_path).await?;
+    let blocks = parse_blocks(&content);
+
+    let top_level = blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Heading { level, .. } => Some(*level),
+            _ => None,
+        })
+        .min()
+        .unwrap_or(1);
+
+    let mut prompts: Vec<OutlinePrompt> = Vec::new();
+    let mut words = 0usize;
+    for block in &blocks {
+        let text: String = match block {
+            Block::Heading { spans, .. }
+            | Block::Paragraph { spans }
+            | Block::ListItem { spans, .. }
+            | Block::Quote { spans } => spans.iter().map(|s| s.text.as_str()).collect(),
+            Block::Code { text } => text.clone(),
+            Block::Rule => continue,
+        };
+
+        if let Block::Heading { level, .. } = block {
+            if let Some(last) = prompts.last_mut() {
+                last.estimated_words = Some(words as u32);
+            }
+            words = 0;
+            prompts.push(OutlinePrompt {
+                id: uuid::Uuid::new_v4().to_string(),
+                title: text.trim().to_string(),
+                description: String::new(),
+                estimated_words: None,
+                depth: Some(level - top_level),
+            });
+            continue;
+        }
+
+        // Text before the first heading doesn't belong to any section
+        let Some(current) = prompts.last_mut() else {
+            continue;
+        };
+        words += text.split_whitespace().count();
+        if current.description.is_empty() && matches!(block, Block::Paragraph { .. }) {
+            current.description = text.trim().to_string();
+        }
+    }
+    if let Some(last) = prompts.last_mut() {
+        last.estimated_words = Some(words as u32);
+    }
+
+    if apply.unwrap_or(false) {
+        let sidecar_path = get_sidecar_path(&md_path)?;
+        let _guard = lock_sidecar(&sidecar_path).await;
+        let mut sidecar = read_existing_sidecar(&sidecar_path)
+            .await?
+            .unwrap_or_else(Sidecar::new);
+        sidecar.outline.replace(prompts.clone());
+        save_sidecar(&sidecar_path, &sidecar).await?;
+    }
+
+    Ok(prompts)
+}
+
+/// Store the system prompt template used for this document's assistant.
+/// An empty template clears it.
+#[tauri::command]
+pub async fn set_system_prompt_template(
+    md_path: String,
+    template: Option<String>,
+) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (mut sidecar, _) = parse_sidecar(&content)?;
+
+    sidecar.settings.system_prompt_template = template.filter(|t| !t.trim().is_empty());
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(sidecar)
+}
+
+/// Set the document's word-count goal. `None` or 0 clears it.
+#[tauri::command]
+pub async fn set_word_goal(md_path: String, goal: Option<u32>) -> Result<Sidecar, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (mut sidecar, _) = parse_sidecar(&content)?;
+
+    sidecar.settings.word_goal = goal.filter(|g| *g > 0);
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(sidecar)
+}
+
+/// Compare two entries in `concept.versions`, field by field
+#[tauri::command]
+pub async fn diff_concept_versions(
+    md_path: String,
+    from_index: usize,
+    to_index: usize,
+) -> Result<ConceptDiff, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (sidecar, _) = parse_sidecar(&content)?;
+
+    let versions = &sidecar.concept.versions;
+    let version = |index: usize| {
+        versions.get(index).ok_or(FileError::VersionOutOfRange {
+            index,
+            count: versions.len(),
+        })
+    };
+
+    Ok(ConceptDiff::between(version(from_index)?, version(to_index)?))
+}
+
 #[tauri::command]
 pub fn file_exists(path: String) -> bool {
     PathBuf::from(path).exists()
 }
 
+/// Path of a document's sidecar. When only the gzipped variant exists, that
+/// path (ending in `.gz`) is returned.
 #[tauri::command]
 pub fn get_sidecar_path_for_document(md_path: String) -> Result<String, FileError> {
     let path = get_sidecar_path(&md_path)?;
+    let compressed_path = compressed_sidecar_path(&path);
+    let path = if !path.exists() && compressed_path.exists() {
+        compressed_path
+    } else {
+        path
+    };
     Ok(path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
 pub async fn rename_document(old_path: String, new_path: String) -> Result<(), FileError> {
-    let old_md = PathBuf::from(&old_path);
-    let new_md = PathBuf::from(&new_path);
+    move_document_files(&old_path, &new_path).await
+}
+
+/// Move a document and its sidecar into another folder, creating the folder
+/// if needed. Returns the document's new path.
+#[tauri::command]
+pub async fn move_document(old_path: String, new_dir: String) -> Result<String, FileError> {
+    let file_name = PathBuf::from(&old_path)
+        .file_name()
+        .map(|n| n.to_os_string())
+        .ok_or_else(|| FileError::InvalidPath("Source path has no file name".to_string()))?;
+    let new_path = PathBuf::from(&new_dir)
+        .join(file_name)
+        .to_string_lossy()
+        .to_string();
+
+    tokio::fs::create_dir_all(&new_dir).await?;
+    move_document_files(&old_path, &new_path).await?;
+
+    Ok(new_path)
+}
+
+/// Longest file stem `sync_filename_to_title` will produce, in characters
+const MAX_TITLE_STEM_CHARS: usize = 100;
+
+/// Rename a document (and its sidecar) after its concept title. A numeric
+/// suffix is added if another document already has that name. Returns the
+/// document's new path, which is unchanged if it already matches.
+#[tauri::command]
+pub async fn sync_filename_to_title(md_path: String) -> Result<String, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    if !sidecar_exists(&sidecar_path) {
+        return Err(FileError::MissingTitle);
+    }
+    let (sidecar, _) = parse_sidecar(&read_sidecar_file(&sidecar_path).await?)?;
+    let stem = sidecar
+        .concept
+        .current
+        .map(|c| title_file_stem(&c.title))
+        .filter(|stem| !stem.is_empty())
+        .ok_or(FileError::MissingTitle)?;
+
+    let old_md = PathBuf::from(&md_path);
+    if old_md.file_stem().and_then(|s| s.to_str()) == Some(stem.as_str()) {
+        return Ok(md_path);
+    }
+    let parent = old_md.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut suffix = 1;
+    let new_md = loop {
+        let name = if suffix == 1 {
+            format!("{}.md", stem)
+        } else {
+            format!("{} {}.md", stem, suffix)
+        };
+        let candidate = parent.join(name);
+        if candidate == old_md {
+            return Ok(md_path);
+        }
+        // A case-only rename of this same document is not a collision
+        if is_same_file(&old_md, &candidate)
+            || !(candidate.exists()
+                || sidecar_exists(&get_sidecar_path(&candidate.to_string_lossy())?))
+        {
+            break candidate;
+        }
+        suffix += 1;
+    };
+
+    let new_path = new_md.to_string_lossy().to_string();
+    move_document_files(&md_path, &new_path).await?;
+
+    Ok(new_path)
+}
+
+/// Device names Windows reserves regardless of extension
+const RESERVED_WINDOWS_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+fn is_reserved_windows_name(stem: &str) -> bool {
+    let base = stem.split('.').next().unwrap_or(stem).trim_end();
+    RESERVED_WINDOWS_NAMES
+        .iter()
+        .any(|name| base.eq_ignore_ascii_case(name))
+}
+
+/// Turn a title into a lowercase, hyphenated file stem, e.g.
+/// "My First Draft!" becomes "my-first-draft". Letters and digits from any
+/// script are kept; everything else becomes a hyphen.
+#[tauri::command]
+pub fn slugify_title(title: String) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug: String = slug.chars().take(MAX_TITLE_STEM_CHARS).collect();
+    let slug = slug.trim_end_matches('-');
 
-    // Validate both paths are .md files
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else if is_reserved_windows_name(slug) {
+        format!("{}-doc", slug)
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Turn a title into a file stem that is safe on macOS, Windows and Linux
+fn title_file_stem(title: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => ' ',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let stem = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let stem: String = stem.chars().take(MAX_TITLE_STEM_CHARS).collect();
+    // Leading dots hide the file; trailing dots and spaces upset Windows
+    let stem = stem.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if is_reserved_windows_name(stem) {
+        // Windows matches the part before the first dot, so the suffix
+        // has to go there: "lpt1.txt" becomes "lpt1 doc.txt"
+        let (base, extension) = stem.split_at(stem.find('.').unwrap_or(stem.len()));
+        format!("{} doc{}", base.trim_end(), extension)
+    } else {
+        stem.to_string()
+    }
+}
+
+/// Check both paths are .md files and that the source exists
+fn validate_document_paths(old_md: &Path, new_md: &Path) -> Result<(), FileError> {
     if old_md.extension().and_then(|e| e.to_str()) != Some("md") {
         return Err(FileError::InvalidPath("Source file must have .md extension".to_string()));
     }
@@ -105,37 +1160,410 @@ pub async fn rename_document(old_path: String, new_path: String) -> Result<(), F
         return Err(FileError::InvalidPath("Target file must have .md extension".to_string()));
     }
 
-    // Check source exists
     if !old_md.exists() {
         return Err(FileError::InvalidPath("Source file does not exist".to_string()));
     }
 
-    // Check target doesn't exist (unless same file with different case)
-    if new_md.exists() && old_path.to_lowercase() != new_path.to_lowercase() {
-        return Err(FileError::InvalidPath("A file with that name already exists".to_string()));
-    }
+    Ok(())
+}
+
+/// Move a document to a new path, taking its sidecar along.
+/// Refuses to overwrite anything already at the destination.
+async fn move_document_files(old_path: &str, new_path: &str) -> Result<(), FileError> {
+    let old_md = PathBuf::from(old_path);
+    let new_md = PathBuf::from(new_path);
+    validate_document_paths(&old_md, &new_md)?;
 
     // Get sidecar paths
-    let old_sidecar = get_sidecar_path(&old_path)?;
-    let new_sidecar = get_sidecar_path(&new_path)?;
+    let old_sidecar = get_sidecar_path(old_path)?;
+    let new_sidecar = get_sidecar_path(new_path)?;
+
+    // Check targets don't exist (unless same file with different case)
+    if !is_same_file(&old_md, &new_md)
+        && (new_md.exists() || (sidecar_exists(&old_sidecar) && sidecar_exists(&new_sidecar)))
+    {
+        return Err(FileError::InvalidPath("A file with that name already exists".to_string()));
+    }
 
-    // Rename the markdown file
-    tokio::fs::rename(&old_md, &new_md).await?;
+    // Move the markdown file
+    move_file(&old_md, &new_md).await?;
 
-    // Rename sidecar if it exists
+    // Move sidecar if it exists, in whichever format it's stored
     if old_sidecar.exists() {
-        tokio::fs::rename(&old_sidecar, &new_sidecar).await?;
+        move_file(&old_sidecar, &new_sidecar).await?;
+    }
+    let old_compressed = compressed_sidecar_path(&old_sidecar);
+    if old_compressed.exists() {
+        move_file(&old_compressed, &compressed_sidecar_path(&new_sidecar)).await?;
+    }
+
+    Ok(())
+}
+
+/// Copy a document to `new_path`. The sidecar comes along with a new
+/// document ID, fresh timestamps and an empty edit history. Returns the new
+/// path.
+#[tauri::command]
+pub async fn duplicate_document(path: String, new_path: String) -> Result<String, FileError> {
+    let old_md = PathBuf::from(&path);
+    let new_md = PathBuf::from(&new_path);
+    validate_document_paths(&old_md, &new_md)?;
+
+    let old_sidecar = get_sidecar_path(&path)?;
+    let new_sidecar = get_sidecar_path(&new_path)?;
+    if new_md.exists() || sidecar_exists(&new_sidecar) {
+        return Err(FileError::InvalidPath("A file with that name already exists".to_string()));
+    }
+
+    tokio::fs::copy(&old_md, &new_md).await?;
+
+    if sidecar_exists(&old_sidecar) {
+        let content = read_sidecar_file(&old_sidecar).await?;
+        let (sidecar, _) = parse_sidecar(&content)?;
+        let compress = compressed_sidecar_path(&old_sidecar).exists();
+        save_sidecar_as(&new_sidecar, &sidecar.duplicate(), compress).await?;
+    }
+
+    Ok(new_path)
+}
+
+/// Whether two paths name the same existing file, as a case-only rename does
+/// on a case-insensitive filesystem. A different file that only differs in
+/// case (on a case-sensitive filesystem) doesn't count.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Rename a file, copying then removing it when the destination is on
+/// another filesystem
+async fn move_file(from: &Path, to: &Path) -> Result<(), FileError> {
+    let renamed = tokio::fs::rename(from, to).await;
+    finish_move(from, to, renamed).await
+}
+
+/// Act on the outcome of renaming `from` to `to`: a cross-device failure
+/// falls back to copying, anything else is returned as is
+async fn finish_move(
+    from: &Path,
+    to: &Path,
+    renamed: std::io::Result<()>,
+) -> Result<(), FileError> {
+    match renamed {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            tracing::warn!(path = %to.display(), "Rename crossed devices, copying instead");
+            copy_then_remove(from, to).await
+        }
+        result => Ok(result?),
+    }
+}
+
+/// The cross-device fallback for `move_file`. `from` is only removed once
+/// the copy has fully landed, so a failed copy never loses the source.
+async fn copy_then_remove(from: &Path, to: &Path) -> Result<(), FileError> {
+    tokio::fs::copy(from, to).await?;
+    tokio::fs::remove_file(from).await?;
+    Ok(())
+}
+
+// ============================================
+// Crash recovery
+// ============================================
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecoveryAction {
+    /// The temp file was newer than the sidecar and replaced it
+    Promoted,
+    /// The sidecar was already newer; the temp file was removed
+    Discarded,
+    /// The temp file couldn't be parsed and was removed
+    Corrupt,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredWrite {
+    pub temp_path: String,
+    pub sidecar_path: String,
+    pub action: RecoveryAction,
+}
+
+/// Temp files younger than this are left alone by `recover_pending_writes`
+const IN_FLIGHT_WRITE_GRACE: Duration = Duration::from_secs(10);
+
+/// The sidecar held by a temp file, if it's readable
+async fn read_temp_sidecar(temp_path: &Path, compressed: bool) -> Option<Sidecar> {
+    let bytes = tokio::fs::read(temp_path).await.ok()?;
+    let json = if compressed {
+        gunzip_sidecar(&bytes).ok()?
+    } else {
+        bytes
+    };
+    parse_sidecar(&json).ok().map(|(sidecar, _)| sidecar)
+}
+
+/// Finish or clean up sidecar writes interrupted by a crash in `dir`. Orphaned
+/// `.writing.json.tmp` (or `.writing.json.gz.tmp`) files that parse are
+/// promoted when newer than the live sidecar; the rest are deleted.
+#[tauri::command]
+pub async fn recover_pending_writes(dir: String) -> Result<Vec<RecoveredWrite>, FileError> {
+    let mut recovered = Vec::new();
+    let mut entries = tokio::fs::read_dir(&dir).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let temp_path = entry.path();
+        let Some(name) = temp_path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(live_name) = name.strip_suffix(".tmp") else {
+            continue;
+        };
+        let compressed = live_name.ends_with(".writing.json.gz");
+        if !compressed && !live_name.ends_with(".writing.json") {
+            continue;
+        }
+        let live_path = temp_path.with_file_name(live_name);
+
+        // A temp this fresh may belong to a write that's still in progress
+        let temp_modified = entry.metadata().await?.modified().ok();
+        if temp_modified
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age < IN_FLIGHT_WRITE_GRACE)
+        {
+            continue;
+        }
+
+        let action = if read_temp_sidecar(&temp_path, compressed).await.is_none() {
+            tokio::fs::remove_file(&temp_path).await?;
+            RecoveryAction::Corrupt
+        } else {
+            let live_modified = tokio::fs::metadata(&live_path)
+                .await
+                .ok()
+                .and_then(|m| m.modified().ok());
+
+            if live_modified.is_none() || temp_modified > live_modified {
+                move_file(&temp_path, &live_path).await?;
+                RecoveryAction::Promoted
+            } else {
+                tokio::fs::remove_file(&temp_path).await?;
+                RecoveryAction::Discarded
+            }
+        };
+
+        tracing::info!(path = %temp_path.display(), ?action, "Recovered interrupted sidecar write");
+        recovered.push(RecoveredWrite {
+            temp_path: temp_path.to_string_lossy().to_string(),
+            sidecar_path: live_path.to_string_lossy().to_string(),
+            action,
+        });
+    }
+
+    Ok(recovered)
+}
+
+// ============================================
+// Edit undo/redo
+// ============================================
+
+/// Swap one side of a recorded edit for the other in the document, and log
+/// the swap as a new history entry. `revert` restores the `before` text;
+/// otherwise the `after` text is reapplied.
+async fn swap_edit(
+    md_path: &str,
+    edit_id: &str,
+    revert: bool,
+) -> Result<RewrittenDocument, FileError> {
+    let sidecar_path = get_sidecar_path(md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (mut sidecar, _) = parse_sidecar(&content)?;
+
+    let entry = sidecar
+        .editing_history
+        .iter()
+        .find(|e| e.id == edit_id)
+        .cloned()
+        .ok_or_else(|| FileError::EditNotFound(edit_id.to_string()))?;
+
+    let (current, replacement) = if revert {
+        (&entry.after, &entry.before)
+    } else {
+        (&entry.before, &entry.after)
+    };
+
+    // Empty text matches everywhere, so there's no region to swap
+    if current.is_empty() {
+        return Err(FileError::Conflict(format!(
+            "The {} text is empty, so it can't be found in the document",
+            entry.scope
+        )));
+    }
+
+    // Only touch the document when the region is unambiguous and unchanged
+    let document = tokio::fs::read_to_string(md_path).await?;
+    if document.matches(current.as_str()).count() != 1 {
+        return Err(FileError::Conflict(format!(
+            "The {} text no longer matches the document",
+            entry.scope
+        )));
+    }
+    let updated = document.replacen(current.as_str(), replacement, 1);
+
+    let verb = if revert { "Reverted" } else { "Reapplied" };
+    sidecar.editing_history.push(EditHistoryEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        scope: entry.scope.clone(),
+        before: current.clone(),
+        after: replacement.clone(),
+        accepted: true,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        rationale: Some(format!("{} edit {}", verb, entry.id)),
+    });
+
+    let mtime = write_document(md_path.to_string(), updated.clone(), None, None).await?;
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(RewrittenDocument {
+        content: updated,
+        mtime,
+        sidecar,
+    })
+}
+
+/// Undo an edit by restoring its `before` text, returning the updated document.
+/// Fails with `FileError::Conflict` if the edited text is no longer in place.
+#[tauri::command]
+pub async fn revert_edit(md_path: String, edit_id: String) -> Result<RewrittenDocument, FileError> {
+    swap_edit(&md_path, &edit_id, true).await
+}
+
+/// Redo an edit by putting its `after` text back, returning the updated document.
+/// Fails with `FileError::Conflict` if the original text is no longer in place.
+#[tauri::command]
+pub async fn reapply_edit(
+    md_path: String,
+    edit_id: String,
+) -> Result<RewrittenDocument, FileError> {
+    swap_edit(&md_path, &edit_id, false).await
+}
+
+/// Delete a document and its sidecar by moving them to the OS trash,
+/// so an accidental delete can be undone from there.
+#[tauri::command]
+pub async fn delete_document(path: String) -> Result<(), FileError> {
+    let md_path = PathBuf::from(&path);
+
+    if md_path.extension().and_then(|e| e.to_str()) != Some("md") {
+        return Err(FileError::InvalidPath("File must have .md extension".to_string()));
+    }
+    if !md_path.exists() {
+        return Err(FileError::InvalidPath("File does not exist".to_string()));
     }
 
+    let mut targets = vec![md_path];
+    targets.extend(existing_sidecar_files(&get_sidecar_path(&path)?));
+
+    // Trash APIs are blocking (and slow on some platforms)
+    tokio::task::spawn_blocking(move || trash::delete_all(&targets))
+        .await
+        .map_err(|e| FileError::Trash(e.to_string()))?
+        .map_err(|e| FileError::Trash(e.to_string()))?;
+
     Ok(())
 }
 
+/// Show a file in the OS file manager (Finder, Explorer, ...), selected in
+/// its containing folder
+#[tauri::command]
+pub async fn reveal_in_file_manager(app: AppHandle, path: String) -> Result<(), FileError> {
+    if !Path::new(&path).exists() {
+        return Err(FileError::InvalidPath(format!("{} does not exist", path)));
+    }
+    app.opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| FileError::Reveal(e.to_string()))
+}
+
+/// Resolve a `fizz://open?path=...` link to a document path. Only existing
+/// .md files inside the documents directory are accepted, so a link from
+/// another app can't open arbitrary files.
+pub(crate) fn resolve_open_link(url: &reqwest::Url) -> Result<String, FileError> {
+    let path = url
+        .query_pairs()
+        .find(|(key, _)| key == "path")
+        .map(|(_, value)| PathBuf::from(value.as_ref()))
+        .ok_or_else(|| FileError::InvalidPath("Link has no path".to_string()))?;
+
+    if path.extension().and_then(|e| e.to_str()) != Some("md") {
+        return Err(FileError::InvalidPath(
+            "Link must point to a .md file".to_string(),
+        ));
+    }
+    if !path.is_file() {
+        return Err(FileError::InvalidPath(format!(
+            "{} does not exist",
+            path.display()
+        )));
+    }
+
+    // Canonicalize both sides so ../ and symlinks can't escape
+    let docs_dir = document_dir()
+        .or_else(home_dir)
+        .ok_or(FileError::NoHomeDirectory)?
+        .canonicalize()?;
+    let path = path.canonicalize()?;
+    if !path.starts_with(&docs_dir) {
+        return Err(FileError::InvalidPath(format!(
+            "{} is outside the documents directory",
+            path.display()
+        )));
+    }
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// The latest `fizz://open` link not yet taken by the frontend. A link that
+/// launches the app arrives before the frontend listens for
+/// 'open-document', so it waits here.
+#[derive(Default)]
+pub struct PendingOpenDocument(Mutex<Option<String>>);
+
+impl PendingOpenDocument {
+    pub(crate) fn set(&self, path: String) {
+        *self.0.lock().unwrap() = Some(path);
+    }
+}
+
+/// Take the document a `fizz://open` link asked to open, if any. Called at
+/// startup and on each 'open-document' event, so every link opens once.
+#[tauri::command]
+pub fn take_pending_open_document(pending: State<'_, PendingOpenDocument>) -> Option<String> {
+    pending.0.lock().unwrap().take()
+}
+
 /// Get the default documents directory for WriteCraft files.
-/// Creates ~/Documents/WriteCraft if it doesn't exist.
+/// Creates ~/Documents/WriteCraft if it doesn't exist, falling back to
+/// ~/WriteCraft when the platform has no documents directory.
 #[tauri::command]
 pub async fn get_writecraft_documents_dir() -> Result<String, FileError> {
     let docs_dir = document_dir()
-        .ok_or_else(|| FileError::InvalidPath("Could not find documents directory".to_string()))?;
+        .or_else(home_dir)
+        .ok_or(FileError::NoHomeDirectory)?;
 
     let writecraft_dir = docs_dir.join("WriteCraft");
 
@@ -146,3 +1574,2612 @@ pub async fn get_writecraft_documents_dir() -> Result<String, FileError> {
 
     Ok(writecraft_dir.to_string_lossy().to_string())
 }
+
+// ============================================
+// Autosave
+// ============================================
+
+/// Quiet period after the last queued change before a sidecar is written
+const AUTOSAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Debounced sidecar saves, keyed by sidecar path
+#[derive(Default)]
+pub struct SidecarSaveQueue {
+    /// Latest queued sidecar per path, tagged with its sequence number
+    pending: Mutex<HashMap<PathBuf, (u64, Sidecar)>>,
+    seq: AtomicU64,
+    /// Held while taking a save off the queue and writing it, so writes never
+    /// overlap. Records the newest sequence written per path so an older save
+    /// never lands on top of a newer one.
+    written: tokio::sync::Mutex<HashMap<PathBuf, u64>>,
+}
+
+impl SidecarSaveQueue {
+    fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Take the queued save for a path if it hasn't been replaced since
+    fn take_if_current(&self, sidecar_path: &Path, seq: u64) -> Option<Sidecar> {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.get(sidecar_path) {
+            Some((queued, _)) if *queued == seq => pending.remove(sidecar_path).map(|(_, s)| s),
+            _ => None,
+        }
+    }
+
+    /// Write every queued save now
+    pub(crate) async fn flush(&self) -> Result<(), FileError> {
+        // Waits for any save already being written
+        let mut written = self.written.lock().await;
+        let pending: Vec<(PathBuf, (u64, Sidecar))> =
+            self.pending.lock().unwrap().drain().collect();
+
+        let mut first_error = None;
+        for (sidecar_path, (seq, sidecar)) in pending {
+            if let Err(e) = write_queued_sidecar(&mut written, &sidecar_path, seq, &sidecar).await {
+                tracing::error!(path = %sidecar_path.display(), error = %e, "Flushing autosave failed");
+                first_error.get_or_insert(e);
+            }
+        }
+
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+/// Per-sidecar locks, created on first use
+static SIDECAR_LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Lock a sidecar for a read-modify-write. Every command that rewrites a
+/// sidecar holds this from its read to its write, so concurrent commands,
+/// autosaves and streaming replies can't drop each other's changes.
+/// The lock isn't reentrant; helpers called under it must not take it again.
+pub(crate) async fn lock_sidecar(sidecar_path: &Path) -> tokio::sync::OwnedMutexGuard<()> {
+    let lock = SIDECAR_LOCKS
+        .lock()
+        .unwrap()
+        .entry(sidecar_path.to_path_buf())
+        .or_default()
+        .clone();
+    lock.lock_owned().await
+}
+
+// ============================================
+// Streaming replies
+// ============================================
+
+/// How often an in-progress reply is written to the sidecar
+const REPLY_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Payload of 'claude-reply-persisted', sent once a streamed reply is
+/// complete so the frontend can put the message in its copy of the sidecar.
+/// It isn't suffixed with the request's channel since the document store
+/// listens for every document's replies.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplyPersistedEvent {
+    pub md_path: String,
+    pub message: ChatMessage,
+}
+
+/// An assistant reply saved to a document's conversation as it streams, so
+/// the partial text survives a crash. Nothing is written until text arrives,
+/// so an empty reply never reaches the sidecar. If the reply is dropped
+/// without `finish` (an early return while streaming), it is finished in
+/// the background.
+pub(crate) struct StreamingReply {
+    state: Option<ReplyState>,
+}
+
+struct ReplyState {
+    app: AppHandle,
+    md_path: String,
+    sidecar_path: PathBuf,
+    message: ChatMessage,
+    text: String,
+    last_flush: Option<Instant>,
+    dirty: bool,
+}
+
+impl StreamingReply {
+    /// Prepare an assistant message for the document's conversation
+    pub(crate) fn start(app: &AppHandle, md_path: &str) -> Result<Self, FileError> {
+        Ok(StreamingReply {
+            state: Some(ReplyState {
+                app: app.clone(),
+                md_path: md_path.to_string(),
+                sidecar_path: get_sidecar_path(md_path)?,
+                message: ChatMessage {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    role: "assistant".to_string(),
+                    content: MessageContent::Text(String::new()),
+                    created_at: chrono::Utc::now().to_rfc3339(),
+                },
+                text: String::new(),
+                last_flush: None,
+                dirty: false,
+            }),
+        })
+    }
+
+    /// Add streamed text, writing it out right away for the first text and
+    /// then at most every `REPLY_FLUSH_INTERVAL`
+    pub(crate) async fn push(&mut self, text: &str) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        state.text.push_str(text);
+        state.dirty = true;
+        if state
+            .last_flush
+            .is_none_or(|at| at.elapsed() >= REPLY_FLUSH_INTERVAL)
+        {
+            state.flush_logged().await;
+        }
+    }
+
+    /// Write out whatever has streamed since the last write and emit
+    /// 'claude-reply-persisted'
+    pub(crate) async fn finish(mut self) {
+        if let Some(state) = self.state.take() {
+            state.complete().await;
+        }
+    }
+}
+
+impl Drop for StreamingReply {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            tauri::async_runtime::spawn(state.complete());
+        }
+    }
+}
+
+impl ReplyState {
+    async fn complete(mut self) {
+        if self.text.is_empty() {
+            return;
+        }
+        if self.dirty {
+            self.flush_logged().await;
+        }
+        let _ = self.app.emit(
+            "claude-reply-persisted",
+            ReplyPersistedEvent {
+                md_path: self.md_path.clone(),
+                message: self.message.clone(),
+            },
+        );
+    }
+
+    async fn flush_logged(&mut self) {
+        if let Err(e) = self.flush().await {
+            tracing::warn!(path = %self.sidecar_path.display(), "Failed to save streaming reply: {}", e);
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), FileError> {
+        let _guard = lock_sidecar(&self.sidecar_path).await;
+
+        let (mut sidecar, _) = parse_sidecar(&read_sidecar_file(&self.sidecar_path).await?)?;
+        self.message.content = MessageContent::Text(self.text.clone());
+        let messages = &mut sidecar.conversation.messages;
+        match messages.iter_mut().find(|m| m.id == self.message.id) {
+            Some(existing) => existing.content = self.message.content.clone(),
+            // Also covers a save from the frontend that dropped the message
+            None => messages.push(self.message.clone()),
+        }
+        save_sidecar(&self.sidecar_path, &sidecar).await?;
+
+        self.last_flush = Some(Instant::now());
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Save a sidecar unless a newer save for the same path has been written.
+/// Callers hold the queue's `written` lock, which is always taken before the
+/// sidecar's own lock.
+async fn write_queued_sidecar(
+    written: &mut HashMap<PathBuf, u64>,
+    sidecar_path: &Path,
+    seq: u64,
+    sidecar: &Sidecar,
+) -> Result<(), FileError> {
+    if written.get(sidecar_path).is_some_and(|last| *last > seq) {
+        return Ok(());
+    }
+    let _guard = lock_sidecar(sidecar_path).await;
+    save_sidecar(sidecar_path, sidecar).await?;
+    written.insert(sidecar_path.to_path_buf(), seq);
+    Ok(())
+}
+
+/// Queue a sidecar save. Rapid calls for the same document are coalesced and
+/// only the latest sidecar is written, once no new call has arrived for
+/// `AUTOSAVE_DELAY`. Failures are logged and emitted as 'sidecar-save-failed'.
+#[tauri::command]
+pub fn queue_sidecar_save(
+    app: AppHandle,
+    queue: State<'_, SidecarSaveQueue>,
+    md_path: String,
+    sidecar: Sidecar,
+) -> Result<(), FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let seq = queue.next_seq();
+    queue
+        .pending
+        .lock()
+        .unwrap()
+        .insert(sidecar_path.clone(), (seq, sidecar));
+
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(AUTOSAVE_DELAY).await;
+
+        let queue = app.state::<SidecarSaveQueue>();
+        let mut written = queue.written.lock().await;
+        // Replaced by a newer call or already flushed
+        let Some(sidecar) = queue.take_if_current(&sidecar_path, seq) else {
+            return;
+        };
+        if let Err(e) = write_queued_sidecar(&mut written, &sidecar_path, seq, &sidecar).await {
+            tracing::error!(path = %sidecar_path.display(), error = %e, "Autosave failed");
+            let _ = app.emit("sidecar-save-failed", md_path);
+        }
+    });
+
+    Ok(())
+}
+
+/// Write every queued sidecar save now. The app also does this when it
+/// exits.
+#[tauri::command]
+pub async fn flush_pending_saves(queue: State<'_, SidecarSaveQueue>) -> Result<(), FileError> {
+    queue.flush().await
+}
+
+// ============================================
+// External change watching
+// ============================================
+
+/// Changes within this window are reported as a single event
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+/// How long after WriteCraft writes a file its change events are ignored
+const OWN_WRITE_GRACE: Duration = Duration::from_secs(2);
+
+/// Files WriteCraft wrote recently, so the watcher can tell its own atomic
+/// writes apart from edits made by other apps
+static RECENT_WRITES: LazyLock<Mutex<HashMap<PathBuf, Instant>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn note_own_write(path: &Path) {
+    let mut recent = RECENT_WRITES.lock().unwrap();
+    recent.retain(|_, at| at.elapsed() < OWN_WRITE_GRACE);
+    recent.insert(path.to_path_buf(), Instant::now());
+}
+
+fn is_own_write(path: &Path) -> bool {
+    RECENT_WRITES
+        .lock()
+        .unwrap()
+        .get(path)
+        .is_some_and(|at| at.elapsed() < OWN_WRITE_GRACE)
+}
+
+/// Active document watchers, keyed by document path
+#[derive(Default)]
+pub struct DocumentWatcher {
+    watches: Mutex<HashMap<PathBuf, Debouncer<RecommendedWatcher>>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentChangedEvent {
+    pub path: String,
+    pub document_changed: bool,
+    pub sidecar_changed: bool,
+}
+
+/// Watch a document and its sidecar for changes made outside WriteCraft,
+/// emitting 'document-changed-externally'. Watching again replaces the
+/// previous watch.
+#[tauri::command]
+pub fn watch_document(
+    app: AppHandle,
+    watcher: State<'_, DocumentWatcher>,
+    path: String,
+) -> Result<(), FileError> {
+    let md_path = PathBuf::from(&path);
+    let sidecar_path = get_sidecar_path(&path)?;
+    let md_name = md_path.file_name().map(|n| n.to_os_string());
+    let sidecar_name = sidecar_path.file_name().map(|n| n.to_os_string());
+    let compressed_path = compressed_sidecar_path(&sidecar_path);
+    let compressed_name = compressed_path.file_name().map(|n| n.to_os_string());
+
+    // Watch the folder rather than the file: atomic writes replace the file,
+    // which would end a watch on the file itself
+    let dir = match md_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let handler = move |result: DebounceEventResult| {
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => {
+                tracing::warn!(error = %e, "Document watch error");
+                return;
+            }
+        };
+
+        // Only settled changes count; `AnyContinuous` fires while writes are
+        // still streaming in and would double-report
+        let changed = |name: &Option<std::ffi::OsString>, own_path: &Path| {
+            events.iter().any(|e| {
+                e.kind == DebouncedEventKind::Any && e.path.file_name() == name.as_deref()
+            }) && !is_own_write(own_path)
+        };
+        let document_changed = changed(&md_name, &md_path);
+        let sidecar_changed = changed(&sidecar_name, &sidecar_path)
+            || changed(&compressed_name, &compressed_path);
+
+        if document_changed || sidecar_changed {
+            let _ = app.emit(
+                "document-changed-externally",
+                DocumentChangedEvent {
+                    path: md_path.to_string_lossy().to_string(),
+                    document_changed,
+                    sidecar_changed,
+                },
+            );
+        }
+    };
+
+    let mut debouncer =
+        new_debouncer(WATCH_DEBOUNCE, handler).map_err(|e| FileError::Watch(e.to_string()))?;
+    debouncer
+        .watcher()
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| FileError::Watch(e.to_string()))?;
+
+    watcher
+        .watches
+        .lock()
+        .unwrap()
+        .insert(PathBuf::from(path), debouncer);
+
+    Ok(())
+}
+
+/// Stop watching a document. Unwatching a document that isn't watched is a no-op.
+#[tauri::command]
+pub fn unwatch_document(watcher: State<'_, DocumentWatcher>, path: String) {
+    watcher.watches.lock().unwrap().remove(&PathBuf::from(path));
+}
+
+// ============================================
+// Backups
+// ============================================
+
+const BACKUPS_DIR: &str = ".writecraft/backups";
+const DEFAULT_MAX_BACKUPS: usize = 20;
+
+/// Timestamp used for backup folder names. Avoids characters like `:` that
+/// are not allowed in Windows paths, and sorts chronologically.
+fn backup_timestamp() -> String {
+    chrono::Utc::now().format("%Y-%m-%d_%H-%M-%S-%3f").to_string()
+}
+
+/// Copy a document and its sidecar into `.writecraft/backups/<timestamp>/`
+/// next to the document. Returns the backup directory path.
+/// Keeps at most `max_backups` backups of this document (default 20).
+#[tauri::command]
+pub async fn create_document_backup(
+    path: String,
+    max_backups: Option<usize>,
+) -> Result<String, FileError> {
+    let md_path = PathBuf::from(&path);
+
+    if md_path.extension().and_then(|e| e.to_str()) != Some("md") {
+        return Err(FileError::InvalidPath("File must have .md extension".to_string()));
+    }
+    if !md_path.exists() {
+        return Err(FileError::InvalidPath("Source file does not exist".to_string()));
+    }
+
+    let file_name = md_path
+        .file_name()
+        .ok_or_else(|| FileError::InvalidPath("Invalid document path".to_string()))?;
+    let parent = md_path.parent().unwrap_or_else(|| Path::new("."));
+    let backups_root = parent.join(BACKUPS_DIR);
+    let backup_dir = backups_root.join(backup_timestamp());
+
+    tokio::fs::create_dir_all(&backup_dir).await?;
+    tokio::fs::copy(&md_path, backup_dir.join(file_name)).await?;
+
+    // Documents that haven't been opened yet have no sidecar
+    for sidecar_file in existing_sidecar_files(&get_sidecar_path(&path)?) {
+        if let Some(sidecar_name) = sidecar_file.file_name() {
+            tokio::fs::copy(&sidecar_file, backup_dir.join(sidecar_name)).await?;
+        }
+    }
+
+    prune_backups(
+        &backups_root,
+        file_name,
+        max_backups.unwrap_or(DEFAULT_MAX_BACKUPS),
+    )
+    .await?;
+
+    Ok(backup_dir.to_string_lossy().to_string())
+}
+
+/// Remove the oldest backups of a document, keeping the newest `keep`.
+/// Other documents' backups in the same folder are left alone.
+async fn prune_backups(
+    backups_root: &Path,
+    file_name: &std::ffi::OsStr,
+    keep: usize,
+) -> Result<(), FileError> {
+    let mut backup_dirs = Vec::new();
+    let mut entries = tokio::fs::read_dir(backups_root).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let dir = entry.path();
+        if dir.is_dir() && dir.join(file_name).exists() {
+            backup_dirs.push(dir);
+        }
+    }
+
+    if backup_dirs.len() <= keep {
+        return Ok(());
+    }
+
+    // Timestamped names sort oldest first
+    backup_dirs.sort();
+    let excess = backup_dirs.len() - keep;
+
+    for dir in backup_dirs.into_iter().take(excess) {
+        let md_backup = dir.join(file_name);
+        tokio::fs::remove_file(&md_backup).await?;
+
+        let sidecar_backup = get_sidecar_path(&md_backup.to_string_lossy())?;
+        for sidecar_file in existing_sidecar_files(&sidecar_backup) {
+            tokio::fs::remove_file(&sidecar_file).await?;
+        }
+
+        // Only removes the folder once no other document's backup is in it
+        let _ = tokio::fs::remove_dir(&dir).await;
+    }
+
+    Ok(())
+}
+
+// ============================================
+// Snapshots
+// ============================================
+
+/// Snapshots kept per document; the oldest are dropped past this
+const MAX_SNAPSHOTS: usize = 20;
+
+/// A snapshot just taken, with the sidecar it was saved in
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSnapshot {
+    pub snapshot_id: String,
+    pub sidecar: Sidecar,
+}
+
+/// Save a labelled copy of the document body in its sidecar, returning the
+/// snapshot id and the updated sidecar. Only the newest `MAX_SNAPSHOTS` are
+/// kept.
+#[tauri::command]
+pub async fn snapshot_document(md_path: String, label: String) -> Result<SavedSnapshot, FileError> {
+    let content = tokio::fs::read_to_string(&md_path).await?;
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let _guard = lock_sidecar(&sidecar_path).await;
+    let (mut sidecar, _) = parse_sidecar(&read_sidecar_file(&sidecar_path).await?)?;
+
+    let label = label.trim();
+    let label = if label.is_empty() { "Snapshot" } else { label };
+    let snapshot_id = sidecar.add_snapshot(label, content, MAX_SNAPSHOTS);
+    save_sidecar(&sidecar_path, &sidecar).await?;
+
+    Ok(SavedSnapshot {
+        snapshot_id,
+        sidecar,
+    })
+}
+
+/// Rewrite the document from a snapshot, backing up the current document
+/// and sidecar first (see `create_document_backup`). Returns the restored
+/// document for the editor to take over.
+#[tauri::command]
+pub async fn restore_snapshot(
+    md_path: String,
+    snapshot_id: String,
+) -> Result<RewrittenDocument, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let (sidecar, _) = parse_sidecar(&read_sidecar_file(&sidecar_path).await?)?;
+    let content = sidecar
+        .snapshots
+        .iter()
+        .find(|s| s.id == snapshot_id)
+        .map(|s| s.content.clone())
+        .ok_or(FileError::SnapshotNotFound(snapshot_id))?;
+
+    create_document_backup(md_path.clone(), None).await?;
+    let mtime = write_document(md_path, content.clone(), None, None).await?;
+
+    Ok(RewrittenDocument {
+        content,
+        mtime,
+        sidecar,
+    })
+}
+
+// ============================================
+// Document library
+// ============================================
+
+/// Summary of a document for the library view.
+/// Sidecar-derived fields are `None` when the document has no sidecar yet.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSummary {
+    pub path: String,
+    pub title: Option<String>,
+    pub stage: Option<DocumentStage>,
+    pub created_at: Option<String>,
+    pub modified_at: Option<String>,
+    pub message_count: Option<usize>,
+    /// Value of the custom field asked for in `list_documents`, if set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_field: Option<serde_json::Value>,
+}
+
+/// Read a document's sidecar without creating one if it's missing.
+pub(crate) async fn load_existing_sidecar(md_path: &Path) -> Option<Sidecar> {
+    let sidecar_path = get_sidecar_path(&md_path.to_string_lossy()).ok()?;
+    let content = read_sidecar_file(&sidecar_path).await.ok()?;
+    parse_sidecar(&content).ok().map(|(sidecar, _)| sidecar)
+}
+
+/// Read a sidecar, or `None` if the document doesn't have one yet. Unlike
+/// `load_existing_sidecar`, a sidecar that can't be read is an error, so
+/// callers that write it back never replace it with an empty one.
+pub(crate) async fn read_existing_sidecar(
+    sidecar_path: &Path,
+) -> Result<Option<Sidecar>, FileError> {
+    if !sidecar_exists(sidecar_path) {
+        return Ok(None);
+    }
+    let content = read_sidecar_file(sidecar_path).await?;
+    Ok(Some(parse_sidecar(&content)?.0))
+}
+
+// ============================================
+// Ignore rules
+// ============================================
+
+/// Gitignore-style patterns, one per line, for paths the library should skip
+const IGNORE_FILE_NAME: &str = ".writecraftignore";
+
+/// A line of `.writecraftignore` that isn't a valid glob
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidIgnorePattern {
+    pub pattern: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IgnoreRules {
+    /// Non-blank lines in file order, comments included
+    pub patterns: Vec<String>,
+    pub invalid: Vec<InvalidIgnorePattern>,
+}
+
+/// Build a matcher rooted at `dir`, collecting the lines that don't parse
+fn build_ignore_matcher(
+    dir: &Path,
+    patterns: &[String],
+) -> (Option<Gitignore>, Vec<InvalidIgnorePattern>) {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut invalid = Vec::new();
+    for pattern in patterns {
+        if let Err(e) = builder.add_line(None, pattern) {
+            invalid.push(InvalidIgnorePattern {
+                pattern: pattern.clone(),
+                error: e.to_string(),
+            });
+        }
+    }
+    (builder.build().ok(), invalid)
+}
+
+async fn read_ignore_patterns(dir: &Path) -> Result<Vec<String>, FileError> {
+    match tokio::fs::read_to_string(dir.join(IGNORE_FILE_NAME)).await {
+        Ok(content) => Ok(content
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The patterns in a directory's `.writecraftignore`, with any that are
+/// invalid (and therefore not applied) listed separately
+#[tauri::command]
+pub async fn get_ignore_rules(dir: String) -> Result<IgnoreRules, FileError> {
+    let dir_path = PathBuf::from(&dir);
+    let patterns = read_ignore_patterns(&dir_path).await?;
+    let (_, invalid) = build_ignore_matcher(&dir_path, &patterns);
+    Ok(IgnoreRules { patterns, invalid })
+}
+
+/// Replace a directory's `.writecraftignore`. Rejects the whole list if any
+/// pattern is invalid; an empty list removes the file.
+#[tauri::command]
+pub async fn set_ignore_rules(dir: String, patterns: Vec<String>) -> Result<(), FileError> {
+    let dir_path = PathBuf::from(&dir);
+    if !dir_path.is_dir() {
+        return Err(FileError::InvalidPath("Not a directory".to_string()));
+    }
+
+    let patterns: Vec<String> = patterns
+        .iter()
+        .map(|p| p.trim_end().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let (_, invalid) = build_ignore_matcher(&dir_path, &patterns);
+    if let Some(first) = invalid.first() {
+        return Err(FileError::InvalidIgnorePattern(format!(
+            "{}: {}",
+            first.pattern, first.error
+        )));
+    }
+
+    let ignore_path = dir_path.join(IGNORE_FILE_NAME);
+    if patterns.is_empty() {
+        if ignore_path.exists() {
+            tokio::fs::remove_file(&ignore_path).await?;
+        }
+        return Ok(());
+    }
+    let mut content = patterns.join("\n");
+    content.push('\n');
+    tokio::fs::write(&ignore_path, content).await?;
+    Ok(())
+}
+
+/// Collect .md files in a directory, optionally descending into subfolders.
+/// Hidden folders (like `.writecraft`) and paths matched by the directory's
+/// `.writecraftignore` are skipped.
+pub(crate) async fn collect_markdown_files(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>, FileError> {
+    let patterns = read_ignore_patterns(dir).await?;
+    let (ignore, invalid) = build_ignore_matcher(dir, &patterns);
+    for pattern in &invalid {
+        tracing::warn!(pattern = %pattern.pattern, "Skipping invalid ignore pattern: {}", pattern.error);
+    }
+    let ignored = |path: &Path, is_dir: bool| {
+        ignore
+            .as_ref()
+            .is_some_and(|ignore| ignore.matched(path, is_dir).is_ignore())
+    };
+
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&current).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let file_type = entry.file_type().await?;
+
+            if file_type.is_dir() {
+                let hidden = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with('.'));
+                if recursive && !hidden && !ignored(&path, true) {
+                    pending.push(path);
+                }
+            } else if path.extension().and_then(|e| e.to_str()) == Some("md")
+                && !ignored(&path, false)
+            {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// List the documents in a directory, most recently modified first.
+/// `custom_field` names a sidecar custom field to include in each summary.
+#[tauri::command]
+pub async fn list_documents(
+    dir: String,
+    recursive: Option<bool>,
+    custom_field: Option<String>,
+) -> Result<Vec<DocumentSummary>, FileError> {
+    let dir_path = PathBuf::from(&dir);
+    if !dir_path.is_dir() {
+        return Err(FileError::InvalidPath("Not a directory".to_string()));
+    }
+
+    let files = collect_markdown_files(&dir_path, recursive.unwrap_or(false)).await?;
+
+    let mut documents = Vec::with_capacity(files.len());
+    for path in files {
+        let modified = tokio::fs::metadata(&path)
+            .await
+            .ok()
+            .and_then(|m| m.modified().ok());
+        let sidecar = load_existing_sidecar(&path).await;
+
+        let summary = DocumentSummary {
+            path: path.to_string_lossy().to_string(),
+            title: sidecar
+                .as_ref()
+                .and_then(|s| s.concept.current.as_ref())
+                .map(|c| c.title.clone()),
+            stage: sidecar.as_ref().map(|s| s.stage.clone()),
+            created_at: sidecar.as_ref().map(|s| s.created_at.clone()),
+            modified_at: modified
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()),
+            message_count: sidecar.as_ref().map(|s| s.conversation.messages.len()),
+            custom_field: custom_field.as_ref().and_then(|key| {
+                sidecar
+                    .as_ref()
+                    .and_then(|s| s.custom_fields.get(key).cloned())
+            }),
+        };
+        documents.push((modified, summary));
+    }
+
+    // Most recently modified first
+    documents.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    Ok(documents.into_iter().map(|(_, summary)| summary).collect())
+}
+
+// ============================================
+// Sidecar validation
+// ============================================
+
+/// A problem found in a sidecar, located by a dotted path such as
+/// `conversation.messages[3].createdAt`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationIssue {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub valid: bool,
+    /// Schema version recorded in the file, if any
+    pub version: Option<String>,
+    /// The file is an older schema that will be upgraded when next opened
+    pub needs_migration: bool,
+    pub issues: Vec<ValidationIssue>,
+}
+
+const SIDECAR_REQUIRED_FIELDS: &[&str] = &[
+    "version",
+    "documentId",
+    "createdAt",
+    "stage",
+    "concept",
+    "outline",
+    "conversation",
+    "editingHistory",
+    "settings",
+    "meta",
+];
+
+const STAGE_NAMES: &[&str] = &["idea", "concept", "outline", "draft", "edits", "polish"];
+
+/// Collects issues while walking a sidecar's JSON
+struct SidecarChecker {
+    issues: Vec<ValidationIssue>,
+}
+
+impl SidecarChecker {
+    fn issue(&mut self, path: &str, message: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn timestamp(&mut self, value: &serde_json::Value, path: &str) {
+        match value.as_str() {
+            None if value.is_null() => self.issue(path, "Missing timestamp"),
+            None => self.issue(path, "Timestamp must be a string"),
+            Some(s) if chrono::DateTime::parse_from_rfc3339(s).is_err() => {
+                self.issue(path, format!("Malformed timestamp {:?}", s))
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn stage(&mut self, value: &serde_json::Value, path: &str) {
+        match value.as_str() {
+            Some(s) if STAGE_NAMES.contains(&s) => {}
+            Some(s) => self.issue(path, format!("Unknown stage {:?}", s)),
+            None => self.issue(path, "Stage must be a string"),
+        }
+    }
+
+    /// Check a timestamp field on every element of an array
+    fn each_timestamp(&mut self, value: &serde_json::Value, path: &str, field: &str) {
+        if let Some(items) = value.as_array() {
+            for (i, item) in items.iter().enumerate() {
+                self.timestamp(&item[field], &format!("{}[{}].{}", path, i, field));
+            }
+        }
+    }
+
+    fn check(&mut self, value: &serde_json::Value) {
+        let Some(root) = value.as_object() else {
+            self.issue("", "Sidecar must be a JSON object");
+            return;
+        };
+
+        for field in SIDECAR_REQUIRED_FIELDS {
+            if !root.contains_key(*field) {
+                self.issue(field, "Missing required field");
+            }
+        }
+
+        if root.contains_key("createdAt") {
+            self.timestamp(&value["createdAt"], "createdAt");
+        }
+        if root.contains_key("stage") {
+            self.stage(&value["stage"], "stage");
+        }
+        if let Some(history) = value["stageHistory"].as_array() {
+            for (i, transition) in history.iter().enumerate() {
+                self.stage(&transition["from"], &format!("stageHistory[{}].from", i));
+                self.stage(&transition["to"], &format!("stageHistory[{}].to", i));
+                self.timestamp(&transition["changedAt"], &format!("stageHistory[{}].changedAt", i));
+            }
+        }
+
+        let concept = &value["concept"];
+        if concept["current"].is_object() {
+            self.timestamp(&concept["current"]["updatedAt"], "concept.current.updatedAt");
+        }
+        self.each_timestamp(&concept["versions"], "concept.versions", "updatedAt");
+        self.each_timestamp(&value["outline"]["versions"], "outline.versions", "createdAt");
+        self.each_timestamp(
+            &value["conversation"]["messages"],
+            "conversation.messages",
+            "createdAt",
+        );
+        self.each_timestamp(&value["editingHistory"], "editingHistory", "createdAt");
+        self.each_timestamp(&value["snapshots"], "snapshots", "createdAt");
+
+        if value["meta"].is_object() {
+            self.timestamp(&value["meta"]["lastOpenedAt"], "meta.lastOpenedAt");
+        }
+    }
+}
+
+/// Check a document's sidecar against the current schema, reporting every
+/// problem found rather than stopping at the first. Never modifies the file.
+#[tauri::command]
+pub async fn validate_sidecar(md_path: String) -> Result<ValidationReport, FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    let content = read_sidecar_file(&sidecar_path).await?;
+
+    let mut checker = SidecarChecker { issues: Vec::new() };
+    let mut value: serde_json::Value = match serde_json::from_slice(&content) {
+        Ok(value) => value,
+        Err(e) => {
+            checker.issue("", format!("Not valid JSON: {}", e));
+            return Ok(ValidationReport {
+                valid: false,
+                version: None,
+                needs_migration: false,
+                issues: checker.issues,
+            });
+        }
+    };
+
+    if !value.is_object() {
+        checker.issue("", "Sidecar must be a JSON object");
+        return Ok(ValidationReport {
+            valid: false,
+            version: None,
+            needs_migration: false,
+            issues: checker.issues,
+        });
+    }
+
+    let version = value["version"].as_str().map(str::to_string);
+    // Validate against the current schema, as the app would see it after
+    // upgrading. This works on an in-memory copy only.
+    let needs_migration = match migrate_sidecar(&mut value) {
+        Ok(migrated) => migrated,
+        Err(unsupported) => {
+            checker.issue("version", format!("Unsupported sidecar version {}", unsupported));
+            false
+        }
+    };
+
+    checker.check(&value);
+
+    // Anything the targeted checks missed (wrong types, missing nested
+    // fields) surfaces here, located by serde_path_to_error
+    if checker.issues.is_empty() {
+        if let Err(e) = serde_path_to_error::deserialize::<_, Sidecar>(&value) {
+            checker.issue(&e.path().to_string(), e.inner().to_string());
+        }
+    }
+
+    Ok(ValidationReport {
+        valid: checker.issues.is_empty(),
+        version,
+        needs_migration,
+        issues: checker.issues,
+    })
+}
+
+// ============================================
+// Planning report
+// ============================================
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Markdown,
+    Plaintext,
+}
+
+/// Builds a report in either format, so the section logic is written once
+struct ReportWriter {
+    format: ReportFormat,
+    out: String,
+}
+
+impl ReportWriter {
+    fn heading(&mut self, level: usize, text: &str) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        match self.format {
+            ReportFormat::Markdown => {
+                self.out.push_str(&format!("{} {}\n\n", "#".repeat(level), text));
+            }
+            ReportFormat::Plaintext => {
+                let underline = if level == 1 { '=' } else { '-' };
+                let rule: String = std::iter::repeat_n(underline, text.chars().count()).collect();
+                self.out.push_str(&format!("{}\n{}\n\n", text, rule));
+            }
+        }
+    }
+
+    fn note(&mut self, text: &str) {
+        match self.format {
+            ReportFormat::Markdown => self.out.push_str(&format!("_{}_\n\n", text)),
+            ReportFormat::Plaintext => self.out.push_str(&format!("{}\n\n", text)),
+        }
+    }
+
+    /// A labelled value; skipped when the value is blank
+    fn field(&mut self, label: &str, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        match self.format {
+            ReportFormat::Markdown => self.out.push_str(&format!("**{}:** {}\n\n", label, value)),
+            ReportFormat::Plaintext => self.out.push_str(&format!("{}: {}\n\n", label, value)),
+        }
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        self.out.push_str(text.trim());
+        self.out.push_str("\n\n");
+    }
+
+    fn outline_item(&mut self, number: usize, prompt: &OutlinePrompt) {
+        let words = prompt
+            .estimated_words
+            .map(|w| format!(" (~{} words)", w))
+            .unwrap_or_default();
+        let title = match self.format {
+            ReportFormat::Markdown => format!("**{}**", prompt.title.trim()),
+            ReportFormat::Plaintext => prompt.title.trim().to_string(),
+        };
+        self.out.push_str(&format!("{}. {}{}\n", number, title, words));
+
+        let description = prompt.description.trim();
+        if !description.is_empty() {
+            for line in description.lines() {
+                self.out.push_str(&format!("   {}\n", line));
+            }
+        }
+        self.out.push('\n');
+    }
+
+    fn finish(mut self) -> String {
+        let trimmed = self.out.trim_end().len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+        self.out
+    }
+}
+
+/// Render a sidecar's concept, outline and conversation summary. Empty
+/// sections are left out.
+fn render_sidecar_report(sidecar: &Sidecar, document_name: &str, format: ReportFormat) -> String {
+    let mut report = ReportWriter {
+        format,
+        out: String::new(),
+    };
+
+    let concept = sidecar
+        .concept
+        .current
+        .as_ref()
+        .filter(|c| {
+            [&c.title, &c.core_argument, &c.audience, &c.tone]
+                .iter()
+                .any(|field| !field.trim().is_empty())
+        });
+    let title = concept
+        .map(|c| c.title.trim())
+        .filter(|t| !t.is_empty())
+        .unwrap_or(document_name);
+
+    report.heading(1, &format!("Planning report: {}", title));
+    report.note(&format!(
+        "Generated {} · Stage: {:?} · Started {}",
+        chrono::Utc::now().to_rfc3339(),
+        sidecar.stage,
+        sidecar.created_at
+    ));
+
+    let mut empty = true;
+
+    if let Some(concept) = concept {
+        empty = false;
+        report.heading(2, "Concept");
+        report.note(&format!("Updated {}", concept.updated_at));
+        report.field("Title", &concept.title);
+        report.field("Core argument", &concept.core_argument);
+        report.field("Audience", &concept.audience);
+        report.field("Tone", &concept.tone);
+    }
+
+    if let Some(prompts) = sidecar.outline.current.as_ref().filter(|p| !p.is_empty()) {
+        empty = false;
+        report.heading(2, "Outline");
+        if let Some(latest) = sidecar.outline.versions.last() {
+            report.note(&format!("Last revised {}", latest.created_at));
+        }
+        for (i, prompt) in prompts.iter().enumerate() {
+            report.outline_item(i + 1, prompt);
+        }
+    }
+
+    let conversation = &sidecar.conversation;
+    if !conversation.summary.trim().is_empty() {
+        empty = false;
+        report.heading(2, "Conversation summary");
+        if let Some(last) = conversation.messages.last() {
+            report.note(&format!(
+                "{} messages in the current conversation, latest {}",
+                conversation.messages.len(),
+                last.created_at
+            ));
+        }
+        report.paragraph(&conversation.summary);
+    }
+
+    if empty {
+        report.paragraph("No planning notes yet.");
+    }
+
+    report.finish()
+}
+
+/// Write the document's planning artifacts (concept, outline, conversation
+/// summary) to `output_path` as markdown or plain text
+#[tauri::command]
+pub async fn export_sidecar_report(
+    md_path: String,
+    output_path: String,
+    format: ReportFormat,
+) -> Result<(), FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    if !sidecar_exists(&sidecar_path) {
+        return Err(FileError::InvalidPath("Document has no planning data yet".to_string()));
+    }
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (sidecar, _) = parse_sidecar(&content)?;
+
+    let document_name = Path::new(&md_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let report = render_sidecar_report(&sidecar, &document_name, format);
+
+    write_atomic(Path::new(&output_path), report.as_bytes()).await
+}
+
+// ============================================
+// Conversation transcript
+// ============================================
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Markdown,
+    Html,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A markdown code fence longer than any backtick run in `text`
+fn code_fence(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Show a message timestamp in local time, or as stored if it doesn't parse
+fn transcript_time(created_at: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(created_at)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| created_at.to_string())
+}
+
+/// Builds a transcript in either format, so the message walk is written once
+struct TranscriptWriter {
+    format: TranscriptFormat,
+    out: String,
+}
+
+impl TranscriptWriter {
+    fn header(&mut self, title: &str, message_count: usize) {
+        let note = format!(
+            "Exported {} · {} messages",
+            chrono::Local::now().format("%Y-%m-%d %H:%M"),
+            message_count
+        );
+        match self.format {
+            TranscriptFormat::Markdown => {
+                self.out.push_str(&format!("# Conversation: {}\n\n_{}_\n\n", title, note));
+            }
+            TranscriptFormat::Html => {
+                let title = escape_html(title);
+                self.out.push_str(&format!(
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                     <title>Conversation: {title}</title>\n<style>\n\
+                     body {{ font-family: -apple-system, sans-serif; max-width: 720px; margin: 2em auto; line-height: 1.5; }}\n\
+                     .message {{ margin-bottom: 1.5em; }}\n\
+                     .text {{ white-space: pre-wrap; }}\n\
+                     time, .note {{ color: #666; font-size: 0.9em; }}\n\
+                     pre {{ background: #f5f5f5; padding: 0.75em; overflow-x: auto; }}\n\
+                     </style>\n</head>\n<body>\n<h1>Conversation: {title}</h1>\n\
+                     <p class=\"note\">{}</p>\n",
+                    escape_html(&note)
+                ));
+            }
+        }
+    }
+
+    fn begin_message(&mut self, role: &str, created_at: &str) {
+        let speaker = if role == "user" { "You" } else { "Assistant" };
+        let time = transcript_time(created_at);
+        match self.format {
+            TranscriptFormat::Markdown => {
+                self.out.push_str(&format!("## {} · {}\n\n", speaker, time));
+            }
+            TranscriptFormat::Html => {
+                self.out.push_str(&format!(
+                    "<section class=\"message {}\">\n<h2>{} <time datetime=\"{}\">{}</time></h2>\n",
+                    role,
+                    speaker,
+                    escape_html(created_at),
+                    escape_html(&time)
+                ));
+            }
+        }
+    }
+
+    fn end_message(&mut self) {
+        if let TranscriptFormat::Html = self.format {
+            self.out.push_str("</section>\n");
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        match self.format {
+            TranscriptFormat::Markdown => self.out.push_str(&format!("{}\n\n", text)),
+            TranscriptFormat::Html => {
+                self.out.push_str(&format!("<div class=\"text\">{}</div>\n", escape_html(text)));
+            }
+        }
+    }
+
+    /// Tool calls are collapsed so the prose stays readable
+    fn tool_use(&mut self, name: &str, input: &serde_json::Value) {
+        let input = serde_json::to_string_pretty(input).unwrap_or_default();
+        match self.format {
+            TranscriptFormat::Markdown => {
+                let fence = code_fence(&input);
+                self.out.push_str(&format!(
+                    "<details>\n<summary>Tool call: {}</summary>\n\n{}json\n{}\n{}\n\n</details>\n\n",
+                    name, fence, input, fence
+                ));
+            }
+            TranscriptFormat::Html => {
+                self.out.push_str(&format!(
+                    "<details>\n<summary>Tool call: {}</summary>\n<pre><code>{}</code></pre>\n</details>\n",
+                    escape_html(name),
+                    escape_html(&input)
+                ));
+            }
+        }
+    }
+
+    fn tool_result(&mut self, content: &str, is_error: bool) {
+        let label = if is_error { "Tool error" } else { "Tool result" };
+        match self.format {
+            TranscriptFormat::Markdown => {
+                let fence = code_fence(content);
+                self.out.push_str(&format!(
+                    "**{}:**\n\n{}\n{}\n{}\n\n",
+                    label,
+                    fence,
+                    content.trim_end(),
+                    fence
+                ));
+            }
+            TranscriptFormat::Html => {
+                self.out.push_str(&format!(
+                    "<p><strong>{}:</strong></p>\n<pre>{}</pre>\n",
+                    label,
+                    escape_html(content.trim_end())
+                ));
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        match self.format {
+            TranscriptFormat::Markdown => {
+                let trimmed = self.out.trim_end().len();
+                self.out.truncate(trimmed);
+                self.out.push('\n');
+            }
+            TranscriptFormat::Html => self.out.push_str("</body>\n</html>\n"),
+        }
+        self.out
+    }
+}
+
+/// Render the user and assistant messages of a conversation. Messages with
+/// any other role (system or internal notes) are skipped.
+fn render_transcript(messages: &[ChatMessage], title: &str, format: TranscriptFormat) -> String {
+    let messages: Vec<&ChatMessage> = messages
+        .iter()
+        .filter(|m| matches!(m.role.as_str(), "user" | "assistant"))
+        .collect();
+
+    let mut transcript = TranscriptWriter {
+        format,
+        out: String::new(),
+    };
+    transcript.header(title, messages.len());
+
+    for message in messages {
+        transcript.begin_message(&message.role, &message.created_at);
+        match &message.content {
+            MessageContent::Text(text) => transcript.text(text),
+            MessageContent::Blocks(blocks) => {
+                for block in blocks {
+                    match block {
+                        ContentBlock::Text { text } => transcript.text(text),
+                        ContentBlock::ToolUse { name, input, .. } => {
+                            transcript.tool_use(name, input)
+                        }
+                        ContentBlock::ToolResult {
+                            content, is_error, ..
+                        } => transcript.tool_result(content, is_error.unwrap_or(false)),
+                        ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {}
+                    }
+                }
+            }
+        }
+        transcript.end_message();
+    }
+
+    transcript.finish()
+}
+
+/// Write a document's conversation to `output_path` as a shareable markdown
+/// or HTML transcript, with a timestamp on each message
+#[tauri::command]
+pub async fn export_conversation(
+    md_path: String,
+    output_path: String,
+    format: TranscriptFormat,
+) -> Result<(), FileError> {
+    let sidecar_path = get_sidecar_path(&md_path)?;
+    if !sidecar_exists(&sidecar_path) {
+        return Err(FileError::InvalidPath("Document has no conversation yet".to_string()));
+    }
+    let content = read_sidecar_file(&sidecar_path).await?;
+    let (sidecar, _) = parse_sidecar(&content)?;
+
+    let title = sidecar
+        .concept
+        .current
+        .as_ref()
+        .map(|c| c.title.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .or_else(|| {
+            Path::new(&md_path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+        })
+        .unwrap_or_default();
+    let transcript = render_transcript(&sidecar.conversation.messages, &title, format);
+
+    write_atomic(Path::new(&output_path), transcript.as_bytes()).await
+}
+
+// ============================================
+// Recent documents
+// ============================================
+
+const MAX_RECENT_DOCUMENTS: usize = 10;
+
+/// Serializes read-modify-write cycles on the recents file
+static RECENT_DOCUMENTS_LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
+
+/// A recently opened document, newest first in the persisted list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentEntry {
+    pub path: String,
+    pub name: String,
+    pub opened_at: String,
+}
+
+fn recent_documents_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("com.writecraft.app").join("recent_documents.json"))
+}
+
+fn read_recent_file() -> Vec<RecentEntry> {
+    recent_documents_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn write_recent_file(entries: &[RecentEntry]) -> Result<(), FileError> {
+    let path = recent_documents_path().ok_or(FileError::NoHomeDirectory)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(entries)?)?;
+    Ok(())
+}
+
+/// Load the recents list, dropping entries whose files no longer exist
+pub(crate) fn load_recent_documents() -> Vec<RecentEntry> {
+    let _guard = RECENT_DOCUMENTS_LOCK.lock().unwrap();
+    let mut entries = read_recent_file();
+    let count = entries.len();
+    entries.retain(|entry| Path::new(&entry.path).exists());
+
+    if entries.len() != count {
+        if let Err(e) = write_recent_file(&entries) {
+            tracing::warn!("Failed to prune recent documents: {}", e);
+        }
+    }
+    entries
+}
+
+/// Move a document to the top of the recents list and refresh the menu.
+/// Failures are logged rather than surfaced; recents are best-effort.
+fn record_recent_document(app: &AppHandle, path: &str) {
+    let path = std::fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string());
+    let name = Path::new(&path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.clone());
+
+    {
+        let _guard = RECENT_DOCUMENTS_LOCK.lock().unwrap();
+        let mut entries = read_recent_file();
+        entries.retain(|entry| entry.path != path);
+        entries.insert(
+            0,
+            RecentEntry {
+                path,
+                name,
+                opened_at: chrono::Utc::now().to_rfc3339(),
+            },
+        );
+        entries.truncate(
+            load_app_settings()
+                .max_recent_documents
+                .unwrap_or(MAX_RECENT_DOCUMENTS),
+        );
+
+        if let Err(e) = write_recent_file(&entries) {
+            tracing::warn!("Failed to save recent documents: {}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = crate::rebuild_recent_menu(app) {
+        tracing::warn!("Failed to rebuild Open Recent menu: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn get_recent_documents() -> Result<Vec<RecentEntry>, FileError> {
+    Ok(load_recent_documents())
+}
+
+#[tauri::command]
+pub fn clear_recent_documents(app: AppHandle) -> Result<(), FileError> {
+    {
+        let _guard = RECENT_DOCUMENTS_LOCK.lock().unwrap();
+        write_recent_file(&[])?;
+    }
+
+    if let Err(e) = crate::rebuild_recent_menu(&app) {
+        tracing::warn!("Failed to rebuild Open Recent menu: {}", e);
+    }
+    Ok(())
+}
+
+// ============================================
+// App settings
+// ============================================
+
+/// App-wide preferences that can be exported and imported between machines.
+/// API keys and sessions live in the keychain and are never part of this.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub default_model: Option<String>,
+    pub log_level: Option<String>,
+    /// Word goal suggested for new documents
+    pub default_word_goal: Option<u32>,
+    pub max_recent_documents: Option<usize>,
+    pub max_concurrent_requests: Option<usize>,
+    pub idle_timeout_minutes: Option<u32>,
+}
+
+impl AppSettings {
+    fn validate(&self) -> Result<(), FileError> {
+        if let Some(level) = &self.log_level {
+            parse_log_level(level).map_err(|e| FileError::InvalidSettings(e.to_string()))?;
+        }
+        if self
+            .default_model
+            .as_deref()
+            .is_some_and(|m| m.trim().is_empty())
+        {
+            return Err(FileError::InvalidSettings(
+                "Default model is empty".to_string(),
+            ));
+        }
+        if self.max_recent_documents == Some(0) {
+            return Err(FileError::InvalidSettings(
+                "Recent documents limit must be at least 1".to_string(),
+            ));
+        }
+        if self.max_concurrent_requests == Some(0) {
+            return Err(FileError::InvalidSettings(
+                "Concurrent request limit must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn app_settings_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("com.writecraft.app").join("settings.json"))
+}
+
+pub(crate) fn load_app_settings() -> AppSettings {
+    app_settings_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Serializes read-modify-write cycles on `settings.json`
+static APP_SETTINGS_LOCK: LazyLock<tokio::sync::Mutex<()>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(()));
+
+/// Change saved settings in place, so a setter's value survives a restart
+/// and is what `export_settings` reports
+pub(crate) async fn update_app_settings(
+    update: impl FnOnce(&mut AppSettings),
+) -> Result<(), FileError> {
+    let path = app_settings_path().ok_or(FileError::NoHomeDirectory)?;
+    let _guard = APP_SETTINGS_LOCK.lock().await;
+
+    let mut settings = load_app_settings();
+    update(&mut settings);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    write_atomic(&path, &serde_json::to_vec_pretty(&settings)?).await
+}
+
+/// Push the settings that live in managed state into effect. Called at
+/// startup and after an import.
+pub(crate) fn apply_app_settings(app: &AppHandle, settings: &AppSettings) {
+    if let Some(level) = &settings.log_level {
+        if let Err(e) = app.state::<LogState>().set_level(level) {
+            tracing::warn!("Ignoring saved log level: {}", e);
+        }
+    }
+    if let Some(limit) = settings.max_concurrent_requests {
+        app.state::<RequestQueue>().set_limit(limit.max(1));
+    }
+    if settings.idle_timeout_minutes.is_some() {
+        app.state::<IdleLock>()
+            .set_timeout_minutes(settings.idle_timeout_minutes);
+    }
+}
+
+/// Serialize app settings to JSON for moving to another machine. The log
+/// level is the one in effect, which may differ from the saved one if saving
+/// it failed.
+#[tauri::command]
+pub fn export_settings(log: State<'_, LogState>) -> Result<String, FileError> {
+    let settings = AppSettings {
+        log_level: Some(log.level()),
+        ..load_app_settings()
+    };
+    Ok(serde_json::to_string_pretty(&settings)?)
+}
+
+/// Validate and apply settings produced by `export_settings`, then save them
+/// so they survive a restart. Unknown fields, including any secrets pasted
+/// into the file, are ignored.
+#[tauri::command]
+pub async fn import_settings(app: AppHandle, json: String) -> Result<AppSettings, FileError> {
+    let settings: AppSettings = serde_json::from_str(&json)?;
+    settings.validate()?;
+
+    update_app_settings(|saved| *saved = settings.clone()).await?;
+
+    apply_app_settings(&app, &settings);
+    Ok(settings)
+}
+
+/// Saved app settings. New sidecars take `default_word_goal`, and requests
+/// that don't name a model use `default_model`.
+#[tauri::command]
+pub fn get_app_settings() -> AppSettings {
+    load_app_settings()
+}
+
+// ============================================
+// Local usage ledger
+// ============================================
+
+/// Serializes read-modify-write cycles on the usage ledger
+static USAGE_LEDGER_LOCK: LazyLock<tokio::sync::Mutex<()>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(()));
+
+/// Tokens used by one successful request made with the user's own API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageRecord {
+    timestamp: String,
+    model: String,
+    input_tokens: u32,
+    output_tokens: u32,
+    #[serde(default)]
+    cache_creation_input_tokens: u32,
+    #[serde(default)]
+    cache_read_input_tokens: u32,
+}
+
+/// Token totals for one day or month, keyed `YYYY-MM-DD` or `YYYY-MM` in
+/// local time
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageBucket {
+    pub period: String,
+    pub requests: u32,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+impl UsageBucket {
+    fn add(&mut self, record: &UsageRecord) {
+        self.requests += 1;
+        self.input_tokens += u64::from(record.input_tokens);
+        self.output_tokens += u64::from(record.output_tokens);
+        self.cache_creation_input_tokens += u64::from(record.cache_creation_input_tokens);
+        self.cache_read_input_tokens += u64::from(record.cache_read_input_tokens);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageSummary {
+    /// Oldest first
+    pub buckets: Vec<UsageBucket>,
+    pub total: UsageBucket,
+}
+
+fn usage_ledger_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|p| p.join("com.writecraft.app").join("usage.json"))
+}
+
+/// The ledger's records; empty if it doesn't exist yet. A ledger that can't
+/// be parsed is an error rather than an empty one, so it isn't overwritten.
+async fn read_usage_ledger(path: &Path) -> Result<Vec<UsageRecord>, FileError> {
+    match tokio::fs::read(path).await {
+        Ok(content) => Ok(serde_json::from_slice(&content)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Append a request's token counts to the ledger. Failures are logged; a
+/// missed entry shouldn't fail the chat request that produced it.
+pub(crate) async fn record_usage(model: &str, usage: &Usage) {
+    let Some(path) = usage_ledger_path() else {
+        return;
+    };
+
+    let _guard = USAGE_LEDGER_LOCK.lock().await;
+    let mut records = match read_usage_ledger(&path).await {
+        Ok(records) => records,
+        Err(e) => {
+            tracing::warn!("Not recording usage, the ledger can't be read: {}", e);
+            return;
+        }
+    };
+    records.push(UsageRecord {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        model: model.to_string(),
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+        cache_creation_input_tokens: usage.cache_creation_input_tokens,
+        cache_read_input_tokens: usage.cache_read_input_tokens,
+    });
+
+    let result = async {
+        let json = serde_json::to_vec(&records)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        write_atomic(&path, &json).await
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record usage: {}", e);
+    }
+}
+
+/// Token usage from requests made with the user's own API key, grouped by
+/// `period` ("day" or "month")
+#[tauri::command]
+pub async fn get_local_usage(period: String) -> Result<UsageSummary, FileError> {
+    let format = match period.as_str() {
+        "day" => "%Y-%m-%d",
+        "month" => "%Y-%m",
+        _ => return Err(FileError::InvalidUsagePeriod(period)),
+    };
+
+    let records = match usage_ledger_path() {
+        Some(path) => {
+            let _guard = USAGE_LEDGER_LOCK.lock().await;
+            read_usage_ledger(&path).await?
+        }
+        None => Vec::new(),
+    };
+
+    let mut buckets: std::collections::BTreeMap<String, UsageBucket> = Default::default();
+    let mut total = UsageBucket {
+        period: "total".to_string(),
+        ..Default::default()
+    };
+    for record in &records {
+        let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(&record.timestamp) else {
+            continue;
+        };
+        let key = timestamp.with_timezone(&chrono::Local).format(format).to_string();
+        buckets
+            .entry(key.clone())
+            .or_insert_with(|| UsageBucket {
+                period: key,
+                ..Default::default()
+            })
+            .add(record);
+        total.add(record);
+    }
+
+    Ok(UsageSummary {
+        buckets: buckets.into_values().collect(),
+        total,
+    })
+}
+
+// ============================================
+// Document stats
+// ============================================
+
+/// Average adult silent reading speed, used for the reading time estimate
+const READING_WORDS_PER_MINUTE: usize = 238;
+
+/// Counts for a document's visible text, with markdown syntax stripped
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentStats {
+    pub words: usize,
+    pub characters: usize,
+    pub characters_no_spaces: usize,
+    pub paragraphs: usize,
+    pub reading_time_minutes: usize,
+    /// The document's word goal, if one is set
+    pub goal: Option<u32>,
+    /// Words written as a percentage of `goal`; can pass 100
+    pub percent_complete: Option<u32>,
+}
+
+/// Compute stats over the text a reader sees: headings, paragraphs, list
+/// items, quotes and code, without markup or link URLs.
+pub(crate) fn compute_document_stats(markdown: &str) -> DocumentStats {
+    let mut stats = DocumentStats {
+        words: 0,
+        characters: 0,
+        characters_no_spaces: 0,
+        paragraphs: 0,
+        reading_time_minutes: 0,
+        goal: None,
+        percent_complete: None,
+    };
+
+    for block in parse_blocks(markdown) {
+        let text = match &block {
+            Block::Heading { spans, .. }
+            | Block::Paragraph { spans }
+            | Block::ListItem { spans, .. }
+            | Block::Quote { spans } => spans.iter().map(|s| s.text.as_str()).collect(),
+            Block::Code { text } => text.clone(),
+            Block::Rule => continue,
+        };
+
+        if !matches!(block, Block::Heading { .. }) {
+            stats.paragraphs += 1;
+        }
+        stats.words += text.split_whitespace().count();
+        stats.characters += text.trim().chars().count();
+        stats.characters_no_spaces += text.chars().filter(|c| !c.is_whitespace()).count();
+    }
+
+    stats.reading_time_minutes = stats.words.div_ceil(READING_WORDS_PER_MINUTE);
+    stats
+}
+
+/// Word, character and paragraph counts for a document, with progress
+/// toward its word goal when one is set
+#[tauri::command]
+pub async fn document_stats(path: String) -> Result<DocumentStats, FileError> {
+    let content = tokio::fs::read_to_string(&path).await?;
+    let mut stats = compute_document_stats(&content);
+
+    let goal = load_existing_sidecar(Path::new(&path))
+        .await
+        .and_then(|sidecar| sidecar.settings.word_goal)
+        .filter(|goal| *goal > 0);
+    if let Some(goal) = goal {
+        stats.goal = Some(goal);
+        stats.percent_complete = Some((stats.words as u64 * 100 / u64::from(goal)) as u32);
+    }
+
+    Ok(stats)
+}
+
+// ============================================
+// Readability
+// ============================================
+
+/// Sentence and Flesch scores for a document's prose
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadabilityStats {
+    pub sentences: usize,
+    pub words: usize,
+    pub syllables: usize,
+    /// Words per sentence
+    pub average_sentence_length: f64,
+    /// 0-100, higher is easier to read
+    pub flesch_reading_ease: f64,
+    /// Approximate US school grade needed to follow the text
+    pub flesch_kincaid_grade: f64,
+}
+
+/// Estimate syllables by counting vowel groups, discounting a silent final
+/// "e" (but not "-le" as in "table"). Every word has at least one.
+fn count_syllables(word: &str) -> usize {
+    let letters: Vec<char> = word
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+
+    let mut count = 0;
+    let mut prev_vowel = false;
+    for &c in &letters {
+        let vowel = is_vowel(c);
+        if vowel && !prev_vowel {
+            count += 1;
+        }
+        prev_vowel = vowel;
+    }
+
+    match letters.as_slice() {
+        [.., before, 'l', 'e'] if !is_vowel(*before) => {}
+        [_, .., before, 'e'] if !is_vowel(*before) => count -= 1,
+        _ => {}
+    }
+    count.max(1)
+}
+
+/// Whether a word ends a sentence, looking past closing quotes and brackets
+fn ends_sentence(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', ')', ']', '\u{201d}', '\u{2019}'])
+        .ends_with(['.', '!', '?', '\u{2026}'])
+}
+
+fn round_tenth(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+/// Compute readability over the document's prose. Code blocks and inline
+/// code are skipped; a heading or list item without closing punctuation still
+/// counts as one sentence.
+pub(crate) fn compute_readability(markdown: &str) -> ReadabilityStats {
+    let mut sentences = 0;
+    let mut words = 0;
+    let mut syllables = 0;
+
+    for block in parse_blocks(markdown) {
+        let spans = match &block {
+            Block::Heading { spans, .. }
+            | Block::Paragraph { spans }
+            | Block::ListItem { spans, .. }
+            | Block::Quote { spans } => spans,
+            Block::Code { .. } | Block::Rule => continue,
+        };
+        let text: String = spans
+            .iter()
+            .filter(|s| !s.code)
+            .map(|s| s.text.as_str())
+            .collect();
+
+        let mut pending = 0;
+        for word in text.split_whitespace() {
+            if !word.chars().any(char::is_alphanumeric) {
+                continue;
+            }
+            words += 1;
+            pending += 1;
+            syllables += count_syllables(word);
+            if ends_sentence(word) {
+                sentences += 1;
+                pending = 0;
+            }
+        }
+        if pending > 0 {
+            sentences += 1;
+        }
+    }
+
+    if words == 0 {
+        return ReadabilityStats {
+            sentences: 0,
+            words: 0,
+            syllables: 0,
+            average_sentence_length: 0.0,
+            flesch_reading_ease: 0.0,
+            flesch_kincaid_grade: 0.0,
+        };
+    }
+
+    let words_per_sentence = words as f64 / sentences as f64;
+    let syllables_per_word = syllables as f64 / words as f64;
+    ReadabilityStats {
+        sentences,
+        words,
+        syllables,
+        average_sentence_length: round_tenth(words_per_sentence),
+        flesch_reading_ease: round_tenth(
+            206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word,
+        ),
+        flesch_kincaid_grade: round_tenth(
+            0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59,
+        ),
+    }
+}
+
+/// Flesch Reading Ease and Flesch-Kincaid grade for a document.
+/// Empty documents get zeroed stats.
+#[tauri::command]
+pub async fn readability_stats(path: String) -> Result<ReadabilityStats, FileError> {
+    let content = tokio::fs::read_to_string(&path).await?;
+    Ok(compute_readability(&content))
+}
+
+// ============================================
+// Search
+// ============================================
+
+/// Files larger than this are skipped when searching
+const SEARCH_MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// Default cap on body matches reported per file
+const SEARCH_DEFAULT_MAX_HITS_PER_FILE: usize = 20;
+/// Characters of context kept on each side of a match in a snippet
+const SEARCH_SNIPPET_CONTEXT: usize = 60;
+
+/// Options for `search_documents`. Every field is optional from the frontend.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchOptions {
+    /// Only match the query as a whole word
+    pub whole_word: bool,
+    /// Treat the query as a regular expression
+    pub regex: bool,
+    /// Descend into subfolders
+    pub recursive: bool,
+    /// Also search the sidecar's concept title
+    pub include_titles: bool,
+    /// Also search the sidecar's conversation summary
+    pub include_summaries: bool,
+    pub max_hits_per_file: Option<usize>,
+}
+
+/// Where in a document a search hit was found
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchHitSource {
+    Body,
+    Title,
+    Summary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub path: String,
+    pub source: SearchHitSource,
+    /// 1-based line number, only set for body hits
+    pub line_number: Option<usize>,
+    pub snippet: String,
+}
+
+/// Build a case-insensitive matcher for the query in the requested mode.
+fn build_search_pattern(query: &str, options: &SearchOptions) -> Result<regex::Regex, FileError> {
+    let pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    let pattern = if options.whole_word {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    };
+
+    regex::RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| FileError::InvalidQuery(e.to_string()))
+}
+
+/// Cut a snippet of text around a match, marking truncated ends with an ellipsis.
+fn search_snippet(text: &str, start: usize, end: usize) -> String {
+    let prefix: String = text[..start]
+        .chars()
+        .rev()
+        .take(SEARCH_SNIPPET_CONTEXT)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    let suffix: String = text[end..].chars().take(SEARCH_SNIPPET_CONTEXT).collect();
+
+    let mut snippet = String::new();
+    if prefix.len() < start {
+        snippet.push('…');
+    }
+    snippet.push_str(&prefix);
+    snippet.push_str(&text[start..end]);
+    snippet.push_str(&suffix);
+    if end + suffix.len() < text.len() {
+        snippet.push('…');
+    }
+    snippet.trim().to_string()
+}
+
+/// Read a file for searching, skipping oversized and binary files.
+async fn read_searchable(path: &Path) -> Option<String> {
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    if metadata.len() > SEARCH_MAX_FILE_BYTES {
+        return None;
+    }
+    let bytes = tokio::fs::read(path).await.ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Search the documents in a directory for a query. Matching is
+/// case-insensitive; body hits are capped per file.
+#[tauri::command]
+pub async fn search_documents(
+    dir: String,
+    query: String,
+    options: SearchOptions,
+) -> Result<Vec<SearchHit>, FileError> {
+    let dir_path = PathBuf::from(&dir);
+    if !dir_path.is_dir() {
+        return Err(FileError::InvalidPath("Not a directory".to_string()));
+    }
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let pattern = build_search_pattern(&query, &options)?;
+    let max_hits = options
+        .max_hits_per_file
+        .unwrap_or(SEARCH_DEFAULT_MAX_HITS_PER_FILE);
+
+    let mut files = collect_markdown_files(&dir_path, options.recursive).await?;
+    files.sort();
+
+    let mut hits = Vec::new();
+    for path in files {
+        let path_str = path.to_string_lossy().to_string();
+
+        if options.include_titles || options.include_summaries {
+            if let Some(sidecar) = load_existing_sidecar(&path).await {
+                let title = sidecar.concept.current.as_ref().map(|c| c.title.as_str());
+                let summary = Some(sidecar.conversation.summary.as_str());
+                let fields = [
+                    (options.include_titles, SearchHitSource::Title, title),
+                    (options.include_summaries, SearchHitSource::Summary, summary),
+                ];
+                for (enabled, source, text) in fields {
+                    let Some(text) = text.filter(|_| enabled) else {
+                        continue;
+                    };
+                    if let Some(m) = pattern.find(text) {
+                        hits.push(SearchHit {
+                            path: path_str.clone(),
+                            source,
+                            line_number: None,
+                            snippet: search_snippet(text, m.start(), m.end()),
+                        });
+                    }
+                }
+            }
+        }
+
+        let Some(content) = read_searchable(&path).await else {
+            continue;
+        };
+        let body_hits = content
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                pattern.find(line).map(|m| SearchHit {
+                    path: path_str.clone(),
+                    source: SearchHitSource::Body,
+                    line_number: Some(index + 1),
+                    snippet: search_snippet(line, m.start(), m.end()),
+                })
+            })
+            .take(max_hits);
+        hits.extend(body_hits);
+    }
+
+    Ok(hits)
+}
+
+// ============================================
+// Image attachments
+// ============================================
+
+/// Detect an image's media type from its leading bytes, limited to the
+/// formats Claude accepts.
+fn detect_image_media_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// Read an image file and encode it as a base64 source for a Claude image block.
+#[tauri::command]
+pub async fn encode_image_for_claude(path: String) -> Result<ImageSource, FileError> {
+    let bytes = tokio::fs::read(&path).await?;
+
+    let media_type = detect_image_media_type(&bytes).ok_or_else(|| {
+        let extension = Path::new(&path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("unknown");
+        FileError::UnsupportedImage(extension.to_string())
+    })?;
+
+    Ok(ImageSource::Base64 {
+        media_type: media_type.to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir() -> tempfile::TempDir {
+        tempfile::tempdir().expect("create temp dir")
+    }
+
+    #[tokio::test]
+    async fn copy_then_remove_moves_contents() {
+        let dir = scratch_dir();
+        let from = dir.path().join("draft.md.tmp");
+        let to = dir.path().join("draft.md");
+        std::fs::write(&from, "new text").unwrap();
+
+        copy_then_remove(&from, &to).await.unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "new text");
+    }
+
+    #[tokio::test]
+    async fn copy_then_remove_replaces_existing_file() {
+        let dir = scratch_dir();
+        let from = dir.path().join("draft.writing.json.tmp");
+        let to = dir.path().join("draft.writing.json");
+        std::fs::write(&from, "new").unwrap();
+        std::fs::write(&to, "old").unwrap();
+
+        copy_then_remove(&from, &to).await.unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "new");
+    }
+
+    #[tokio::test]
+    async fn copy_then_remove_keeps_source_when_copy_fails() {
+        let dir = scratch_dir();
+        let from = dir.path().join("draft.md");
+        let to = dir.path().join("missing").join("draft.md");
+        std::fs::write(&from, "only copy").unwrap();
+
+        assert!(copy_then_remove(&from, &to).await.is_err());
+
+        assert_eq!(std::fs::read_to_string(&from).unwrap(), "only copy");
+        assert!(!to.exists());
+    }
+
+    #[tokio::test]
+    async fn move_falls_back_to_copy_when_rename_crosses_devices() {
+        let dir = scratch_dir();
+        let from = dir.path().join("draft.md.tmp");
+        let to = dir.path().join("draft.md");
+        std::fs::write(&from, "moved text").unwrap();
+
+        let crossed = Err(std::io::Error::from(std::io::ErrorKind::CrossesDevices));
+        finish_move(&from, &to, crossed).await.unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "moved text");
+    }
+
+    #[tokio::test]
+    async fn move_returns_other_rename_errors_without_copying() {
+        let dir = scratch_dir();
+        let from = dir.path().join("draft.md.tmp");
+        let to = dir.path().join("draft.md");
+        std::fs::write(&from, "kept").unwrap();
+
+        let denied = Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert!(finish_move(&from, &to, denied).await.is_err());
+
+        assert_eq!(std::fs::read_to_string(&from).unwrap(), "kept");
+        assert!(!to.exists());
+    }
+
+    #[tokio::test]
+    async fn move_file_renames_within_a_filesystem() {
+        let dir = scratch_dir();
+        let from = dir.path().join("old.md");
+        let to = dir.path().join("new.md");
+        std::fs::write(&from, "text").unwrap();
+
+        move_file(&from, &to).await.unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "text");
+    }
+
+    #[test]
+    fn parse_sidecar_rejects_json_that_is_not_an_object() {
+        for content in ["[]", "42"] {
+            assert!(
+                matches!(parse_sidecar(content.as_bytes()), Err(FileError::Json(_))),
+                "{content} should be reported as corrupt"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn damaged_compressed_sidecar_reads_as_corrupt() {
+        let dir = scratch_dir();
+        let sidecar_path = dir.path().join("draft.writing.json");
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"{\"version\": \"1.1\"}").unwrap();
+        let mut gz = encoder.finish().unwrap();
+        gz.truncate(gz.len() / 2);
+        std::fs::write(compressed_sidecar_path(&sidecar_path), gz).unwrap();
+
+        assert!(matches!(
+            read_sidecar_file(&sidecar_path).await,
+            Err(FileError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn slugify_keeps_letters_from_any_script() {
+        assert_eq!(slugify_title("My First Draft!".into()), "my-first-draft");
+        assert_eq!(slugify_title("Café au Lait".into()), "café-au-lait");
+        assert_eq!(slugify_title("日本語のタイトル".into()), "日本語のタイトル");
+        assert_eq!(slugify_title("Ελληνικά: Δοκιμή".into()), "ελληνικά-δοκιμή");
+    }
+
+    #[test]
+    fn slugify_falls_back_to_untitled() {
+        assert_eq!(slugify_title("".into()), "untitled");
+        assert_eq!(slugify_title("?!... --- ***".into()), "untitled");
+    }
+
+    #[test]
+    fn slugify_avoids_reserved_names() {
+        assert_eq!(slugify_title("CON".into()), "con-doc");
+        assert_eq!(slugify_title("lpt1".into()), "lpt1-doc");
+        // The dot becomes a hyphen, which Windows no longer treats as reserved
+        assert_eq!(slugify_title("lpt1.txt".into()), "lpt1-txt");
+    }
+
+    #[test]
+    fn slugify_truncates_long_titles() {
+        let slug = slugify_title("a".repeat(MAX_TITLE_STEM_CHARS + 50));
+        assert_eq!(slug.chars().count(), MAX_TITLE_STEM_CHARS);
+
+        // A hyphen left at the cut is dropped
+        let title = format!("{} b", "a".repeat(MAX_TITLE_STEM_CHARS - 1));
+        assert_eq!(slugify_title(title), "a".repeat(MAX_TITLE_STEM_CHARS - 1));
+
+        // Counted in characters, not bytes
+        let slug = slugify_title("é".repeat(MAX_TITLE_STEM_CHARS + 50));
+        assert_eq!(slug, "é".repeat(MAX_TITLE_STEM_CHARS));
+    }
+
+    #[test]
+    fn title_stem_keeps_unicode_and_strips_separators() {
+        assert_eq!(title_file_stem("Café: a/b"), "Café a b");
+        assert_eq!(title_file_stem("日本語のタイトル"), "日本語のタイトル");
+        assert_eq!(title_file_stem("  ..Hidden draft.. "), "Hidden draft");
+    }
+
+    #[test]
+    fn title_stem_of_punctuation_is_empty() {
+        // `sync_filename_to_title` reports this as a missing title
+        assert_eq!(title_file_stem("?*:|"), "");
+        assert_eq!(title_file_stem("..."), "");
+    }
+
+    #[test]
+    fn title_stem_avoids_reserved_names() {
+        assert_eq!(title_file_stem("CON"), "CON doc");
+        assert_eq!(title_file_stem("con."), "con doc");
+        assert_eq!(title_file_stem("lpt1.txt"), "lpt1 doc.txt");
+        assert_eq!(title_file_stem("Console"), "Console");
+    }
+
+    #[test]
+    fn title_stem_truncates_long_titles() {
+        let stem = title_file_stem(&"é".repeat(MAX_TITLE_STEM_CHARS + 50));
+        assert_eq!(stem, "é".repeat(MAX_TITLE_STEM_CHARS));
+    }
+
+    #[test]
+    fn files_differing_only_in_case_are_not_the_same_file() {
+        let dir = scratch_dir();
+        let lower = dir.path().join("draft.md");
+        let upper = dir.path().join("Draft.md");
+        std::fs::write(&lower, "one").unwrap();
+
+        assert!(is_same_file(&lower, &lower));
+        assert!(!is_same_file(&lower, &dir.path().join("missing.md")));
+        // Where the filesystem is case-sensitive this is a second document
+        if !upper.exists() {
+            std::fs::write(&upper, "two").unwrap();
+            assert!(!is_same_file(&lower, &upper));
+        }
+    }
+
+    fn write_scratch(dir: &tempfile::TempDir, name: &str, content: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn read_document_range_moves_mid_character_ends_back() {
+        let dir = scratch_dir();
+        // Bytes: a | é é | 日 日 日 | b
+        let path = write_scratch(&dir, "draft.md", "aé日b");
+
+        let read = |start, length| read_document_range(path.clone(), start, length);
+        assert_eq!(read(0, 2).await.unwrap(), "a");
+        assert_eq!(read(2, 3).await.unwrap(), "é");
+        assert_eq!(read(4, 1).await.unwrap(), "");
+        assert_eq!(read(100, 5).await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn read_document_range_chunks_join_up() {
+        let dir = scratch_dir();
+        let content = "aé日b — ok";
+        let path = write_scratch(&dir, "draft.md", content);
+
+        let mut joined = String::new();
+        for start in (0..content.len()).step_by(2) {
+            joined.push_str(&read_document_range(path.clone(), start, 2).await.unwrap());
+        }
+        assert_eq!(joined, content);
+    }
+
+    #[tokio::test]
+    async fn read_document_range_of_empty_file_is_empty() {
+        let dir = scratch_dir();
+        let path = write_scratch(&dir, "empty.md", "");
+
+        assert_eq!(read_document_range(path, 0, 10).await.unwrap(), "");
+    }
+
+    #[test]
+    fn split_frontmatter_separates_yaml_from_body() {
+        let (frontmatter, body) = split_frontmatter("---\ntitle: Café\n---\nBody\n").unwrap();
+        assert_eq!(frontmatter["title"], "Café");
+        assert_eq!(body, "Body\n");
+    }
+
+    #[test]
+    fn split_frontmatter_accepts_bom_and_crlf() {
+        let (frontmatter, body) =
+            split_frontmatter("\u{feff}---\r\ntags: [a, b]\r\n---\r\nBody").unwrap();
+        assert_eq!(frontmatter["tags"], serde_json::json!(["a", "b"]));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn split_frontmatter_ignores_documents_without_a_mapping() {
+        assert!(split_frontmatter("").is_none());
+        assert!(split_frontmatter("Body only").is_none());
+        assert!(split_frontmatter("---\ntitle: x\nno closing marker").is_none());
+        assert!(split_frontmatter("---\n- a list\n---\nBody").is_none());
+    }
+
+    #[test]
+    fn document_stats_of_empty_document_are_zero() {
+        let stats = compute_document_stats("");
+        assert_eq!(stats.words, 0);
+        assert_eq!(stats.characters, 0);
+        assert_eq!(stats.paragraphs, 0);
+        assert_eq!(stats.reading_time_minutes, 0);
+    }
+
+    #[test]
+    fn document_stats_count_characters_not_bytes() {
+        let stats = compute_document_stats("# Café\n\nNaïve résumé here.\n\n- 日本語 text\n");
+        assert_eq!(stats.words, 6);
+        assert_eq!(stats.paragraphs, 2);
+        assert_eq!(stats.characters, 4 + 18 + 8);
+        assert_eq!(stats.characters_no_spaces, 4 + 16 + 7);
+        assert_eq!(stats.reading_time_minutes, 1);
+    }
+
+    #[test]
+    fn count_syllables_handles_silent_e_and_non_ascii() {
+        assert_eq!(count_syllables("cat"), 1);
+        assert_eq!(count_syllables("make"), 1);
+        assert_eq!(count_syllables("table"), 2);
+        assert_eq!(count_syllables("beautiful"), 3);
+        // Every word has at least one, even without ASCII vowels
+        assert_eq!(count_syllables("日本"), 1);
+        assert_eq!(count_syllables(""), 1);
+    }
+
+    #[test]
+    fn ends_sentence_looks_past_closing_quotes() {
+        assert!(ends_sentence("done."));
+        assert!(ends_sentence("really?\u{201d}"));
+        assert!(ends_sentence("wait\u{2026}"));
+        assert!(!ends_sentence("however,"));
+    }
+
+    #[test]
+    fn readability_of_empty_or_code_only_document_is_zero() {
+        for markdown in ["", "```\nlet x = 1;\n```\n"] {
+            let stats = compute_readability(markdown);
+            assert_eq!(stats.sentences, 0);
+            assert_eq!(stats.words, 0);
+            assert_eq!(stats.flesch_reading_ease, 0.0);
+        }
+    }
+
+    #[test]
+    fn readability_scores_simple_sentences() {
+        let stats = compute_readability("The cat sat. The dog ran!");
+        assert_eq!(stats.sentences, 2);
+        assert_eq!(stats.words, 6);
+        assert_eq!(stats.syllables, 6);
+        assert_eq!(stats.average_sentence_length, 3.0);
+        assert_eq!(stats.flesch_reading_ease, 119.2);
+        assert_eq!(stats.flesch_kincaid_grade, -2.6);
+    }
+
+    #[test]
+    fn readability_counts_unpunctuated_list_items_as_sentences() {
+        let stats = compute_readability("- first item\n- second\n");
+        assert_eq!(stats.sentences, 2);
+        assert_eq!(stats.words, 3);
+    }
+
+    #[test]
+    fn search_pattern_is_case_insensitive_and_escapes_queries() {
+        let options = SearchOptions::default();
+        let pattern = build_search_pattern("CAFÉ (draft)", &options).unwrap();
+        assert!(pattern.is_match("my café (draft) notes"));
+
+        let whole_word = SearchOptions {
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        let pattern = build_search_pattern("cat", &whole_word).unwrap();
+        assert!(pattern.is_match("the cat sat"));
+        assert!(!pattern.is_match("concatenate"));
+    }
+
+    #[test]
+    fn search_pattern_reports_invalid_regex() {
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        assert!(matches!(
+            build_search_pattern("(unclosed", &options),
+            Err(FileError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn search_snippet_cuts_on_character_boundaries() {
+        let text = format!("{}needle{}", "é".repeat(100), "日".repeat(100));
+        let start = text.find("needle").unwrap();
+        let snippet = search_snippet(&text, start, start + "needle".len());
+
+        let expected = format!(
+            "…{}needle{}…",
+            "é".repeat(SEARCH_SNIPPET_CONTEXT),
+            "日".repeat(SEARCH_SNIPPET_CONTEXT)
+        );
+        assert_eq!(snippet, expected);
+        assert_eq!(search_snippet("needle", 0, 6), "needle");
+    }
+
+    /// Backdate a file past `IN_FLIGHT_WRITE_GRACE`
+    fn backdate(path: &Path) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(std::time::SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn recover_pending_writes_promotes_and_cleans_up_temps() {
+        let dir = scratch_dir();
+        let sidecar = serde_json::to_vec(&Sidecar::new()).unwrap();
+
+        let orphan = dir.path().join("a.writing.json.tmp");
+        std::fs::write(&orphan, &sidecar).unwrap();
+        backdate(&orphan);
+        let corrupt = dir.path().join("b.writing.json.tmp");
+        std::fs::write(&corrupt, "{ not json").unwrap();
+        backdate(&corrupt);
+        let in_flight = dir.path().join("c.writing.json.tmp");
+        std::fs::write(&in_flight, &sidecar).unwrap();
+
+        let recovered = recover_pending_writes(dir.path().to_string_lossy().to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(recovered.len(), 2);
+        assert!(!orphan.exists());
+        assert_eq!(
+            std::fs::read(dir.path().join("a.writing.json")).unwrap(),
+            sidecar
+        );
+        assert!(!corrupt.exists());
+        assert!(!dir.path().join("b.writing.json").exists());
+        assert!(in_flight.exists());
+        for write in recovered {
+            let expected_corrupt = write.temp_path.ends_with("b.writing.json.tmp");
+            assert_eq!(
+                matches!(write.action, RecoveryAction::Corrupt),
+                expected_corrupt
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn recover_pending_writes_in_empty_dir_finds_nothing() {
+        let dir = scratch_dir();
+        let recovered = recover_pending_writes(dir.path().to_string_lossy().to_string())
+            .await
+            .unwrap();
+        assert!(recovered.is_empty());
+    }
+}
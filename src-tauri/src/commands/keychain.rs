@@ -1,9 +1,18 @@
 use keyring::Entry;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 use std::collections::HashMap;
+use tauri::State;
 
 const SERVICE_NAME: &str = "writecraft";
-const ACCOUNT_NAME: &str = "claude-api-key";
+/// Provider used when a command doesn't name one
+const DEFAULT_PROVIDER: &str = "anthropic";
+/// Keyring account of the original single (Anthropic) key, kept so
+/// existing keys are still found
+const ANTHROPIC_ACCOUNT_NAME: &str = "claude-api-key";
+/// Providers the app sends requests to, whose keys are removed with the account
+const KNOWN_PROVIDERS: &[&str] = &[DEFAULT_PROVIDER, "openai"];
 
 // Fallback in-memory storage when keychain fails
 static FALLBACK_STORAGE: std::sync::LazyLock<Mutex<HashMap<String, String>>> =
@@ -13,6 +22,24 @@ static FALLBACK_STORAGE: std::sync::LazyLock<Mutex<HashMap<String, String>>> =
 pub enum KeychainError {
     #[error("Keychain error: {0}")]
     Keyring(String),
+    #[error("Invalid provider: {0}")]
+    InvalidProvider(String),
+    #[error("Invalid API key")]
+    InvalidApiKey,
+    #[error("No internet connection")]
+    Offline,
+    #[error("Network error: {0}")]
+    Network(String),
+    #[error("API error ({0}): {1}")]
+    Api(u16, String),
+    #[error("No API key stored")]
+    NoKey,
+    #[error("Access to the API key was denied")]
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    RevealDenied,
+    #[error("Revealing the API key isn't supported on this platform")]
+    #[cfg_attr(target_os = "macos", allow(dead_code))]
+    RevealUnsupported,
 }
 
 impl serde::Serialize for KeychainError {
@@ -24,26 +51,68 @@ impl serde::Serialize for KeychainError {
     }
 }
 
-fn get_entry() -> Result<Entry, KeychainError> {
-    Entry::new(SERVICE_NAME, ACCOUNT_NAME)
+impl From<reqwest::Error> for KeychainError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_connect() {
+            KeychainError::Offline
+        } else {
+            KeychainError::Network(e.to_string())
+        }
+    }
+}
+
+/// Resolve the provider name, defaulting to Anthropic. Names are used in
+/// keyring accounts and env var names, so only simple identifiers are allowed.
+fn resolve_provider(provider: Option<String>) -> Result<String, KeychainError> {
+    let provider = provider
+        .map(|p| p.trim().to_lowercase())
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+
+    if !provider
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(KeychainError::InvalidProvider(provider));
+    }
+    Ok(provider)
+}
+
+fn account_name(provider: &str) -> String {
+    if provider == DEFAULT_PROVIDER {
+        ANTHROPIC_ACCOUNT_NAME.to_string()
+    } else {
+        format!("{}-api-key", provider)
+    }
+}
+
+fn get_entry(provider: &str) -> Result<Entry, KeychainError> {
+    Entry::new(SERVICE_NAME, &account_name(provider))
         .map_err(|e| KeychainError::Keyring(e.to_string()))
 }
 
-fn fallback_key() -> String {
-    format!("{}:{}", SERVICE_NAME, ACCOUNT_NAME)
+fn fallback_key(provider: &str) -> String {
+    format!("{}:{}", SERVICE_NAME, account_name(provider))
 }
 
-#[tauri::command]
-pub fn get_api_key() -> Result<Option<String>, KeychainError> {
-    // Check environment variable first (useful for development)
-    if let Ok(key) = std::env::var("ANTHROPIC_API_KEY") {
+/// Get the API key for a provider ("anthropic", "openai", ...).
+/// Omitting `provider` is the deprecated single-key form and means Anthropic.
+/// Backend use only: the frontend checks for a key with `has_api_key` and
+/// shows it with `reveal_api_key`.
+pub fn get_api_key(provider: Option<String>) -> Result<Option<String>, KeychainError> {
+    let provider = resolve_provider(provider)?;
+
+    // Check environment variable first (useful for development),
+    // e.g. ANTHROPIC_API_KEY or OPENAI_API_KEY
+    let env_var = format!("{}_API_KEY", provider.to_uppercase().replace('-', "_"));
+    if let Ok(key) = std::env::var(env_var) {
         if !key.is_empty() {
             return Ok(Some(key));
         }
     }
 
     // Try keychain next
-    if let Ok(entry) = get_entry() {
+    if let Ok(entry) = get_entry(&provider) {
         match entry.get_password() {
             Ok(password) => return Ok(Some(password)),
             Err(keyring::Error::NoEntry) => {}
@@ -55,18 +124,70 @@ pub fn get_api_key() -> Result<Option<String>, KeychainError> {
 
     // Fall back to in-memory storage
     let storage = FALLBACK_STORAGE.lock().unwrap();
-    Ok(storage.get(&fallback_key()).cloned())
+    Ok(storage.get(&fallback_key(&provider)).cloned())
+}
+
+/// Whether an API key is configured for a provider, without exposing it
+#[tauri::command]
+pub fn has_api_key(provider: Option<String>) -> Result<bool, KeychainError> {
+    Ok(get_api_key(provider)?.is_some())
 }
 
+/// Return the stored API key for display. On macOS the key is read through
+/// the `security` tool, which isn't on the item's access list, so the
+/// Keychain asks the user to authenticate before handing it over. Other
+/// platforms have no equivalent prompt and refuse.
 #[tauri::command]
-pub fn set_api_key(key: String) -> Result<(), KeychainError> {
+pub async fn reveal_api_key(provider: Option<String>) -> Result<String, KeychainError> {
+    let provider = resolve_provider(provider)?;
+    tracing::info!(provider = %provider, "API key reveal requested");
+
+    #[cfg(target_os = "macos")]
+    {
+        // Runs off the async runtime since it waits on the user
+        let account = account_name(&provider);
+        let output = tokio::task::spawn_blocking(move || {
+            std::process::Command::new("/usr/bin/security")
+                .args(["find-generic-password", "-w", "-s", SERVICE_NAME, "-a", account.as_str()])
+                .output()
+        })
+        .await
+        .map_err(|e| KeychainError::Keyring(e.to_string()))?
+        .map_err(|e| KeychainError::Keyring(e.to_string()))?;
+
+        // 44 is errSecItemNotFound; anything else means the prompt was
+        // cancelled or authentication failed
+        match output.status.code() {
+            Some(0) => {
+                tracing::info!(provider = %provider, "API key revealed");
+                Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+            }
+            Some(44) => Err(KeychainError::NoKey),
+            _ => {
+                tracing::info!(provider = %provider, "API key reveal denied");
+                Err(KeychainError::RevealDenied)
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        Err(KeychainError::RevealUnsupported)
+    }
+}
+
+/// Store the API key for a provider. Omitting `provider` means Anthropic.
+#[tauri::command]
+pub fn set_api_key(key: String, provider: Option<String>) -> Result<(), KeychainError> {
+    let provider = resolve_provider(provider)?;
+
     // Try keychain first
-    if let Ok(entry) = get_entry() {
+    if let Ok(entry) = get_entry(&provider) {
         match entry.set_password(&key) {
             Ok(()) => {
                 // Also store in fallback for this session
                 let mut storage = FALLBACK_STORAGE.lock().unwrap();
-                storage.insert(fallback_key(), key);
+                storage.insert(fallback_key(&provider), key);
                 return Ok(());
             }
             Err(e) => {
@@ -77,14 +198,17 @@ pub fn set_api_key(key: String) -> Result<(), KeychainError> {
 
     // Fall back to in-memory storage
     let mut storage = FALLBACK_STORAGE.lock().unwrap();
-    storage.insert(fallback_key(), key);
+    storage.insert(fallback_key(&provider), key);
     Ok(())
 }
 
+/// Delete the API key for a provider. Omitting `provider` means Anthropic.
 #[tauri::command]
-pub fn delete_api_key() -> Result<(), KeychainError> {
+pub fn delete_api_key(provider: Option<String>) -> Result<(), KeychainError> {
+    let provider = resolve_provider(provider)?;
+
     // Try to delete from keychain
-    if let Ok(entry) = get_entry() {
+    if let Ok(entry) = get_entry(&provider) {
         match entry.delete_credential() {
             Ok(()) => {}
             Err(keyring::Error::NoEntry) => {}
@@ -96,19 +220,81 @@ pub fn delete_api_key() -> Result<(), KeychainError> {
 
     // Also remove from fallback storage
     let mut storage = FALLBACK_STORAGE.lock().unwrap();
-    storage.remove(&fallback_key());
+    storage.remove(&fallback_key(&provider));
     Ok(())
 }
 
+/// Delete the API keys of every known provider, plus any other provider with
+/// a key stored this session. Failures are logged and the rest still deleted.
+pub(crate) fn delete_all_api_keys() {
+    let mut providers: Vec<String> = KNOWN_PROVIDERS.iter().map(|p| p.to_string()).collect();
+    let prefix = format!("{}:", SERVICE_NAME);
+    for key in FALLBACK_STORAGE.lock().unwrap().keys() {
+        // The Anthropic account name predates the `<provider>-api-key` scheme
+        let provider = key
+            .strip_prefix(&prefix)
+            .filter(|account| *account != ANTHROPIC_ACCOUNT_NAME)
+            .and_then(|account| account.strip_suffix("-api-key"));
+        if let Some(provider) = provider {
+            if !providers.iter().any(|p| p == provider) {
+                providers.push(provider.to_string());
+            }
+        }
+    }
+
+    for provider in providers {
+        if let Err(e) = delete_api_key(Some(provider.clone())) {
+            tracing::warn!(provider = %provider, "Failed to delete API key: {}", e);
+        }
+    }
+}
+
+/// Empty the in-memory copies of API keys and the auth session. The keychain
+/// and session file are left alone, and the user is not signed out on the
+/// server; a session persisted there is picked up again on next use.
 #[tauri::command]
-pub async fn test_api_key(key: String) -> Result<bool, KeychainError> {
-    // Test the API key by making a simple request to Claude API
-    let client = reqwest::Client::new();
+pub fn clear_credential_caches() -> Result<(), KeychainError> {
+    FALLBACK_STORAGE.lock().unwrap().clear();
+    super::auth::clear_auth_fallback_storage();
+    tracing::info!("In-memory credential caches cleared");
+    Ok(())
+}
+
+/// Check an API key against Claude. Omitting `key` tests the stored one.
+#[tauri::command]
+pub async fn test_api_key(
+    client: State<'_, Client>,
+    key: Option<String>,
+) -> Result<bool, KeychainError> {
+    let key = match key {
+        Some(key) => key,
+        None => get_api_key(None)?.ok_or(KeychainError::NoKey)?,
+    };
+    check_api_key(&client, &key).await
+}
 
+/// Validate a new Anthropic key and store it only if Claude accepts it, so a
+/// bad paste never overwrites a working key
+#[tauri::command]
+pub async fn replace_api_key(
+    client: State<'_, Client>,
+    new_key: String,
+) -> Result<(), KeychainError> {
+    let new_key = new_key.trim().to_string();
+    if new_key.is_empty() || !check_api_key(&client, &new_key).await? {
+        return Err(KeychainError::InvalidApiKey);
+    }
+    set_api_key(new_key, None)
+}
+
+/// Returns Ok(false) when Claude rejects the key, and an error for any other
+/// non-success response
+async fn check_api_key(client: &Client, key: &str) -> Result<bool, KeychainError> {
+    // Test the API key by making a simple request to Claude API
     let response = client
-        .post("https://api.anthropic.com/v1/messages")
-        .header("x-api-key", &key)
-        .header("anthropic-version", "2023-06-01")
+        .post(super::claude::claude_messages_url())
+        .header("x-api-key", key)
+        .header("anthropic-version", super::claude::anthropic_version())
         .header("content-type", "application/json")
         .json(&serde_json::json!({
             "model": "claude-haiku-4-5-20251001",
@@ -118,8 +304,7 @@ pub async fn test_api_key(key: String) -> Result<bool, KeychainError> {
             ]
         }))
         .send()
-        .await
-        .map_err(|e| KeychainError::Keyring(format!("Network error: {}", e)))?;
+        .await?;
 
     let status = response.status().as_u16();
 
@@ -140,7 +325,76 @@ pub async fn test_api_key(key: String) -> Result<bool, KeychainError> {
                 error_body
             };
 
-            Err(KeychainError::Keyring(format!("API error ({}): {}", status, error_msg)))
+            Err(KeychainError::Api(status, error_msg))
+        }
+    }
+}
+
+/// A model available to an API key
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelListResponse {
+    data: Vec<ModelListEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelListEntry {
+    id: String,
+    display_name: Option<String>,
+}
+
+/// Validate an API key and list the models it can use, newest first
+#[tauri::command]
+pub async fn list_models(
+    client: State<'_, Client>,
+    key: String,
+) -> Result<Vec<ModelInfo>, KeychainError> {
+    let response = client
+        .get(super::claude::claude_models_url())
+        .query(&[("limit", "1000")])
+        .header("x-api-key", &key)
+        .header("anthropic-version", super::claude::anthropic_version())
+        .send()
+        .await?;
+
+    let status = response.status().as_u16();
+
+    match status {
+        200 => {
+            let models: ModelListResponse = response
+                .json()
+                .await
+                .map_err(|e| KeychainError::Network(format!("Invalid response: {}", e)))?;
+
+            Ok(models
+                .data
+                .into_iter()
+                .map(|m| ModelInfo {
+                    display_name: m.display_name.unwrap_or_else(|| m.id.clone()),
+                    id: m.id,
+                })
+                .collect())
+        }
+        401 => Err(KeychainError::InvalidApiKey),
+        _ => {
+            let error_body = response.text().await.unwrap_or_default();
+
+            let error_msg = if let Ok(json) = serde_json::from_str::<serde_json::Value>(&error_body) {
+                json["error"]["message"]
+                    .as_str()
+                    .unwrap_or(&error_body)
+                    .to_string()
+            } else {
+                error_body
+            };
+
+            Err(KeychainError::Api(status, error_msg))
         }
     }
 }
@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+use thiserror::Error;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+/// Filter used when RUST_LOG isn't set
+const DEFAULT_LOG_FILTER: &str = "info";
+/// Log files are named writecraft.YYYY-MM-DD.log
+const LOG_FILE_PREFIX: &str = "writecraft";
+const LOG_FILE_SUFFIX: &str = "log";
+/// Daily files kept before the oldest is deleted
+const MAX_LOG_FILES: usize = 7;
+
+#[derive(Debug, Error)]
+pub enum LogError {
+    #[error("Invalid log level: {0}")]
+    InvalidLevel(String),
+    #[error("Failed to update log level: {0}")]
+    Reload(String),
+    #[error("File logging is unavailable")]
+    Unavailable,
+    #[error("Failed to save log level: {0}")]
+    Save(String),
+}
+
+impl serde::Serialize for LogError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Handle for changing the log filter at runtime. Also owns the file writer's
+/// guard, which flushes buffered lines when the app exits.
+pub struct LogState {
+    filter: reload::Handle<EnvFilter, Registry>,
+    /// The directive currently in effect
+    level: Mutex<String>,
+    log_dir: Option<PathBuf>,
+    _guard: Option<WorkerGuard>,
+}
+
+/// Same location Tauri uses for the app log directory
+fn log_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        dirs::home_dir().map(|p| p.join("Library").join("Logs").join("com.writecraft.app"))
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        dirs::data_local_dir().map(|p| p.join("com.writecraft.app").join("logs"))
+    }
+}
+
+fn file_appender(dir: &Path) -> Option<RollingFileAppender> {
+    std::fs::create_dir_all(dir).ok()?;
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)
+        .ok()
+}
+
+/// Install the global tracing subscriber: a reloadable filter over a rolling
+/// log file, plus stdout in debug builds. If the log directory can't be
+/// created, logging continues without the file sink.
+pub fn init_logging() -> LogState {
+    let level = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|directive| EnvFilter::try_new(directive).is_ok())
+        .unwrap_or_else(|| DEFAULT_LOG_FILTER.to_string());
+    let (filter_layer, filter) = reload::Layer::new(EnvFilter::new(&level));
+
+    let log_dir = log_dir();
+    let (file_layer, guard) = match log_dir.as_deref().and_then(file_appender) {
+        Some(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer().with_ansi(false).with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    let stdout_layer = cfg!(debug_assertions).then(fmt::layer);
+
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(file_layer)
+        .with(stdout_layer)
+        .init();
+
+    LogState {
+        filter,
+        level: Mutex::new(level),
+        log_dir: log_dir.filter(|_| guard.is_some()),
+        _guard: guard,
+    }
+}
+
+impl LogState {
+    /// The log directive currently in effect
+    pub(crate) fn level(&self) -> String {
+        self.level.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_level(&self, level: &str) -> Result<(), LogError> {
+        let level = level.trim();
+        let filter = parse_log_level(level)?;
+        self.filter
+            .reload(filter)
+            .map_err(|e| LogError::Reload(e.to_string()))?;
+        *self.level.lock().unwrap() = level.to_string();
+        tracing::info!("Log level set to {}", level);
+        Ok(())
+    }
+}
+
+/// Parse a level or RUST_LOG-style directive
+pub(crate) fn parse_log_level(level: &str) -> Result<EnvFilter, LogError> {
+    let level = level.trim();
+    if level.is_empty() {
+        return Err(LogError::InvalidLevel(level.to_string()));
+    }
+    EnvFilter::try_new(level).map_err(|_| LogError::InvalidLevel(level.to_string()))
+}
+
+/// Change the log filter at runtime. Accepts a level ("error", "warn",
+/// "info", "debug", "trace") or any RUST_LOG-style directive. The level is
+/// saved with the app settings.
+#[tauri::command]
+pub async fn set_log_level(state: State<'_, LogState>, level: String) -> Result<(), LogError> {
+    state.set_level(&level)?;
+    super::file::update_app_settings(|settings| settings.log_level = Some(level))
+        .await
+        .map_err(|e| LogError::Save(e.to_string()))
+}
+
+/// Path of the most recent log file, for revealing it in the file manager.
+/// Falls back to the log directory if nothing has been written yet.
+#[tauri::command]
+pub fn get_log_file_path(state: State<'_, LogState>) -> Result<String, LogError> {
+    let dir = state.log_dir.as_ref().ok_or(LogError::Unavailable)?;
+    let prefix = format!("{}.", LOG_FILE_PREFIX);
+    let suffix = format!(".{}", LOG_FILE_SUFFIX);
+
+    // Dates in the file names sort chronologically
+    let latest = std::fs::read_dir(dir)
+        .map_err(|_| LogError::Unavailable)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(&suffix))
+        })
+        .max();
+
+    Ok(latest
+        .unwrap_or_else(|| dir.clone())
+        .to_string_lossy()
+        .to_string())
+}
@@ -1,9 +1,15 @@
 pub mod auth;
 pub mod claude;
+pub mod export;
 pub mod file;
 pub mod keychain;
+pub mod logging;
+pub mod openai;
 
 pub use auth::*;
 pub use claude::*;
+pub use export::*;
 pub use file::*;
 pub use keychain::*;
+pub use logging::*;
+pub use openai::*;
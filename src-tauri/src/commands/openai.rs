@@ -0,0 +1,499 @@
+use super::claude::{
+    document_system_prompt, next_chunk, send_with_retry, stream_ended_early, stream_event,
+    validate_channel_id, CancelToken, ClaudeError, ContentBlock, GenerationState, ImageSource,
+    Message, MessageContent, ModelFallbackEvent, RequestQueue, StreamChunk, StreamError,
+    DEFAULT_MAX_RETRIES, DEFAULT_MAX_TOKENS,
+};
+use super::file::StreamingReply;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com";
+/// Overrides the OpenAI base URL, e.g. for another OpenAI-compatible server
+const OPENAI_BASE_URL_ENV: &str = "WRITECRAFT_OPENAI_BASE_URL";
+const DEFAULT_OPENAI_MODEL: &str = "gpt-4o";
+/// Keychain provider the API key is stored under
+const OPENAI_PROVIDER: &str = "openai";
+
+/// OpenAI-compatible base URL, honoring the `WRITECRAFT_OPENAI_BASE_URL` override
+fn openai_base_url() -> String {
+    let base = std::env::var(OPENAI_BASE_URL_ENV)
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string());
+    base.trim_end_matches('/').to_string()
+}
+
+fn openai_chat_url() -> String {
+    format!("{}/v1/chat/completions", openai_base_url())
+}
+
+// ============================================
+// API Request types
+// ============================================
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Serialize)]
+struct ImageUrl {
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ChatContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Serialize)]
+struct ToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    call_type: String,
+    function: FunctionCall,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionCall {
+    name: String,
+    /// JSON-encoded arguments, as OpenAI expects a string here
+    arguments: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatMessage {
+    role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<ChatContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    fn text(role: &str, text: String) -> Self {
+        ChatMessage {
+            role: role.to_string(),
+            content: Some(ChatContent::Text(text)),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    max_tokens: u32,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+}
+
+/// Translate WriteCraft messages into OpenAI chat messages. The system prompt
+/// becomes a leading system message, and tool results, which Anthropic nests
+/// in a user message, become separate `tool` messages.
+fn to_openai_messages(messages: Vec<Message>, system_prompt: Option<String>) -> Vec<ChatMessage> {
+    let mut out: Vec<ChatMessage> = system_prompt
+        .map(|text| ChatMessage::text("system", text))
+        .into_iter()
+        .collect();
+
+    for message in messages {
+        let blocks = match message.content {
+            MessageContent::Text(text) => {
+                out.push(ChatMessage::text(&message.role, text));
+                continue;
+            }
+            MessageContent::Blocks(blocks) => blocks,
+        };
+
+        let mut parts = Vec::new();
+        let mut tool_calls = Vec::new();
+        for block in blocks {
+            match block {
+                ContentBlock::Text { text } => parts.push(ContentPart::Text { text }),
+                ContentBlock::Image { source } => {
+                    let url = match source {
+                        ImageSource::Base64 { media_type, data } => {
+                            format!("data:{};base64,{}", media_type, data)
+                        }
+                        ImageSource::Url { url } => url,
+                    };
+                    parts.push(ContentPart::ImageUrl {
+                        image_url: ImageUrl { url },
+                    });
+                }
+                ContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                    id,
+                    call_type: "function".to_string(),
+                    function: FunctionCall {
+                        name,
+                        arguments: input.to_string(),
+                    },
+                }),
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    ..
+                } => out.push(ChatMessage {
+                    role: "tool".to_string(),
+                    content: Some(ChatContent::Text(content)),
+                    tool_calls: None,
+                    tool_call_id: Some(tool_use_id),
+                }),
+                // Claude's extended thinking means nothing to other providers
+                ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {}
+            }
+        }
+
+        if parts.is_empty() && tool_calls.is_empty() {
+            continue;
+        }
+        out.push(ChatMessage {
+            role: message.role,
+            content: (!parts.is_empty()).then_some(ChatContent::Parts(parts)),
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+            tool_call_id: None,
+        });
+    }
+
+    out
+}
+
+// ============================================
+// SSE parsing types
+// ============================================
+
+#[derive(Debug, Deserialize)]
+struct ChunkDelta {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    delta: Option<ChunkDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    error: Option<ApiErrorBody>,
+}
+
+// ============================================
+// Streaming
+// ============================================
+
+/// The OpenAI key from the keychain
+pub(crate) fn openai_api_key() -> Result<String, ClaudeError> {
+    super::keychain::get_api_key(Some(OPENAI_PROVIDER.to_string()))
+        .map_err(|e| ClaudeError::Api(e.to_string()))?
+        .ok_or(ClaudeError::NoApiKey)
+}
+
+/// Model used when a request doesn't name one
+pub(crate) fn default_openai_model() -> String {
+    DEFAULT_OPENAI_MODEL.to_string()
+}
+
+/// Reject sampling parameters outside the range the API accepts
+pub(crate) fn validate_openai_sampling(
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+) -> Result<(), ClaudeError> {
+    if let Some(t) = temperature {
+        if !(0.0..=2.0).contains(&t) {
+            return Err(ClaudeError::Api(format!(
+                "Temperature must be between 0.0 and 2.0 (got {})",
+                t
+            )));
+        }
+    }
+    if let Some(p) = top_p {
+        if !(0.0..=1.0).contains(&p) {
+            return Err(ClaudeError::Api(format!(
+                "top_p must be between 0.0 and 1.0 (got {})",
+                p
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// A chat request whose key, model and sampling the calling command has
+/// already resolved and validated
+pub(crate) struct ChatParams {
+    pub api_key: String,
+    pub model: String,
+    /// Tried in order when `model` is overloaded or unknown
+    pub fallback_models: Vec<String>,
+    pub messages: Vec<Message>,
+    pub system_prompt: Option<String>,
+    pub max_retries: Option<u32>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+}
+
+/// Stream a chat completion from an OpenAI-compatible endpoint, emitting the
+/// same 'claude-stream-chunk' / 'claude-stream-error' events as the Claude
+/// commands so the frontend doesn't care which provider answered. The caller
+/// holds the generation and request queue slot; `reply`, if given, saves the
+/// text to the document as it streams.
+pub(crate) async fn stream_chat(
+    app: &AppHandle,
+    client: &Client,
+    cancel: &CancelToken,
+    params: ChatParams,
+    mut reply: Option<StreamingReply>,
+    channel_id: Option<&str>,
+) -> Result<String, ClaudeError> {
+    let mut request_body = ChatRequest {
+        model: params.model,
+        messages: to_openai_messages(params.messages, params.system_prompt),
+        max_tokens: params.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        stream: true,
+        temperature: params.temperature,
+        top_p: params.top_p,
+    };
+
+    let mut fallback_models = params.fallback_models.into_iter();
+    let response = loop {
+        let request = client
+            .post(openai_chat_url())
+            .bearer_auth(&params.api_key)
+            .header("content-type", "application/json")
+            .json(&request_body);
+        let response = send_with_retry(
+            app,
+            request,
+            params.max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+            channel_id,
+        )
+        .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            break response;
+        }
+
+        let error_body = response.text().await.unwrap_or_default();
+        let json = serde_json::from_str::<serde_json::Value>(&error_body).ok();
+        let error_code = json
+            .as_ref()
+            .and_then(|json| json["error"]["code"].as_str().map(str::to_string))
+            .unwrap_or_default();
+        let error_msg = json
+            .as_ref()
+            .and_then(|json| json["error"]["message"].as_str().map(str::to_string))
+            .unwrap_or(error_body);
+
+        // Nothing has streamed yet, so an overloaded or unknown model can
+        // still be swapped for the next one in the chain
+        let model_unavailable =
+            status.as_u16() == 503 || (status.as_u16() == 404 && error_code == "model_not_found");
+        if model_unavailable {
+            if let Some(next_model) = fallback_models.next() {
+                tracing::warn!(
+                    from = %request_body.model,
+                    to = %next_model,
+                    status = %status,
+                    "Model unavailable, falling back"
+                );
+                let _ = app.emit(
+                    &stream_event("claude-model-fallback", channel_id),
+                    ModelFallbackEvent {
+                        from_model: std::mem::replace(&mut request_body.model, next_model.clone()),
+                        to_model: next_model,
+                        reason: error_msg,
+                    },
+                );
+                continue;
+            }
+        }
+
+        let error = match status.as_u16() {
+            401 => ClaudeError::Api("Invalid API key".to_string()),
+            429 => ClaudeError::RateLimited(error_msg),
+            400 => ClaudeError::Api(error_msg),
+            500..=599 => ClaudeError::Api(format!("Server error: {}", error_msg)),
+            _ => ClaudeError::Api(format!("Error ({}): {}", status, error_msg)),
+        };
+
+        // Same events as a mid-stream error, so listeners stop waiting
+        if let Some(reply) = reply.take() {
+            reply.finish().await;
+        }
+        let _ = app.emit(
+            &stream_event("claude-stream-error", channel_id),
+            StreamError {
+                error: error.to_string(),
+            },
+        );
+        let _ = app.emit(
+            &stream_event("claude-stream-chunk", channel_id),
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+        return Err(error);
+    };
+
+    let done = || {
+        let _ = app.emit(
+            &stream_event("claude-stream-chunk", channel_id),
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+    };
+
+    let mut stream = response.bytes_stream();
+    let mut full_response = String::new();
+    let mut buffer = String::new();
+    let mut finished = false;
+
+    // Ends early (with None) when cancelled, even if the stream has stalled
+    while let Some(chunk_result) = next_chunk(&mut stream, cancel).await? {
+        let chunk = chunk_result?;
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+        while let Some(newline_pos) = buffer.find('\n') {
+            let line = buffer[..newline_pos].trim().to_string();
+            buffer = buffer[newline_pos + 1..].to_string();
+
+            let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+            if data == "[DONE]" {
+                finished = true;
+                if let Some(reply) = reply.take() {
+                    reply.finish().await;
+                }
+                done();
+                continue;
+            }
+
+            let Ok(event) = serde_json::from_str::<CompletionChunk>(data) else {
+                continue;
+            };
+            if let Some(err) = event.error {
+                if let Some(reply) = reply.take() {
+                    reply.finish().await;
+                }
+                let _ = app.emit(
+                    &stream_event("claude-stream-error", channel_id),
+                    StreamError {
+                        error: err.message.clone(),
+                    },
+                );
+                return Err(ClaudeError::Api(err.message));
+            }
+
+            let text = event
+                .choices
+                .into_iter()
+                .filter_map(|choice| choice.delta.and_then(|d| d.content))
+                .collect::<String>();
+            if !text.is_empty() {
+                full_response.push_str(&text);
+                if let Some(reply) = reply.as_mut() {
+                    reply.push(&text).await;
+                }
+                let _ = app.emit(
+                    &stream_event("claude-stream-chunk", channel_id),
+                    StreamChunk {
+                        chunk: text,
+                        done: false,
+                    },
+                );
+            }
+        }
+    }
+
+    if let Some(reply) = reply {
+        reply.finish().await;
+    }
+    if cancel.is_cancelled() {
+        done();
+    } else if !finished {
+        return Err(stream_ended_early(app, channel_id));
+    }
+    Ok(full_response)
+}
+
+/// Send a message to an OpenAI-compatible chat completions endpoint with a
+/// streaming response. Uses the "openai" key from the keychain; events,
+/// including `channel_id` namespacing, match `send_message`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_message_openai(
+    app: AppHandle,
+    client: State<'_, Client>,
+    generation: State<'_, GenerationState>,
+    messages: Vec<Message>,
+    system_prompt: Option<String>,
+    model: Option<String>,
+    max_retries: Option<u32>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    md_path: Option<String>,
+    channel_id: Option<String>,
+) -> Result<String, ClaudeError> {
+    let channel_id = channel_id.as_deref();
+    validate_channel_id(channel_id)?;
+    let api_key = openai_api_key()?;
+    validate_openai_sampling(temperature, top_p)?;
+
+    let system_prompt = document_system_prompt(md_path.as_deref(), system_prompt).await;
+    let cancel = generation.begin(channel_id);
+    let Some(_slot) = app
+        .state::<RequestQueue>()
+        .acquire(&app, channel_id, &cancel)
+        .await
+    else {
+        let _ = app.emit(
+            &stream_event("claude-stream-chunk", channel_id),
+            StreamChunk {
+                chunk: String::new(),
+                done: true,
+            },
+        );
+        return Ok(String::new());
+    };
+
+    let params = ChatParams {
+        api_key,
+        model: model.unwrap_or_else(default_openai_model),
+        fallback_models: Vec::new(),
+        messages,
+        system_prompt,
+        max_retries,
+        max_tokens,
+        temperature,
+        top_p,
+    };
+    stream_chat(&app, &client, &cancel, params, None, channel_id).await
+}
@@ -0,0 +1,30 @@
+use reqwest::Client;
+use std::time::Duration;
+
+/// How long to wait for a connection to be established
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest silence allowed while waiting for a response or between chunks of
+/// a streamed body. There's deliberately no total timeout: long generations
+/// keep streaming well past any fixed bound.
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(60);
+/// Overrides the read timeout, in seconds
+const READ_TIMEOUT_ENV: &str = "WRITECRAFT_HTTP_READ_TIMEOUT_SECS";
+
+fn read_timeout() -> Duration {
+    std::env::var(READ_TIMEOUT_ENV)
+        .ok()
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_READ_TIMEOUT)
+}
+
+/// Build the HTTP client shared by all network commands.
+/// Reusing one client keeps its connection pool and TLS sessions warm.
+pub fn build_client() -> Client {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(read_timeout())
+        .build()
+        .expect("failed to build HTTP client")
+}
@@ -1,10 +1,76 @@
 mod commands;
+mod http;
 mod models;
 
 use commands::*;
-use tauri::menu::{AboutMetadata, MenuBuilder, MenuItemBuilder, SubmenuBuilder};
-use tauri::{Emitter, Manager};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tauri::menu::{AboutMetadata, MenuBuilder, MenuItemBuilder, PredefinedMenuItem, SubmenuBuilder};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Menu item IDs for recent documents are this prefix followed by the path
+const RECENT_MENU_PREFIX: &str = "open_recent:";
+
+/// Repopulate File > Open Recent from the persisted recents list
+pub(crate) fn rebuild_recent_menu(app: &AppHandle) -> tauri::Result<()> {
+    let Some(submenu) = app
+        .menu()
+        .and_then(|menu| menu.get("file"))
+        .and_then(|item| item.as_submenu().cloned())
+        .and_then(|file| file.get("open_recent"))
+        .and_then(|item| item.as_submenu().cloned())
+    else {
+        return Ok(());
+    };
+
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+
+    let recents = load_recent_documents();
+    if recents.is_empty() {
+        let empty_item = MenuItemBuilder::new("No Recent Documents")
+            .enabled(false)
+            .build(app)?;
+        submenu.append(&empty_item)?;
+    }
+    for entry in &recents {
+        let item = MenuItemBuilder::new(&entry.name)
+            .id(format!("{}{}", RECENT_MENU_PREFIX, entry.path))
+            .build(app)?;
+        submenu.append(&item)?;
+    }
+
+    let clear_item = MenuItemBuilder::new("Clear Menu")
+        .id("clear_recent")
+        .enabled(!recents.is_empty())
+        .build(app)?;
+    submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    submenu.append(&clear_item)?;
+
+    Ok(())
+}
+
+/// Route a deep link to the main window. `fizz://open?path=...` links are
+/// queued for the frontend (see `take_pending_open_document`) and announced
+/// with 'open-document'; anything else, like OAuth callbacks, is passed
+/// through as 'deep-link' for the frontend to handle.
+fn handle_deep_link(app: &AppHandle, url: &str) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.host_str() == Some("open") => match resolve_open_link(&parsed) {
+            Ok(path) => {
+                app.state::<PendingOpenDocument>().set(path.clone());
+                let _ = window.emit("open-document", path);
+            }
+            Err(e) => tracing::warn!("Ignoring open link: {}", e),
+        },
+        _ => {
+            let _ = window.emit("deep-link", url.to_string());
+        }
+    }
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -16,11 +82,8 @@ pub fn run() {
         ..Default::default()
     }));
 
-    // Initialize tracing for structured logging
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .with(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    // Initialize tracing: rolling log file, plus stdout in debug builds
+    let log_state = init_logging();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
@@ -38,11 +101,22 @@ pub fn run() {
                 // Check if any argument looks like a deep link URL
                 for arg in args.iter() {
                     if arg.starts_with("fizz://") {
-                        let _ = window.emit("deep-link", arg.clone());
+                        handle_deep_link(app, arg);
                     }
                 }
             }
         }))
+        .manage(http::build_client())
+        .manage(log_state)
+        .manage(GenerationState::default())
+        .manage(RequestQueue::default())
+        .manage(ToolResultState::default())
+        .manage(SessionRefresher::default())
+        .manage(IdleLock::default())
+        .manage(OAuthFlowState::default())
+        .manage(SidecarSaveQueue::default())
+        .manage(DocumentWatcher::default())
+        .manage(PendingOpenDocument::default())
         .setup(|app| {
             // Custom menu items
             let check_updates_item = MenuItemBuilder::new("Check for Updates...")
@@ -127,10 +201,15 @@ pub fn run() {
                 .quit()
                 .build()?;
 
+            // Populated by rebuild_recent_menu once the menu is installed
+            let open_recent_submenu =
+                SubmenuBuilder::with_id(app, "open_recent", "Open Recent").build()?;
+
             // File menu
-            let file_submenu = SubmenuBuilder::new(app, "File")
+            let file_submenu = SubmenuBuilder::with_id(app, "file", "File")
                 .item(&new_item)
                 .item(&open_item)
+                .item(&open_recent_submenu)
                 .separator()
                 .item(&save_item)
                 .item(&save_as_item)
@@ -179,8 +258,12 @@ pub fn run() {
                 .build()?;
 
             app.set_menu(menu)?;
+            rebuild_recent_menu(app.handle())?;
 
-            // Register deep link handler for OAuth callbacks
+            // Restore saved settings (log level, request limit, idle timeout)
+            apply_app_settings(app.handle(), &load_app_settings());
+
+            // Register deep link handler for OAuth callbacks and document links
             #[cfg(desktop)]
             {
                 use tauri_plugin_deep_link::DeepLinkExt;
@@ -189,58 +272,175 @@ pub fn run() {
                 app.deep_link().on_open_url(move |event| {
                     // Get the URLs from the event
                     for url in event.urls() {
-                        // Emit to frontend for handling
-                        if let Some(window) = handle.get_webview_window("main") {
-                            let _ = window.emit("deep-link", url.to_string());
-                        }
+                        handle_deep_link(&handle, url.as_str());
                     }
                 });
+
+                // On Windows and Linux the link that launched the app comes in
+                // as an argument rather than an open-url event
+                if let Ok(Some(urls)) = app.deep_link().get_current() {
+                    for url in urls {
+                        handle_deep_link(app.handle(), url.as_str());
+                    }
+                }
             }
 
+            // Keep the auth session fresh during long editing sessions
+            app.state::<SessionRefresher>().start(app.handle());
+
+            // Lock the session after inactivity, once a timeout is configured
+            app.state::<IdleLock>().start(app.handle());
+
             Ok(())
         })
         .on_menu_event(|app, event| {
             let id = event.id().as_ref();
+            if id == "clear_recent" {
+                let _ = clear_recent_documents(app.clone());
+                return;
+            }
             if let Some(window) = app.get_webview_window("main") {
-                let _ = window.emit("menu-event", id);
+                match id.strip_prefix(RECENT_MENU_PREFIX) {
+                    Some(path) => {
+                        let _ = window.emit("menu-open-recent", path);
+                    }
+                    None => {
+                        let _ = window.emit("menu-event", id);
+                    }
+                }
             }
         })
         .invoke_handler(tauri::generate_handler![
             // File commands
             read_document,
+            get_document_size,
+            read_document_range,
+            read_document_with_frontmatter,
             write_document,
+            check_free_space,
+            get_document_mtime,
             read_sidecar,
             write_sidecar,
+            repair_document_link,
+            queue_sidecar_save,
+            flush_pending_saves,
+            watch_document,
+            unwatch_document,
+            transition_stage,
+            prune_conversation,
+            compact_editing_history,
+            merge_conversations,
+            append_chat_message,
+            set_custom_field,
+            remove_custom_field,
+            reorder_outline,
+            update_outline_prompt,
+            extract_outline_from_document,
+            diff_concept_versions,
+            export_sidecar_report,
+            export_conversation,
+            validate_sidecar,
+            recover_pending_writes,
+            set_system_prompt_template,
+            set_word_goal,
             file_exists,
             get_sidecar_path_for_document,
             rename_document,
+            move_document,
+            sync_filename_to_title,
+            slugify_title,
+            duplicate_document,
+            delete_document,
+            reveal_in_file_manager,
+            take_pending_open_document,
+            revert_edit,
+            reapply_edit,
             get_writecraft_documents_dir,
+            get_recent_documents,
+            clear_recent_documents,
+            export_settings,
+            import_settings,
+            get_app_settings,
+            get_local_usage,
+            create_document_backup,
+            snapshot_document,
+            restore_snapshot,
+            list_documents,
+            get_ignore_rules,
+            set_ignore_rules,
+            search_documents,
+            document_stats,
+            readability_stats,
+            encode_image_for_claude,
+            // Export commands
+            export_pdf,
+            export_word,
+            export_all,
+            render_markdown,
+            import_docx,
+            import_text,
             // Keychain commands (for legacy API key support)
-            get_api_key,
+            has_api_key,
+            reveal_api_key,
             set_api_key,
+            replace_api_key,
             delete_api_key,
+            clear_credential_caches,
             test_api_key,
+            list_models,
             // Auth commands
             sign_up,
             sign_in,
+            resend_confirmation,
+            sign_in_with_otp,
+            verify_otp,
             sign_in_with_oauth,
             open_oauth_url,
             handle_oauth_callback,
+            cancel_pending_auth,
             sign_out,
+            delete_account,
             get_session,
             refresh_session,
+            report_activity,
+            set_idle_timeout,
+            get_idle_timeout,
             reset_password,
+            update_password,
             get_profile,
             update_profile,
+            get_cached_avatar,
+            list_cloud_documents,
+            pull_cloud_document,
+            push_cloud_document,
             get_subscription_info,
             get_checkout_url,
             get_billing_portal_url,
             debug_auth_state,
+            validate_session_remote,
+            check_deep_link_registration,
             // Claude API commands
             send_message,
             send_message_with_tools,
-            send_message_authenticated
+            send_message_authenticated,
+            send_message_openai,
+            cancel_generation,
+            set_max_concurrent_requests,
+            run_tool_conversation,
+            submit_tool_result,
+            // Logging commands
+            set_log_level,
+            get_log_file_path
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Queued autosaves would otherwise be lost at quit
+            if let tauri::RunEvent::ExitRequested { .. } = event {
+                let queue = app.state::<SidecarSaveQueue>();
+                if let Err(e) = tauri::async_runtime::block_on(queue.flush()) {
+                    tracing::error!("Failed to flush autosaves before exit: {}", e);
+                }
+            }
+        });
 }
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Current sidecar schema version
+pub const SIDECAR_VERSION: &str = "1.1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DocumentStage {
     Idea,
@@ -35,6 +39,13 @@ pub enum ContentBlock {
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
     },
+    Thinking {
+        thinking: String,
+        signature: String,
+    },
+    RedactedThinking {
+        data: String,
+    },
 }
 
 /// Message content can be either a simple string or an array of content blocks
@@ -80,13 +91,68 @@ impl Default for Concept {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    Unchanged,
+    Changed,
+}
+
+/// One concept field compared across two snapshots
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDiff {
+    pub status: DiffStatus,
+    pub old: String,
+    pub new: String,
+}
+
+impl FieldDiff {
+    fn between(old: &str, new: &str) -> Self {
+        FieldDiff {
+            status: if old == new {
+                DiffStatus::Unchanged
+            } else {
+                DiffStatus::Changed
+            },
+            old: old.to_string(),
+            new: new.to_string(),
+        }
+    }
+}
+
+/// Field-by-field differences between two concept snapshots
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConceptDiff {
+    pub title: FieldDiff,
+    pub core_argument: FieldDiff,
+    pub audience: FieldDiff,
+    pub tone: FieldDiff,
+}
+
+impl ConceptDiff {
+    pub fn between(from: &ConceptSnapshot, to: &ConceptSnapshot) -> Self {
+        ConceptDiff {
+            title: FieldDiff::between(&from.title, &to.title),
+            core_argument: FieldDiff::between(&from.core_argument, &to.core_argument),
+            audience: FieldDiff::between(&from.audience, &to.audience),
+            tone: FieldDiff::between(&from.tone, &to.tone),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OutlinePrompt {
     pub id: String,
     pub title: String,
     pub description: String,
     pub estimated_words: Option<u32>,
+    /// Nesting below the top-level sections, for outlines taken from
+    /// document headings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u8>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,6 +178,71 @@ impl Default for Outline {
     }
 }
 
+impl Outline {
+    /// Record the current prompts in `versions` before they change, unless
+    /// the latest version already holds them
+    fn snapshot_current(&mut self) {
+        let Some(current) = &self.current else {
+            return;
+        };
+        if self.versions.last().is_some_and(|v| &v.prompts == current) {
+            return;
+        }
+        self.versions.push(OutlineSnapshot {
+            prompts: current.clone(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+
+    /// Rearrange the current prompts into the given id order. The ids must be
+    /// exactly the ids of the current prompts.
+    pub fn reorder(&mut self, ordered_ids: &[String]) -> Result<(), String> {
+        let current = self.current.as_ref().ok_or("Document has no outline")?;
+
+        let mut reordered = Vec::with_capacity(current.len());
+        for id in ordered_ids {
+            let prompt = current
+                .iter()
+                .find(|p| &p.id == id)
+                .ok_or_else(|| format!("No outline section with id {}", id))?;
+            if reordered.iter().any(|p: &OutlinePrompt| &p.id == id) {
+                return Err(format!("Outline section {} is listed twice", id));
+            }
+            reordered.push(prompt.clone());
+        }
+        if reordered.len() != current.len() {
+            return Err("Every outline section must be included in the new order".to_string());
+        }
+
+        self.snapshot_current();
+        self.current = Some(reordered);
+        Ok(())
+    }
+
+    /// Replace all prompts, keeping the previous ones in `versions`
+    pub fn replace(&mut self, prompts: Vec<OutlinePrompt>) {
+        self.snapshot_current();
+        self.current = Some(prompts);
+    }
+
+    /// Replace the prompt with the same id
+    pub fn update_prompt(&mut self, prompt: OutlinePrompt) -> Result<(), String> {
+        let index = self
+            .current
+            .as_ref()
+            .ok_or("Document has no outline")?
+            .iter()
+            .position(|p| p.id == prompt.id)
+            .ok_or_else(|| format!("No outline section with id {}", prompt.id))?;
+
+        self.snapshot_current();
+        if let Some(current) = self.current.as_mut() {
+            current[index] = prompt;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Conversation {
@@ -128,6 +259,126 @@ impl Default for Conversation {
     }
 }
 
+/// Longest excerpt of a single message kept in a fallback summary
+const SUMMARY_EXCERPT_CHARS: usize = 200;
+
+impl ChatMessage {
+    /// Whether this message carries tool results, which must stay right after
+    /// the assistant message holding the matching tool use
+    fn has_tool_results(&self) -> bool {
+        match &self.content {
+            MessageContent::Text(_) => false,
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .any(|b| matches!(b, ContentBlock::ToolResult { .. })),
+        }
+    }
+
+    /// The message's text, ignoring tool blocks
+    fn text(&self) -> String {
+        match &self.content {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// Split messages into runs that must stay together: a message followed by
+/// any messages carrying its tool results
+fn message_runs(messages: Vec<ChatMessage>) -> Vec<Vec<ChatMessage>> {
+    let mut runs: Vec<Vec<ChatMessage>> = Vec::new();
+    for message in messages {
+        match runs.last_mut() {
+            Some(run) if message.has_tool_results() => run.push(message),
+            _ => runs.push(vec![message]),
+        }
+    }
+    runs
+}
+
+impl Conversation {
+    /// Merge another conversation's messages into this one in chronological
+    /// order. The incoming messages get fresh ids, and a tool use is never
+    /// separated from its results. Returns the number of messages added.
+    pub fn merge(&mut self, incoming: Vec<ChatMessage>) -> usize {
+        let added = incoming.len();
+        let incoming = incoming.into_iter().map(|mut m| {
+            m.id = uuid::Uuid::new_v4().to_string();
+            m
+        });
+
+        let sent_at = |run: &Vec<ChatMessage>| {
+            chrono::DateTime::parse_from_rfc3339(&run[0].created_at).ok()
+        };
+        let mut ours = message_runs(std::mem::take(&mut self.messages)).into_iter().peekable();
+        let mut theirs = message_runs(incoming.collect()).into_iter().peekable();
+
+        // Both sides are already in order, so a merge keeps each side's own
+        // sequence intact; ties go to the existing messages
+        while let (Some(a), Some(b)) = (ours.peek(), theirs.peek()) {
+            let next = if sent_at(b) < sent_at(a) {
+                theirs.next()
+            } else {
+                ours.next()
+            };
+            self.messages.extend(next.into_iter().flatten());
+        }
+        self.messages.extend(ours.flatten());
+        self.messages.extend(theirs.flatten());
+
+        added
+    }
+
+    /// Drop all but the last `keep_last` messages, folding the dropped ones
+    /// into `summary`. A caller-provided summary (e.g. one written by Claude)
+    /// is used as-is; otherwise a condensed transcript is appended.
+    /// Returns the number of messages removed.
+    pub fn prune(&mut self, keep_last: usize, summary: Option<String>) -> usize {
+        let mut cut = self.messages.len().saturating_sub(keep_last);
+        // Never split a tool use from its results
+        while cut > 0 && cut < self.messages.len() && self.messages[cut].has_tool_results() {
+            cut -= 1;
+        }
+        if cut == 0 {
+            return 0;
+        }
+
+        let pruned: Vec<ChatMessage> = self.messages.drain(..cut).collect();
+        match summary {
+            Some(summary) => self.summary = summary,
+            None => {
+                let transcript = pruned
+                    .iter()
+                    .filter_map(|m| {
+                        let text = m.text();
+                        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                        if text.is_empty() {
+                            return None;
+                        }
+                        let excerpt: String = text.chars().take(SUMMARY_EXCERPT_CHARS).collect();
+                        let ellipsis = if excerpt.len() < text.len() { "…" } else { "" };
+                        Some(format!("{}: {}{}", m.role, excerpt, ellipsis))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if !self.summary.is_empty() && !transcript.is_empty() {
+                    self.summary.push('\n');
+                }
+                self.summary.push_str(&transcript);
+            }
+        }
+
+        pruned.len()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EditHistoryEntry {
@@ -140,16 +391,44 @@ pub struct EditHistoryEntry {
     pub rationale: Option<String>,
 }
 
+/// A named copy of the whole document body, for restoring later
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSnapshot {
+    pub id: String,
+    pub label: String,
+    pub created_at: String,
+    pub content: String,
+}
+
+/// A move from one stage to another, recorded for the progress timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageTransition {
+    pub from: DocumentStage,
+    pub to: DocumentStage,
+    pub changed_at: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
     pub model: String,
+    /// Persona for this document's assistant. `{{title}}`, `{{core_argument}}`,
+    /// `{{audience}}` and `{{tone}}` are filled in from the current concept.
+    #[serde(default)]
+    pub system_prompt_template: Option<String>,
+    /// Target length in words, shown as progress in `document_stats`
+    #[serde(default)]
+    pub word_goal: Option<u32>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             model: "claude-sonnet-4-20250514".to_string(),
+            system_prompt_template: None,
+            word_goal: None,
         }
     }
 }
@@ -177,10 +456,17 @@ pub struct Sidecar {
     pub document_id: String,
     pub created_at: String,
     pub stage: DocumentStage,
+    #[serde(default)]
+    pub stage_history: Vec<StageTransition>,
     pub concept: Concept,
     pub outline: Outline,
     pub conversation: Conversation,
     pub editing_history: Vec<EditHistoryEntry>,
+    #[serde(default)]
+    pub snapshots: Vec<DocumentSnapshot>,
+    /// Writer-defined metadata such as client, deadline or genre
+    #[serde(default)]
+    pub custom_fields: HashMap<String, serde_json::Value>,
     pub settings: Settings,
     pub meta: Meta,
 }
@@ -189,14 +475,17 @@ impl Sidecar {
     pub fn new() -> Self {
         let now = chrono::Utc::now().to_rfc3339();
         Sidecar {
-            version: "1.0".to_string(),
+            version: SIDECAR_VERSION.to_string(),
             document_id: uuid::Uuid::new_v4().to_string(),
             created_at: now.clone(),
             stage: DocumentStage::default(),
+            stage_history: Vec::new(),
             concept: Concept::default(),
             outline: Outline::default(),
             conversation: Conversation::default(),
             editing_history: Vec::new(),
+            snapshots: Vec::new(),
+            custom_fields: HashMap::new(),
             settings: Settings::default(),
             meta: Meta {
                 app_version: "0.1.0".to_string(),
@@ -204,6 +493,104 @@ impl Sidecar {
             },
         }
     }
+
+    /// Copy for a duplicated document: a fresh identity and timestamps, with
+    /// the edit history left behind since it describes the original's text
+    pub fn duplicate(&self) -> Self {
+        let now = chrono::Utc::now().to_rfc3339();
+        Sidecar {
+            document_id: uuid::Uuid::new_v4().to_string(),
+            created_at: now.clone(),
+            editing_history: Vec::new(),
+            meta: Meta {
+                last_opened_at: now,
+                ..self.meta.clone()
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Move to a new stage, recording the transition. Moving to the current
+    /// stage is a no-op.
+    pub fn transition_stage(&mut self, new_stage: DocumentStage) {
+        if self.stage == new_stage {
+            return;
+        }
+        self.stage_history.push(StageTransition {
+            from: self.stage.clone(),
+            to: new_stage.clone(),
+            changed_at: chrono::Utc::now().to_rfc3339(),
+        });
+        self.stage = new_stage;
+    }
+
+    /// Drop editing history entries created before `cutoff`. Rejected entries
+    /// are kept unless `include_rejected` is set, and entries whose timestamp
+    /// doesn't parse are always kept. Returns the number of entries removed.
+    pub fn compact_editing_history(
+        &mut self,
+        cutoff: chrono::DateTime<chrono::Utc>,
+        include_rejected: bool,
+    ) -> usize {
+        let before = self.editing_history.len();
+        self.editing_history.retain(|entry| {
+            if !entry.accepted && !include_rejected {
+                return true;
+            }
+            chrono::DateTime::parse_from_rfc3339(&entry.created_at)
+                .map_or(true, |created| created >= cutoff)
+        });
+        before - self.editing_history.len()
+    }
+
+    /// Record a snapshot of the document body, dropping the oldest beyond
+    /// `max_snapshots`. Returns the new snapshot's id.
+    pub fn add_snapshot(&mut self, label: &str, content: String, max_snapshots: usize) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.snapshots.push(DocumentSnapshot {
+            id: id.clone(),
+            label: label.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            content,
+        });
+        let excess = self.snapshots.len().saturating_sub(max_snapshots);
+        self.snapshots.drain(..excess);
+        id
+    }
+
+    /// Build the system prompt for this document: the stored template with
+    /// concept fields filled in, followed by the caller's prompt
+    pub fn system_prompt(&self, base: Option<String>) -> Option<String> {
+        let template = self.settings.system_prompt_template.as_ref().map(|template| {
+            let concept = self.concept.current.as_ref();
+            let field = |f: fn(&ConceptSnapshot) -> &str| concept.map(f).unwrap_or_default();
+            template
+                .replace("{{title}}", field(|c| &c.title))
+                .replace("{{core_argument}}", field(|c| &c.core_argument))
+                .replace("{{audience}}", field(|c| &c.audience))
+                .replace("{{tone}}", field(|c| &c.tone))
+        });
+
+        let parts: Vec<String> = [template, base]
+            .into_iter()
+            .flatten()
+            .filter(|p| !p.trim().is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join("\n\n"))
+    }
+
+    /// Start the concept from a title found in the document itself
+    pub fn seed_concept_title(&mut self, title: &str) {
+        let snapshot = ConceptSnapshot {
+            title: title.to_string(),
+            core_argument: String::new(),
+            audience: String::new(),
+            tone: String::new(),
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        self.concept.versions.push(snapshot.clone());
+        self.concept.current = Some(snapshot);
+    }
 }
 
 impl Default for Sidecar {
@@ -211,3 +598,91 @@ impl Default for Sidecar {
         Self::new()
     }
 }
+
+// ============================================
+// Schema migrations
+// ============================================
+
+type Migration = fn(&mut serde_json::Value);
+
+/// Ordered migrations, each upgrading a sidecar from one version to the next
+const MIGRATIONS: &[(&str, &str, Migration)] = &[("1.0", "1.1", migrate_1_0_to_1_1)];
+
+/// 1.1 guarantees every section is present. Early 1.0 sidecars could be
+/// missing sections that were added later, which made them fail to load.
+fn migrate_1_0_to_1_1(value: &mut serde_json::Value) {
+    let Some(obj) = value.as_object_mut() else {
+        return;
+    };
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let defaults = [
+        ("documentId", serde_json::json!(uuid::Uuid::new_v4().to_string())),
+        ("createdAt", serde_json::json!(now)),
+        ("stage", serde_json::json!(DocumentStage::default())),
+        ("concept", serde_json::json!(Concept::default())),
+        ("outline", serde_json::json!(Outline::default())),
+        ("conversation", serde_json::json!(Conversation::default())),
+        ("editingHistory", serde_json::json!([])),
+        ("settings", serde_json::json!(Settings::default())),
+        ("meta", serde_json::json!(Meta::default())),
+    ];
+    for (key, default) in defaults {
+        obj.entry(key).or_insert(default);
+    }
+}
+
+/// Upgrade a raw sidecar to the current schema version in place.
+/// Sidecars without a version are treated as 1.0. Returns whether anything
+/// changed, or the version string if it can't be migrated (for example a
+/// sidecar written by a newer app).
+pub fn migrate_sidecar(value: &mut serde_json::Value) -> Result<bool, String> {
+    let mut version = value
+        .get("version")
+        .and_then(|v| v.as_str())
+        .unwrap_or("1.0")
+        .to_string();
+    let mut migrated = false;
+
+    while version != SIDECAR_VERSION {
+        let (_, to, migrate) = MIGRATIONS
+            .iter()
+            .find(|(from, _, _)| *from == version)
+            .ok_or_else(|| version.clone())?;
+
+        migrate(value);
+        version = to.to_string();
+        value["version"] = serde_json::json!(version);
+        migrated = true;
+    }
+
+    Ok(migrated)
+}
+
+/// Fill fields missing from a current-version sidecar with defaults, looking
+/// inside nested sections too. Lists are left as they are. Returns whether
+/// anything was added.
+pub fn backfill_sidecar(value: &mut serde_json::Value) -> bool {
+    fn fill(value: &mut serde_json::Value, defaults: serde_json::Value) -> bool {
+        let (Some(obj), serde_json::Value::Object(defaults)) = (value.as_object_mut(), defaults)
+        else {
+            return false;
+        };
+        let mut changed = false;
+        for (key, default) in defaults {
+            match obj.get_mut(&key) {
+                Some(existing) if !existing.is_null() || default.is_null() => {
+                    changed |= fill(existing, default);
+                }
+                _ => {
+                    obj.insert(key, default);
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    let defaults = serde_json::to_value(Sidecar::new()).unwrap_or_default();
+    fill(value, defaults)
+}
@@ -139,10 +139,15 @@ export function convertMessagesToApiFormat(excludeId?: string): Message[] {
 }
 
 /**
- * Build content blocks for assistant message
+ * Build content blocks for assistant message. Thinking blocks go first, as
+ * the API requires when the turn is sent back with tool results.
  */
-export function buildAssistantContent(text: string, toolUses: ToolUseEvent[]): ContentBlock[] {
-  const blocks: ContentBlock[] = [];
+export function buildAssistantContent(
+  text: string,
+  toolUses: ToolUseEvent[],
+  thinkingBlocks: ContentBlock[] = []
+): ContentBlock[] {
+  const blocks: ContentBlock[] = [...thinkingBlocks];
 
   if (text.trim()) {
     blocks.push({ type: 'text', text });
@@ -220,7 +225,11 @@ export async function runAgentLoop(callbacks: AgentCallbacks): Promise<void> {
         messagesToSend,
         systemPrompt,
         ALL_TOOLS,
-        streamCallbacks
+        streamCallbacks,
+        undefined,
+        true,
+        undefined,
+        documentStore.currentPath ?? undefined
       );
 
       // Increment usage count after successful API call
@@ -230,7 +239,7 @@ export async function runAgentLoop(callbacks: AgentCallbacks): Promise<void> {
       if (response.toolUses.length > 0) {
         chatStore.updateMessage(
           currentAssistantMessageId,
-          buildAssistantContent(response.textContent, response.toolUses)
+          buildAssistantContent(response.textContent, response.toolUses, response.thinkingBlocks)
         );
       } else if (response.textContent !== accumulatedText) {
         chatStore.updateMessage(currentAssistantMessageId, response.textContent);
@@ -281,8 +290,9 @@ export async function runAgentLoop(callbacks: AgentCallbacks): Promise<void> {
     chatStore.setLoading(false);
     // Record what Claude has now seen for change tracking
     documentStore.snapshotLastSeen();
-    // Refresh usage count from server to stay in sync
-    authStore.fetchSubscriptionInfo();
+    // Refresh usage count from server to stay in sync; this message
+    // changed it, so skip the cache
+    authStore.fetchSubscriptionInfo(true);
   }
 }
 
@@ -76,13 +76,15 @@ export interface ToolMessageCallbacks {
  * @param systemPrompt - Optional system prompt
  * @param onChunk - Callback for each streamed chunk
  * @param onError - Optional callback for errors
+ * @param mdPath - Optional path of the open document, whose prompt template is applied
  * @returns Promise resolving to the complete response
  */
 export async function sendMessage(
   messages: ChatMessage[],
   systemPrompt?: string,
   onChunk?: OnChunkCallback,
-  onError?: OnErrorCallback
+  onError?: OnErrorCallback,
+  mdPath?: string
 ): Promise<string> {
   let chunkUnlisten: UnlistenFn | null = null;
   let errorUnlisten: UnlistenFn | null = null;
@@ -106,6 +108,7 @@ export async function sendMessage(
       messages,
       systemPrompt: systemPrompt || null,
       model: null, // Use default model
+      mdPath: mdPath || null,
     });
 
     return response;
@@ -128,8 +131,7 @@ export async function sendMessage(
  */
 export async function hasApiKey(): Promise<boolean> {
   try {
-    const key = await invoke<string | null>('get_api_key');
-    return key !== null;
+    return await invoke<boolean>('has_api_key');
   } catch (e) {
     Sentry.captureException(e);
     return false;
@@ -141,9 +143,8 @@ export async function hasApiKey(): Promise<boolean> {
  */
 export async function testApiKey(): Promise<boolean> {
   try {
-    const key = await invoke<string | null>('get_api_key');
-    if (!key) return false;
-    return await invoke<boolean>('test_api_key', { key });
+    if (!(await invoke<boolean>('has_api_key'))) return false;
+    return await invoke<boolean>('test_api_key');
   } catch (e) {
     Sentry.captureException(e);
     return false;
@@ -167,6 +168,7 @@ export interface MessageAnalyticsContext {
  * @param model - Optional model override
  * @param useAuthenticatedProxy - Whether to use the authenticated Supabase proxy
  * @param analyticsContext - Optional analytics context for tracking
+ * @param mdPath - Optional path of the open document, whose prompt template is applied
  * @returns Promise resolving to AssistantResponse
  */
 export async function sendMessageWithTools(
@@ -176,7 +178,8 @@ export async function sendMessageWithTools(
   callbacks?: ToolMessageCallbacks,
   model?: string,
   useAuthenticatedProxy: boolean = true,
-  analyticsContext?: MessageAnalyticsContext
+  analyticsContext?: MessageAnalyticsContext,
+  mdPath?: string
 ): Promise<AssistantResponse> {
   let chunkUnlisten: UnlistenFn | null = null;
   let errorUnlisten: UnlistenFn | null = null;
@@ -220,6 +223,7 @@ export async function sendMessageWithTools(
       systemPrompt: systemPrompt || null,
       tools: tools || null,
       model: model || null,
+      mdPath: mdPath || null,
     });
 
     // Track message sent event
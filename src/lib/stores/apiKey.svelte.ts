@@ -22,16 +22,15 @@ class ApiKeyStore {
     this.error = null;
 
     try {
-      const key = await invoke<string | null>('get_api_key');
-      this.hasKey = key !== null;
+      this.hasKey = await invoke<boolean>('has_api_key');
 
       if (!this.hasKey) {
         this.isValid = null;
         return false;
       }
 
-      if (validate && key) {
-        const valid = await invoke<boolean>('test_api_key', { key });
+      if (validate) {
+        const valid = await invoke<boolean>('test_api_key');
         this.isValid = valid;
         if (!valid) {
           this.error = 'API key is invalid';
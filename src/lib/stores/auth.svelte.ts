@@ -52,6 +52,12 @@ export interface AuthSession {
   user: AuthUser;
 }
 
+/** Payload of the 'auth-state-changed' event */
+export interface AuthStateChange {
+  status: 'signed_in' | 'signed_out' | 'refreshed';
+  user: AuthUser | null;
+}
+
 export interface Profile {
   id: string;
   email: string;
@@ -88,6 +94,28 @@ export interface SubscriptionInfo {
 
 export type OAuthProvider = 'google' | 'apple';
 
+/** How often user activity is reported for the idle lock */
+const ACTIVITY_REPORT_INTERVAL_MS = 30_000;
+
+/** Error the backend sends when Supabase rate-limits a request */
+interface RateLimitedError {
+  kind: 'rateLimited';
+  message: string;
+  /** Seconds to wait, when Supabase says */
+  retryAfter: number | null;
+}
+
+function isRateLimited(e: unknown): e is RateLimitedError {
+  return typeof e === 'object' && e !== null && (e as { kind?: unknown }).kind === 'rateLimited';
+}
+
+/** Message for an error from invoke, which is a string or a structured error */
+function errorMessage(e: unknown): string {
+  if (e instanceof Error) return e.message;
+  if (isRateLimited(e)) return e.message;
+  return String(e);
+}
+
 // ============================================
 // Auth Store
 // ============================================
@@ -107,6 +135,15 @@ class AuthStore {
   isAuthenticating = $state(false);
   error = $state<string | null>(null);
   showUpgradeSuccess = $state(false);
+  /** The session was locked for inactivity; signing in again unlocks it */
+  isLocked = $state(false);
+  /** Seconds until sign-up or password reset emails can be sent again */
+  retrySeconds = $state(0);
+
+  private retryTimer: ReturnType<typeof setInterval> | null = null;
+
+  // Last time activity was reported to the backend's idle lock
+  private lastActivityReport = 0;
 
   // Derived
   get isAuthenticated(): boolean {
@@ -182,10 +219,13 @@ class AuthStore {
 
       // Listen for deep links (OAuth callbacks)
       await this.setupDeepLinkListener();
+
+      // Listen for background session refreshes
+      await this.setupSessionListeners();
     } catch (e) {
       console.error('Failed to initialize auth:', e);
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
     } finally {
       this.isLoading = false;
     }
@@ -209,22 +249,95 @@ class AuthStore {
           analytics.track('subscription_upgraded', { plan: 'pro' });
         } else {
           // For cancel or other billing callbacks, just refresh once
-          await this.fetchSubscriptionInfo();
+          await this.fetchSubscriptionInfo(true);
         }
       }
     });
   }
 
+  private async setupSessionListeners(): Promise<void> {
+    await listen<AuthSession>('session-refreshed', (event) => {
+      this.session = event.payload;
+    });
+
+    // A stale cached subscription was refreshed in the background
+    await listen<SubscriptionInfo>('subscription-updated', (event) => {
+      if (this.session) {
+        this.subscriptionInfo = event.payload;
+      }
+    });
+
+    await listen('session-expired', () => {
+      this.session = null;
+      this.profile = null;
+      this.subscriptionInfo = null;
+    });
+
+    await listen('session-locked', () => {
+      this.session = null;
+      this.profile = null;
+      this.subscriptionInfo = null;
+      this.isLocked = true;
+    });
+
+    await listen<AuthStateChange>('auth-state-changed', (event) => {
+      if (event.payload.status !== 'refreshed') {
+        this.isLocked = false;
+      }
+    });
+
+    for (const type of ['keydown', 'pointerdown', 'wheel']) {
+      window.addEventListener(type, () => this.reportActivity(), { passive: true });
+    }
+  }
+
+  /** Tell the backend the user is active, at most every 30 seconds */
+  private reportActivity(): void {
+    const now = Date.now();
+    if (now - this.lastActivityReport < ACTIVITY_REPORT_INTERVAL_MS) return;
+    this.lastActivityReport = now;
+    invoke('report_activity').catch((e) => Sentry.captureException(e));
+  }
+
+  /** Count down the wait a rate-limited request was given, if any */
+  private startRetryCountdown(e: unknown): void {
+    if (!isRateLimited(e) || !e.retryAfter) return;
+
+    if (this.retryTimer) clearInterval(this.retryTimer);
+    this.retrySeconds = e.retryAfter;
+    this.retryTimer = setInterval(() => {
+      this.retrySeconds = Math.max(0, this.retrySeconds - 1);
+      if (this.retrySeconds === 0 && this.retryTimer) {
+        clearInterval(this.retryTimer);
+        this.retryTimer = null;
+      }
+    }, 1000);
+  }
+
+  /** Lock the session after `minutes` of inactivity; null disables the lock */
+  async setIdleTimeout(minutes: number | null): Promise<void> {
+    await invoke('set_idle_timeout', { minutes });
+  }
+
+  /** The saved idle timeout in minutes; null when the lock is disabled */
+  async getIdleTimeout(): Promise<number | null> {
+    return invoke<number | null>('get_idle_timeout');
+  }
+
   // ============================================
   // Authentication Methods
   // ============================================
 
-  async signUp(email: string, password: string): Promise<void> {
+  async signUp(email: string, password: string, fullName?: string): Promise<void> {
     this.isAuthenticating = true;
     this.error = null;
 
     try {
-      const session = await invoke<AuthSession>('sign_up', { email, password });
+      const session = await invoke<AuthSession>('sign_up', {
+        email,
+        password,
+        fullName: fullName || null,
+      });
       this.session = session;
 
       // Fetch profile and subscription info
@@ -235,7 +348,8 @@ class AuthStore {
       analytics.track('user_signed_up', { method: 'email' });
     } catch (e) {
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
+      this.startRetryCountdown(e);
       throw e;
     } finally {
       this.isAuthenticating = false;
@@ -258,7 +372,7 @@ class AuthStore {
       analytics.track('user_signed_in', { method: 'email' });
     } catch (e) {
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
       throw e;
     } finally {
       this.isAuthenticating = false;
@@ -277,7 +391,7 @@ class AuthStore {
       // The callback will be handled by the deep link listener
     } catch (e) {
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
       this.isAuthenticating = false;
       throw e;
     }
@@ -296,7 +410,7 @@ class AuthStore {
       analytics.track('user_signed_in', { method: 'oauth' });
     } catch (e) {
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
       throw e;
     } finally {
       this.isAuthenticating = false;
@@ -326,7 +440,8 @@ class AuthStore {
       await invoke('reset_password', { email });
     } catch (e) {
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
+      this.startRetryCountdown(e);
       throw e;
     }
   }
@@ -365,7 +480,7 @@ class AuthStore {
       this.profile = profile;
     } catch (e) {
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
       throw e;
     }
   }
@@ -374,9 +489,13 @@ class AuthStore {
   // Subscription Methods
   // ============================================
 
-  async fetchSubscriptionInfo(): Promise<void> {
+  /**
+   * Fetch subscription info. The backend serves a cached value for up to a
+   * minute; pass `force` to bypass it, e.g. right after an upgrade.
+   */
+  async fetchSubscriptionInfo(force = false): Promise<void> {
     try {
-      const info = await invoke<SubscriptionInfo>('get_subscription_info');
+      const info = await invoke<SubscriptionInfo>('get_subscription_info', { force });
       this.subscriptionInfo = info;
     } catch (e) {
       console.error('Failed to fetch subscription info:', e);
@@ -390,7 +509,7 @@ class AuthStore {
    */
   async refreshSubscriptionWithRetry(maxAttempts = 5, delayMs = 1500): Promise<void> {
     for (let attempt = 1; attempt <= maxAttempts; attempt++) {
-      await this.fetchSubscriptionInfo();
+      await this.fetchSubscriptionInfo(true);
 
       // Check if we now have Pro status
       if (this.plan === 'pro') {
@@ -413,7 +532,7 @@ class AuthStore {
       await invoke('open_oauth_url', { url });
     } catch (e) {
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
       throw e;
     }
   }
@@ -424,7 +543,7 @@ class AuthStore {
       await invoke('open_oauth_url', { url });
     } catch (e) {
       Sentry.captureException(e);
-      this.error = e instanceof Error ? e.message : String(e);
+      this.error = errorMessage(e);
       throw e;
     }
   }
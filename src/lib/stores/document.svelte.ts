@@ -29,7 +29,8 @@
  */
 
 import { invoke } from '@tauri-apps/api/core';
-import type { Sidecar, DocumentStage, OutlinePrompt } from '$lib/types/sidecar';
+import { listen } from '@tauri-apps/api/event';
+import type { Sidecar, ChatMessage, DocumentStage, OutlinePrompt } from '$lib/types/sidecar';
 import { recentsStore } from './recents.svelte';
 import * as Sentry from '@sentry/svelte';
 import { analytics } from '$lib/services/analytics';
@@ -42,20 +43,34 @@ let isDirty = $state(false);
 let isLoading = $state(false);
 let error = $state<string | null>(null);
 
+// Modification time of the file on disk when we last read or wrote it
+let diskMtime: number | null = null;
+
 // Last seen by Claude state (for change tracking)
 let lastSeenContent = $state<string>('');
 let lastSeenOutline = $state<OutlinePrompt[] | null>(null);
 let lastSeenStage = $state<DocumentStage | null>(null);
 
+// A document the backend rewrote on disk (mirrors RewrittenDocument in file.rs)
+interface RewrittenDocument {
+  content: string;
+  mtime: number;
+  sidecar: Sidecar;
+}
+
 // Debounce timer
 let saveTimeout: ReturnType<typeof setTimeout> | null = null;
 const DEBOUNCE_MS = 500;
 
+// Index of the last path separator, accepting both `/` and Windows `\`
+function lastSeparator(path: string): number {
+  return Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\'));
+}
+
 // Derived filename
 function getFilename(): string | null {
   if (!currentPath) return null;
-  const parts = currentPath.split('/');
-  return parts[parts.length - 1];
+  return currentPath.substring(lastSeparator(currentPath) + 1);
 }
 
 // Load a document and its sidecar
@@ -67,6 +82,13 @@ async function loadDocument(path: string): Promise<void> {
     // Read the markdown content
     const docContent = await invoke<string>('read_document', { path });
     content = docContent;
+    diskMtime = await invoke<number>('get_document_mtime', { path });
+
+    // Finish any sidecar write a crash interrupted before reading it
+    const dir = path.substring(0, lastSeparator(path));
+    if (dir) {
+      await invoke('recover_pending_writes', { dir }).catch((e) => Sentry.captureException(e));
+    }
 
     // Read or create the sidecar
     const sidecarData = await invoke<Sidecar>('read_sidecar', { mdPath: path });
@@ -100,7 +122,11 @@ async function saveDocument(): Promise<void> {
   if (!currentPath) return;
 
   try {
-    await invoke('write_document', { path: currentPath, content });
+    diskMtime = await invoke<number>('write_document', {
+      path: currentPath,
+      content,
+      expectedMtime: diskMtime,
+    });
     isDirty = false;
     error = null;
   } catch (e) {
@@ -142,21 +168,17 @@ function updateContent(newContent: string): void {
   }, DEBOUNCE_MS);
 }
 
-// Update sidecar with debounced save
+// Update sidecar and queue a save; the backend debounces it and reports
+// failures through 'sidecar-save-failed'
 function updateSidecar(updates: Partial<Sidecar>): void {
-  if (!sidecar) return;
+  if (!sidecar || !currentPath) return;
 
   sidecar = { ...sidecar, ...updates };
 
-  // Clear existing timeout
-  if (saveTimeout) {
-    clearTimeout(saveTimeout);
-  }
-
-  // Set new debounced save
-  saveTimeout = setTimeout(async () => {
-    await saveSidecar();
-  }, DEBOUNCE_MS);
+  invoke('queue_sidecar_save', { mdPath: currentPath, sidecar }).catch((e) => {
+    Sentry.captureException(e);
+    error = e instanceof Error ? e.message : String(e);
+  });
 }
 
 // Flush any pending saves immediately (call before app close)
@@ -166,6 +188,10 @@ async function flushPendingSaves(): Promise<void> {
     saveTimeout = null;
   }
 
+  // Write anything still queued for autosave on the backend first, so an
+  // older queued sidecar can't land on top of the one saved below
+  await invoke('flush_pending_saves');
+
   // Save both document and sidecar if there are pending changes
   if (isDirty && currentPath) {
     await saveDocument();
@@ -176,10 +202,166 @@ async function flushPendingSaves(): Promise<void> {
   }
 }
 
-// Update the document stage
-function updateStage(stage: DocumentStage): void {
-  if (!sidecar) return;
-  updateSidecar({ stage });
+// Take over a sidecar the backend saved, so the next autosave doesn't
+// overwrite the change with the stale in-memory copy
+function adoptSidecar(mdPath: string, updated: Sidecar): void {
+  if (mdPath !== currentPath) return;
+  sidecar = updated;
+}
+
+// Put a reply the backend saved while streaming into the in-memory
+// conversation, so the next autosave keeps it
+function adoptReply(mdPath: string, message: ChatMessage): void {
+  if (mdPath !== currentPath || !sidecar) return;
+  const messages = sidecar.conversation.messages;
+  const index = messages.findIndex((m) => m.id === message.id);
+  if (index === -1) {
+    messages.push(message);
+  } else {
+    messages[index] = message;
+  }
+}
+
+// Listen for replies the backend saves to a document's conversation, and
+// for queued sidecar saves that failed
+async function initialize(): Promise<void> {
+  await listen<{ mdPath: string; message: ChatMessage }>('claude-reply-persisted', (event) => {
+    adoptReply(event.payload.mdPath, event.payload.message);
+  });
+  await listen<string>('sidecar-save-failed', (event) => {
+    if (event.payload === currentPath) {
+      error = 'Failed to save document data';
+    }
+  });
+}
+
+// Take over a document the backend rewrote on disk, including its new
+// modification time so the next save isn't reported as a conflict
+function adoptRewrite(mdPath: string, rewritten: RewrittenDocument): void {
+  if (mdPath !== currentPath) return;
+  content = rewritten.content;
+  diskMtime = rewritten.mtime;
+  sidecar = rewritten.sidecar;
+  isDirty = false;
+}
+
+// Save a labelled snapshot of the document as it is in the editor
+async function snapshotDocument(label: string): Promise<string> {
+  if (!currentPath) {
+    throw new Error('No document is currently open');
+  }
+  const path = currentPath;
+  await flushPendingSaves();
+
+  const saved = await invoke<{ snapshotId: string; sidecar: Sidecar }>('snapshot_document', {
+    mdPath: path,
+    label,
+  });
+  adoptSidecar(path, saved.sidecar);
+  return saved.snapshotId;
+}
+
+// Replace the document with a snapshot (the backend backs up the current one)
+async function restoreSnapshot(snapshotId: string): Promise<void> {
+  if (!currentPath) {
+    throw new Error('No document is currently open');
+  }
+  const path = currentPath;
+  await flushPendingSaves();
+
+  const restored = await invoke<RewrittenDocument>('restore_snapshot', {
+    mdPath: path,
+    snapshotId,
+  });
+  adoptRewrite(path, restored);
+}
+
+// Undo (revert) or redo a recorded edit in the document on disk
+async function swapEdit(editId: string, revert: boolean): Promise<void> {
+  if (!currentPath) {
+    throw new Error('No document is currently open');
+  }
+  const path = currentPath;
+  // The backend edits the file, so it has to hold what the editor shows
+  await flushPendingSaves();
+
+  const updated = await invoke<RewrittenDocument>(revert ? 'revert_edit' : 'reapply_edit', {
+    mdPath: path,
+    editId,
+  });
+  adoptRewrite(path, updated);
+}
+
+// Undo a recorded edit, restoring its `before` text
+function revertEdit(editId: string): Promise<void> {
+  return swapEdit(editId, true);
+}
+
+// Redo a recorded edit, putting its `after` text back
+function reapplyEdit(editId: string): Promise<void> {
+  return swapEdit(editId, false);
+}
+
+// Run a backend command that changes the open document's sidecar, then
+// take over the sidecar it returns
+async function runSidecarCommand(command: string, args: Record<string, unknown>): Promise<void> {
+  if (!currentPath) {
+    throw new Error('No document is currently open');
+  }
+  const path = currentPath;
+  // The backend reads the sidecar from disk, so it has to hold our changes
+  await flushPendingSaves();
+
+  try {
+    const updated = await invoke<Sidecar>(command, { mdPath: path, ...args });
+    adoptSidecar(path, updated);
+    error = null;
+  } catch (e) {
+    Sentry.captureException(e);
+    error = e instanceof Error ? e.message : String(e);
+    throw e;
+  }
+}
+
+// Keep the last `keepLast` messages, folding older ones into the summary
+function pruneConversation(keepLast: number, summary?: string): Promise<void> {
+  return runSidecarCommand('prune_conversation', { keepLast, summary: summary ?? null });
+}
+
+// Rearrange the outline sections into the order of `orderedIds`
+function reorderOutline(orderedIds: string[]): Promise<void> {
+  return runSidecarCommand('reorder_outline', { orderedIds });
+}
+
+// Replace one outline section, matched by id
+function updateOutlinePrompt(prompt: OutlinePrompt): Promise<void> {
+  return runSidecarCommand('update_outline_prompt', { prompt });
+}
+
+// Set the word-count goal; null or 0 clears it
+function setWordGoal(goal: number | null): Promise<void> {
+  return runSidecarCommand('set_word_goal', { goal });
+}
+
+// Set a custom metadata field, replacing any previous value
+function setCustomField(key: string, value: unknown): Promise<void> {
+  return runSidecarCommand('set_custom_field', { key, value });
+}
+
+// Remove a custom metadata field
+function removeCustomField(key: string): Promise<void> {
+  return runSidecarCommand('remove_custom_field', { key });
+}
+
+// Set the document's system prompt template; an empty template clears it
+function setSystemPromptTemplate(template: string | null): Promise<void> {
+  return runSidecarCommand('set_system_prompt_template', { template });
+}
+
+// Update the document stage, recording the transition for the timeline
+async function updateStage(stage: DocumentStage): Promise<void> {
+  if (!sidecar || sidecar.stage === stage) return;
+  await runSidecarCommand('transition_stage', { newStage: stage });
 }
 
 // Create a new document at the given path
@@ -271,9 +453,7 @@ function getChangesSinceLastSeen(): ChangesSinceLastSeen | null {
 // Get the directory of the current document
 function getDirectory(): string | null {
   if (!currentPath) return null;
-  const parts = currentPath.split('/');
-  parts.pop(); // Remove filename
-  return parts.join('/');
+  return currentPath.substring(0, lastSeparator(currentPath));
 }
 
 // Rename the current document
@@ -328,6 +508,7 @@ function closeDocument(): void {
   sidecar = null;
   isDirty = false;
   error = null;
+  diskMtime = null;
 
   // Reset last seen state
   lastSeenContent = '';
@@ -368,17 +549,31 @@ export const documentStore = {
     return lastSeenStage;
   },
 
+  initialize,
   loadDocument,
   saveDocument,
   saveSidecar,
   updateContent,
   updateSidecar,
   updateStage,
+  pruneConversation,
+  reorderOutline,
+  updateOutlinePrompt,
+  setWordGoal,
+  setCustomField,
+  removeCustomField,
+  setSystemPromptTemplate,
   createDocument,
   createDocumentWithDefaultPath,
   renameDocument,
   closeDocument,
   flushPendingSaves,
+  adoptSidecar,
+  adoptRewrite,
+  snapshotDocument,
+  restoreSnapshot,
+  revertEdit,
+  reapplyEdit,
   snapshotLastSeen,
   getChangesSinceLastSeen,
 };
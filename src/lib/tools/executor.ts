@@ -69,7 +69,7 @@ export async function executeToolCall(toolUse: ToolUseEvent): Promise<ToolResult
         return executeUpdateOutline(toolUse.id, validationResult.data as UpdateOutlineInput);
 
       case 'update_stage':
-        return executeUpdateStage(toolUse.id, validationResult.data as UpdateStageInput);
+        return await executeUpdateStage(toolUse.id, validationResult.data as UpdateStageInput);
 
       case 'add_edit_suggestion':
         return executeAddEditSuggestion(
@@ -181,10 +181,13 @@ function executeUpdateOutline(toolUseId: string, input: UpdateOutlineInput): Too
   };
 }
 
-function executeUpdateStage(toolUseId: string, input: UpdateStageInput): ToolResult {
+async function executeUpdateStage(
+  toolUseId: string,
+  input: UpdateStageInput
+): Promise<ToolResult> {
   // Stage is validated by Zod schema
   const previousStage = documentStore.sidecar?.stage ?? 'concept';
-  documentStore.updateStage(input.stage);
+  await documentStore.updateStage(input.stage);
 
   return {
     tool_use_id: toolUseId,
@@ -35,6 +35,7 @@ export interface OutlinePrompt {
   title: string;
   description: string;
   estimatedWords: number | null;
+  depth?: number;
 }
 
 export interface OutlineSnapshot {
@@ -62,8 +63,23 @@ export interface EditHistoryEntry {
   rationale: string | null;
 }
 
+export interface DocumentSnapshot {
+  id: string;
+  label: string;
+  createdAt: string;
+  content: string;
+}
+
+export interface StageTransition {
+  from: DocumentStage;
+  to: DocumentStage;
+  changedAt: string;
+}
+
 export interface Settings {
   model: string;
+  /** Supports {{title}}, {{core_argument}}, {{audience}} and {{tone}} */
+  systemPromptTemplate?: string | null;
 }
 
 export interface Meta {
@@ -72,14 +88,17 @@ export interface Meta {
 }
 
 export interface Sidecar {
-  version: '1.0';
+  version: '1.1';
   documentId: string;
   createdAt: string;
   stage: DocumentStage;
+  stageHistory: StageTransition[];
   concept: Concept;
   outline: Outline;
   conversation: Conversation;
   editingHistory: EditHistoryEntry[];
+  snapshots?: DocumentSnapshot[];
+  customFields?: Record<string, unknown>;
   settings: Settings;
   meta: Meta;
 }
@@ -87,10 +106,11 @@ export interface Sidecar {
 export function createDefaultSidecar(): Sidecar {
   const now = new Date().toISOString();
   return {
-    version: '1.0',
+    version: '1.1',
     documentId: crypto.randomUUID(),
     createdAt: now,
     stage: 'concept',
+    stageHistory: [],
     concept: {
       current: null,
       versions: [],
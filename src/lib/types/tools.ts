@@ -25,13 +25,23 @@ export interface PropertySchema {
   required?: string[];
 }
 
+/**
+ * Image source for vision input
+ */
+export type ImageSource =
+  | { type: 'base64'; media_type: 'image/png' | 'image/jpeg' | 'image/gif' | 'image/webp'; data: string }
+  | { type: 'url'; url: string };
+
 /**
  * Content block types for messages
  */
 export type ContentBlock =
   | { type: 'text'; text: string }
+  | { type: 'image'; source: ImageSource }
   | { type: 'tool_use'; id: string; name: string; input: Record<string, unknown> }
-  | { type: 'tool_result'; tool_use_id: string; content: string; is_error?: boolean };
+  | { type: 'tool_result'; tool_use_id: string; content: string; is_error?: boolean }
+  | { type: 'thinking'; thinking: string; signature: string }
+  | { type: 'redacted_thinking'; data: string };
 
 /**
  * Message content can be text or content blocks
@@ -71,4 +81,21 @@ export interface AssistantResponse {
   textContent: string;
   toolUses: ToolUseEvent[];
   stopReason: string;
+  /** The custom stop sequence that ended generation, if any */
+  stopSequence: string | null;
+  usage: Usage | null;
+  /** Extended thinking text, when thinking was enabled */
+  thinking: string | null;
+  /** Thinking blocks as received, to send back with the tool uses */
+  thinkingBlocks: ContentBlock[];
+}
+
+/**
+ * Token usage for a response, including prompt cache activity
+ */
+export interface Usage {
+  inputTokens: number;
+  outputTokens: number;
+  cacheCreationInputTokens: number;
+  cacheReadInputTokens: number;
 }
@@ -17,6 +17,31 @@ export function getErrorMessage(error: unknown): string {
   return String(error);
 }
 
+/**
+ * Message the backend uses when a network command couldn't reach the server
+ * (DNS failure, connection refused, no route)
+ */
+export const OFFLINE_ERROR_MESSAGE = 'No internet connection';
+
+/**
+ * Check whether an error from a network command means the user is offline
+ * @param error - The error value returned by `invoke`
+ * @returns True if the request never reached the server
+ */
+export function isOfflineError(error: unknown): boolean {
+  return getErrorMessage(error) === OFFLINE_ERROR_MESSAGE;
+}
+
+/**
+ * Check whether a chat request was refused because the model isn't in the
+ * user's plan, so the UI can offer an upgrade instead of an error
+ * @param error - The error value returned by `invoke`
+ * @returns True if the requested model needs a different plan
+ */
+export function isModelNotAllowedError(error: unknown): boolean {
+  return getErrorMessage(error).endsWith('is not included in your plan. Upgrade to use this model.');
+}
+
 /**
  * Create a JSON error response for tool results
  * @param message - The error message
@@ -0,0 +1,58 @@
+// Delete Account Edge Function
+// Cancels any Stripe subscription and permanently deletes the calling user
+
+import { corsHeaders, handleCors } from '../_shared/cors.ts';
+import { createServiceClient, getUser, getSubscription } from '../_shared/supabase.ts';
+import Stripe from 'https://esm.sh/stripe@14.12.0?target=deno';
+
+const stripe = new Stripe(Deno.env.get('STRIPE_SECRET_KEY')!, {
+  apiVersion: '2023-10-16',
+  httpClient: Stripe.createFetchHttpClient(),
+});
+
+Deno.serve(async (req) => {
+  // Handle CORS preflight
+  const corsResponse = handleCors(req);
+  if (corsResponse) return corsResponse;
+
+  if (req.method !== 'POST') {
+    return new Response('Method not allowed', { status: 405 });
+  }
+
+  try {
+    // Validate authentication
+    const authHeader = req.headers.get('Authorization');
+    const user = await getUser(authHeader);
+
+    if (!user) {
+      return new Response(JSON.stringify({ error: 'Unauthorized' }), {
+        status: 401,
+        headers: { ...corsHeaders, 'Content-Type': 'application/json' },
+      });
+    }
+
+    // Stop billing before the account (and its subscription row) disappears
+    const subscription = await getSubscription(user.id);
+    if (subscription?.stripe_subscription_id && subscription.status !== 'canceled') {
+      await stripe.subscriptions.cancel(subscription.stripe_subscription_id);
+    }
+
+    // Profiles, subscriptions and usage cascade from auth.users
+    const supabase = createServiceClient();
+    const { error } = await supabase.auth.admin.deleteUser(user.id);
+    if (error) {
+      throw error;
+    }
+
+    return new Response(JSON.stringify({ deleted: true }), {
+      status: 200,
+      headers: { ...corsHeaders, 'Content-Type': 'application/json' },
+    });
+  } catch (error) {
+    console.error('Error deleting account:', error);
+    return new Response(JSON.stringify({ error: 'Failed to delete account' }), {
+      status: 500,
+      headers: { ...corsHeaders, 'Content-Type': 'application/json' },
+    });
+  }
+});
@@ -0,0 +1,43 @@
+-- Cloud documents
+-- Stores a copy of a document's markdown and sidecar for syncing between machines
+
+-- ============================================
+-- DOCUMENTS TABLE
+-- Keyed by the sidecar's document id
+-- ============================================
+CREATE TABLE public.documents (
+  id UUID PRIMARY KEY,
+  user_id UUID NOT NULL REFERENCES public.profiles(id) ON DELETE CASCADE,
+  title TEXT NOT NULL DEFAULT '',
+  body TEXT NOT NULL DEFAULT '',
+  sidecar JSONB NOT NULL,
+  modified_at TIMESTAMPTZ NOT NULL, -- local file mtime at push, used for conflict checks
+  created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+  updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL
+);
+
+CREATE INDEX documents_user_id_idx ON public.documents(user_id, modified_at DESC);
+
+-- Enable RLS
+ALTER TABLE public.documents ENABLE ROW LEVEL SECURITY;
+
+-- RLS Policies for documents
+CREATE POLICY "Users can view own documents"
+  ON public.documents FOR SELECT
+  USING (auth.uid() = user_id);
+
+CREATE POLICY "Users can insert own documents"
+  ON public.documents FOR INSERT
+  WITH CHECK (auth.uid() = user_id);
+
+CREATE POLICY "Users can update own documents"
+  ON public.documents FOR UPDATE
+  USING (auth.uid() = user_id);
+
+CREATE POLICY "Users can delete own documents"
+  ON public.documents FOR DELETE
+  USING (auth.uid() = user_id);
+
+CREATE TRIGGER update_documents_updated_at
+  BEFORE UPDATE ON public.documents
+  FOR EACH ROW EXECUTE FUNCTION public.update_updated_at_column();